@@ -0,0 +1,128 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use matrix::prelude::*;
+use rand::prelude::*;
+use std::collections::VecDeque;
+use tetris::board::{BitBoard, Board};
+use tetris::game::ShapesFactory;
+
+/// Return a 20x10 board with its lower half randomly filled.
+fn random_board() -> Conventional<bool> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut board = Conventional::new((20, 10));
+    for row in 0..10 {
+        for col in 0..10 {
+            board[(row, col)] = rng.gen_bool(0.6);
+        }
+    }
+    board
+}
+
+/// Try every shape at every position of the board, like an AI looking for
+/// placements would.
+fn fits_everywhere<B: Board>(board: &B, factory: &ShapesFactory) -> usize {
+    let mut n = 0;
+    for shape in factory.shapes() {
+        for row in 0..board.rows() as isize {
+            for col in 0..board.columns() as isize {
+                if board.fits(shape, (row, col)) {
+                    n += 1;
+                }
+            }
+        }
+    }
+    n
+}
+
+fn bench_fits(c: &mut Criterion) {
+    let factory = ShapesFactory::new();
+    let board = random_board();
+    let bits = BitBoard::from(&board);
+
+    let mut group = c.benchmark_group("fits");
+    group.bench_function("conventional", |b| {
+        b.iter(|| fits_everywhere(black_box(&board), &factory))
+    });
+    group.bench_function("bit_board", |b| {
+        b.iter(|| fits_everywhere(black_box(&bits), &factory))
+    });
+    group.finish();
+}
+
+/// The clone-based `clear_full_rows` which was replaced by the in-place
+/// version, kept here to compare the two.
+fn clear_full_rows_by_clone(board: &mut Conventional<bool>) -> usize {
+    let mut rows_to_eliminate = VecDeque::<usize>::new();
+    for row in 0..board.rows {
+        if (0..board.columns).all(|col| board[(row, col)]) {
+            rows_to_eliminate.push_back(row);
+        }
+    }
+    let cleared = rows_to_eliminate.len();
+    if cleared == 0 {
+        return 0;
+    }
+
+    let mut new = Conventional::new(board.dimensions());
+    let mut row_src = 0;
+    for row in 0..board.rows {
+        while rows_to_eliminate.front().is_some_and(|r| *r == row_src) {
+            row_src += 1;
+            rows_to_eliminate.pop_front();
+        }
+        if row_src >= board.rows {
+            break;
+        }
+        for col in 0..board.columns {
+            new[(row, col)] = board[(row_src, col)];
+        }
+        row_src += 1;
+    }
+
+    *board = new;
+    cleared
+}
+
+fn bench_clear_large_board(c: &mut Criterion) {
+    let mut board = Conventional::new((200, 10));
+    for row in 0..200 {
+        for col in 0..10 {
+            board[(row, col)] = [150, 151, 170].contains(&row) || (row + col) % 3 != 0;
+        }
+    }
+
+    let mut group = c.benchmark_group("clear_full_rows_200");
+    group.bench_function("clone", |b| {
+        b.iter(|| clear_full_rows_by_clone(&mut black_box(board.clone())))
+    });
+    group.bench_function("in_place", |b| {
+        b.iter(|| black_box(board.clone()).clear_full_rows())
+    });
+    group.finish();
+}
+
+fn bench_clear_full_rows(c: &mut Criterion) {
+    let mut board = random_board();
+    for row in [0, 3, 4, 8] {
+        for col in 0..board.columns {
+            board[(row, col)] = true;
+        }
+    }
+    let bits = BitBoard::from(&board);
+
+    let mut group = c.benchmark_group("clear_full_rows");
+    group.bench_function("conventional", |b| {
+        b.iter(|| black_box(board.clone()).clear_full_rows())
+    });
+    group.bench_function("bit_board", |b| {
+        b.iter(|| black_box(bits.clone()).clear_full_rows())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_fits,
+    bench_clear_full_rows,
+    bench_clear_large_board
+);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tetris::game::{Event, Game, GameConfig, State};
+
+/// The number of ticks of a benchmarked game.
+const TICKS: u64 = 2000;
+
+/// The events played between two ticks, in order. They move and rotate the
+/// shapes around so some rows are cleared, and they are always the same so
+/// the benchmark is reproducible with the seed.
+const EVENTS: [&[Event]; 8] = [
+    &[Event::Left, Event::Left, Event::Rotate],
+    &[Event::SoftDrop],
+    &[Event::Right, Event::Right, Event::Right],
+    &[Event::HardDrop],
+    &[Event::Rotate, Event::Left],
+    &[],
+    &[Event::Hold, Event::Right],
+    &[Event::SoftDrop, Event::SoftDrop],
+];
+
+/// Return a started game with a fixed seed.
+fn game() -> Game {
+    let mut g = Game::with_config(GameConfig::new((22, 10)).seed(42)).unwrap();
+    g.handle_event(Event::Start);
+    g
+}
+
+/// Play [TICKS] ticks of `g`, starting over whenever the game is over, and
+/// return the lines cleared.
+fn play(g: &mut Game) -> u32 {
+    let mut lines = 0;
+    for i in 0..TICKS as usize {
+        g.apply_events(EVENTS[i % EVENTS.len()]);
+        lines += g.tick().lines;
+        if g.state == State::End {
+            g.restart();
+        }
+    }
+    lines
+}
+
+fn bench_ticks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("game");
+    group.throughput(Throughput::Elements(TICKS));
+    group.bench_function("ticks", |b| {
+        b.iter_batched(game, |mut g| play(black_box(&mut g)), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let mut g = game();
+    play(&mut g);
+    c.bench_function("render", |b| b.iter(|| black_box(&g).render()));
+}
+
+criterion_group!(benches, bench_ticks, bench_render);
+criterion_main!(benches);
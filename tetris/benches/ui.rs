@@ -0,0 +1,97 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tetris::game::{Event, Game, GameConfig};
+use tetris::ui::LevelWidget;
+use tui::{buffer::Buffer, layout::Rect, symbols, widgets::Widget};
+
+/// The system allocator counting the allocations, so the frames could be
+/// compared by how much they allocate as well as by time.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Return a game in progress on a large level.
+fn game() -> Game {
+    let mut g = Game::with_config(GameConfig::new((40, 30)).seed(0)).unwrap();
+    g.handle_event(Event::Start);
+    for _ in 0..20 {
+        g.handle_event(Event::HardDrop);
+    }
+    g
+}
+
+/// Draw the level the way the widget used to: copy the level with the shape,
+/// draw it into a buffer of its own and merge that buffer.
+fn render_by_copy(g: &Game, buf: &mut Buffer) {
+    let display = g.render();
+    let mut level_buf = Buffer::empty(Rect::new(
+        0,
+        0,
+        display.columns as u16 * 2,
+        display.rows as u16,
+    ));
+    for r in 0..display.rows {
+        for c in 0..display.columns {
+            if display[(r, c)] {
+                let (x, y) = ((c * 2) as u16, (display.rows - r - 1) as u16);
+                level_buf.get_mut(x, y).set_symbol(symbols::block::FULL);
+                level_buf.get_mut(x + 1, y).set_symbol(symbols::block::FULL);
+            }
+        }
+    }
+    level_buf.resize(Rect {
+        x: 1,
+        y: 1,
+        ..level_buf.area
+    });
+    buf.merge(&level_buf);
+}
+
+fn render_widget(g: &Game, buf: &mut Buffer) {
+    LevelWidget::new(g).render(buf.area, buf);
+}
+
+/// Return the number of allocations of one frame drawn by `render`.
+fn allocations(g: &Game, buf: &mut Buffer, render: fn(&Game, &mut Buffer)) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    render(g, buf);
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_level(c: &mut Criterion) {
+    let g = game();
+    let mut buf = Buffer::empty(Rect::new(0, 0, 62, 42));
+
+    eprintln!(
+        "allocations per frame: copy {}, widget {}",
+        allocations(&g, &mut buf, render_by_copy),
+        allocations(&g, &mut buf, render_widget)
+    );
+
+    let mut group = c.benchmark_group("level");
+    group.bench_function("copy", |b| {
+        b.iter(|| render_by_copy(black_box(&g), &mut buf))
+    });
+    group.bench_function("widget", |b| {
+        b.iter(|| render_widget(black_box(&g), &mut buf))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_level);
+criterion_main!(benches);
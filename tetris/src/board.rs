@@ -0,0 +1,406 @@
+use super::game::Shape;
+use matrix::prelude::*;
+
+/// A Board is the grid of cells a [Shape] is dropped in. The row 0 is the
+/// bottom row of the board.
+pub trait Board: Size {
+    /// Return true if there is a cell at `pos`.
+    fn is_occupied(&self, pos: (usize, usize)) -> bool;
+
+    /// Set or clear the cell at `pos`.
+    fn set(&mut self, pos: (usize, usize), occupied: bool);
+
+    /// Return true if there is a cell at `(row, col)`, or None if it's out of
+    /// the board, e.g. a negative row of a shape sticking out of the bottom.
+    fn get(&self, row: isize, col: isize) -> Option<bool> {
+        checked_pos(self.dimensions(), row, col).map(|pos| self.is_occupied(pos))
+    }
+
+    /// Set or clear the cell at `(row, col)` like [Board::set], it returns
+    /// false and leaves the board as it is if that's out of the board.
+    fn try_set(&mut self, row: isize, col: isize, occupied: bool) -> bool {
+        match checked_pos(self.dimensions(), row, col) {
+            Some(pos) => {
+                self.set(pos, occupied);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all full rows and let the rows above them fall down, it returns
+    /// the number of rows removed.
+    fn clear_full_rows(&mut self) -> usize;
+
+    /// Return true if the given shape could be put at `pos` (the bottom-left
+    /// corner of the shape) without going out of the board or overlapping
+    /// any cells.
+    fn fits(&self, shape: &Shape, pos: (isize, isize)) -> bool {
+        if !in_bound(self.dimensions(), shape, pos) {
+            return false;
+        }
+
+        for hi in 0..shape.height() {
+            for wi in 0..shape.width() {
+                let b_pos = (pos.0 + hi as isize, pos.1 + wi as isize);
+                if shape.cells()[(hi, wi)] && self.get(b_pos.0, b_pos.1) != Some(false) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Return `(row, col)` as a position of a board of the given dimensions, or
+/// None if it's out of the board.
+fn checked_pos(dimensions: (usize, usize), row: isize, col: isize) -> Option<(usize, usize)> {
+    let pos = (usize::try_from(row).ok()?, usize::try_from(col).ok()?);
+    (pos.0 < dimensions.0 && pos.1 < dimensions.1).then_some(pos)
+}
+
+/// Return true if the bounding box of `shape` at `pos` is inside a board of the
+/// given dimensions.
+pub(crate) fn in_bound(dimensions: (usize, usize), shape: &Shape, pos: (isize, isize)) -> bool {
+    let (rows, columns) = (dimensions.0 as isize, dimensions.1 as isize);
+    pos.0 >= 0
+        && pos.1 >= 0
+        && pos.0 + shape.height() as isize <= rows
+        && pos.1 + shape.width() as isize <= columns
+}
+
+/// The default board used by a [Game](super::game::Game).
+impl Board for Conventional<bool> {
+    fn is_occupied(&self, pos: (usize, usize)) -> bool {
+        self[pos]
+    }
+
+    fn set(&mut self, pos: (usize, usize), occupied: bool) {
+        self[pos] = occupied;
+    }
+
+    fn clear_full_rows(&mut self) -> usize {
+        let is_full = |board: &Self, row| (0..board.columns).all(|col| board[(row, col)]);
+        let first = match (0..self.rows).find(|row| is_full(self, *row)) {
+            Some(row) => row,
+            None => return 0,
+        };
+
+        // shift the rows that are kept down in place, from the bottom up, so
+        // the rows below the first full row are never touched.
+        let mut dst = first;
+        for src in first + 1..self.rows {
+            if is_full(self, src) {
+                continue;
+            }
+            for col in 0..self.columns {
+                self[(dst, col)] = self[(src, col)];
+            }
+            dst += 1;
+        }
+
+        for row in dst..self.rows {
+            for col in 0..self.columns {
+                self[(row, col)] = false;
+            }
+        }
+        self.rows - dst
+    }
+}
+
+/// A BitBoard stores each row of a board as the bits of a `u32`, the bit `n`
+/// of a row is the cell in column `n`. It is much faster than
+/// `Conventional<bool>` for collision checks as a whole row of a shape could be
+/// tested with a single AND, so it should be used when simulating many
+/// placements (e.g. an AI). A BitBoard could have at most 32 columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitBoard {
+    columns: usize,
+    rows: Vec<u32>,
+}
+
+impl BitBoard {
+    /// The maximum number of columns a BitBoard could have.
+    pub const MAX_COLUMNS: usize = u32::BITS as usize;
+
+    /// Return an empty BitBoard with the given `(rows, columns)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `columns` is larger than [BitBoard::MAX_COLUMNS].
+    pub fn new(size: (usize, usize)) -> Self {
+        assert!(
+            size.1 <= Self::MAX_COLUMNS,
+            "a BitBoard could have at most {} columns",
+            Self::MAX_COLUMNS
+        );
+        BitBoard {
+            columns: size.1,
+            rows: vec![0; size.0],
+        }
+    }
+
+    /// Return the mask of a full row.
+    fn full_row(&self) -> u32 {
+        if self.columns == Self::MAX_COLUMNS {
+            u32::MAX
+        } else {
+            (1 << self.columns) - 1
+        }
+    }
+
+    /// Return the bits of a row of this board.
+    pub fn row_mask(&self, row: usize) -> u32 {
+        self.rows[row]
+    }
+}
+
+impl Size for BitBoard {
+    fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn columns(&self) -> usize {
+        self.columns
+    }
+}
+
+impl Board for BitBoard {
+    fn is_occupied(&self, pos: (usize, usize)) -> bool {
+        assert!(pos.1 < self.columns, "column {} is out of bound", pos.1);
+        self.rows[pos.0] & (1 << pos.1) != 0
+    }
+
+    fn set(&mut self, pos: (usize, usize), occupied: bool) {
+        assert!(pos.1 < self.columns, "column {} is out of bound", pos.1);
+        if occupied {
+            self.rows[pos.0] |= 1 << pos.1;
+        } else {
+            self.rows[pos.0] &= !(1 << pos.1);
+        }
+    }
+
+    fn clear_full_rows(&mut self) -> usize {
+        let full = self.full_row();
+        let height = self.rows.len();
+        self.rows.retain(|r| *r != full);
+        let cleared = height - self.rows.len();
+        self.rows.resize(height, 0);
+        cleared
+    }
+
+    fn fits(&self, shape: &Shape, pos: (isize, isize)) -> bool {
+        if !in_bound(self.dimensions(), shape, pos) {
+            return false;
+        }
+
+        (0..shape.height())
+            .all(|hi| self.rows[pos.0 as usize + hi] & (shape.row_mask(hi) << pos.1) == 0)
+    }
+}
+
+impl From<&Conventional<bool>> for BitBoard {
+    fn from(board: &Conventional<bool>) -> Self {
+        let mut res = BitBoard::new(board.dimensions());
+        for row in 0..board.rows {
+            for col in 0..board.columns {
+                res.set((row, col), board[(row, col)]);
+            }
+        }
+        res
+    }
+}
+
+impl From<&BitBoard> for Conventional<bool> {
+    fn from(board: &BitBoard) -> Self {
+        let mut res = Conventional::new(board.dimensions());
+        for row in 0..board.rows() {
+            for col in 0..board.columns() {
+                res[(row, col)] = board.is_occupied((row, col));
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ShapesFactory;
+    use rand::prelude::*;
+    use std::collections::VecDeque;
+
+    /// The original implementation of `clear_full_rows` which builds a new
+    /// board, the in-place version must behave exactly like it.
+    fn clear_full_rows_by_clone(board: &mut Conventional<bool>) -> usize {
+        let mut rows_to_eliminate = VecDeque::<usize>::new();
+        for row in 0..board.rows {
+            if (0..board.columns).all(|col| board[(row, col)]) {
+                rows_to_eliminate.push_back(row);
+            }
+        }
+        let cleared = rows_to_eliminate.len();
+
+        let mut new = Conventional::new(board.dimensions());
+        let mut row_src = 0;
+        for row in 0..board.rows {
+            while rows_to_eliminate.front().is_some_and(|r| *r == row_src) {
+                row_src += 1;
+                rows_to_eliminate.pop_front();
+            }
+            if row_src >= board.rows {
+                break;
+            }
+            for col in 0..board.columns {
+                new[(row, col)] = board[(row_src, col)];
+            }
+            row_src += 1;
+        }
+
+        *board = new;
+        cleared
+    }
+
+    /// Return a board with the given rows full and the others half filled.
+    fn board_with_full_rows(size: (usize, usize), full: &[usize]) -> Conventional<bool> {
+        let mut board = Conventional::new(size);
+        for row in 0..size.0 {
+            for col in 0..size.1 {
+                board[(row, col)] = full.contains(&row) || (row + col) % 2 == 0;
+            }
+        }
+        board
+    }
+
+    fn random_board(rng: &mut StdRng, size: (usize, usize)) -> Conventional<bool> {
+        let mut board = Conventional::new(size);
+        for row in 0..size.0 {
+            // make some rows full so clear_full_rows has something to do.
+            let full = rng.gen_bool(0.3);
+            for col in 0..size.1 {
+                board[(row, col)] = full || rng.gen_bool(0.5);
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn clear_full_rows_matches_clone() {
+        let patterns: &[&[usize]] = &[
+            &[],
+            &[0],
+            &[19],
+            &[0, 1, 2, 3],
+            &[0, 3],
+            &[2, 5, 6, 11, 19],
+            &[16, 17, 18, 19],
+            &(0..20).collect::<Vec<_>>(),
+        ];
+        for full in patterns {
+            let mut board = board_with_full_rows((20, 10), full);
+            let mut expected = board.clone();
+            assert_eq!(board.clear_full_rows(), full.len());
+            assert_eq!(clear_full_rows_by_clone(&mut expected), full.len());
+            assert_eq!(board, expected, "clearing rows {:?}", full);
+        }
+    }
+
+    #[test]
+    fn clear_full_rows_keeps_order() {
+        // from the bottom: "#.", "##", ".#", "#."
+        let mut board = Conventional::from_vec(
+            (4, 2),
+            vec![true, true, false, true, false, true, true, false],
+        );
+        assert_eq!(board.clear_full_rows(), 1);
+        let rows: Vec<_> = (0..4)
+            .map(|row| (board[(row, 0)], board[(row, 1)]))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![(true, false), (false, true), (true, false), (false, false)]
+        );
+    }
+
+    #[test]
+    fn bit_board_round_trip() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let board = random_board(&mut rng, (20, 10));
+        let bits = BitBoard::from(&board);
+        assert_eq!(Conventional::from(&bits), board);
+    }
+
+    #[test]
+    fn checked_cells() {
+        let mut board = Conventional::new((3, 4));
+        let mut bits = BitBoard::new((3, 4));
+        for pos in [(-1, 0), (0, -1), (3, 0), (0, 4), (isize::MIN, isize::MAX)] {
+            assert_eq!(board.get(pos.0, pos.1), None);
+            assert!(!board.try_set(pos.0, pos.1, true));
+            assert_eq!(bits.get(pos.0, pos.1), None);
+            assert!(!bits.try_set(pos.0, pos.1, true));
+        }
+        assert_eq!(board, Conventional::new((3, 4)));
+        assert!(board.try_set(2, 3, true));
+        assert_eq!(board.get(2, 3), Some(true));
+        assert_eq!(board.get(0, 0), Some(false));
+        assert!(bits.try_set(2, 3, true));
+        assert_eq!(bits.get(2, 3), Some(true));
+
+        let square: Shape = "##\n##".parse().unwrap();
+        assert!(!board.fits(&square, (-1, 0)));
+        assert!(!board.fits(&square, (0, -1)));
+        assert!(board.fits(&square, (0, 0)));
+    }
+
+    #[test]
+    fn bit_board_set() {
+        let mut bits = BitBoard::new((4, 32));
+        bits.set((3, 31), true);
+        bits.set((0, 0), true);
+        assert!(bits.is_occupied((3, 31)));
+        assert!(bits.is_occupied((0, 0)));
+        assert!(!bits.is_occupied((0, 1)));
+        bits.set((3, 31), false);
+        assert!(!bits.is_occupied((3, 31)));
+        assert_eq!(bits.row_mask(0), 1);
+    }
+
+    #[test]
+    fn bit_board_clear_full_rows_matches_reference() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for columns in [1, 4, 10, 32] {
+            let mut board = random_board(&mut rng, (20, columns));
+            let mut bits = BitBoard::from(&board);
+            assert_eq!(bits.clear_full_rows(), board.clear_full_rows());
+            assert_eq!(Conventional::from(&bits), board);
+        }
+    }
+
+    #[test]
+    fn bit_board_fits_matches_reference() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let factory = ShapesFactory::new();
+        for _ in 0..20 {
+            let board = random_board(&mut rng, (12, 8));
+            let bits = BitBoard::from(&board);
+            for shape in factory.shapes() {
+                let mut shape = shape.clone();
+                for _ in 0..4 {
+                    for row in -2..14 {
+                        for col in -2..10 {
+                            assert_eq!(
+                                bits.fits(&shape, (row, col)),
+                                board.fits(&shape, (row, col)),
+                                "shape\n{}\nat {:?}",
+                                shape,
+                                (row, col)
+                            );
+                        }
+                    }
+                    shape.rotate();
+                }
+            }
+        }
+    }
+}
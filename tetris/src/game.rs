@@ -1,83 +1,398 @@
+use super::board::{self, Board};
 use tetris_macro::shape2;
 use matrix::prelude::*;
 use rand::prelude::*;
-use std::collections::VecDeque;
-use std::convert::identity;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// A Shape is a piece you could control in a Tetris level. A true element means
 /// there is a cell in that position. You could move rotate it in a
 /// Tetris level.
-#[derive(Debug, Clone, PartialEq)]
-struct Shape(Conventional<bool>);
+#[derive(Debug, Clone)]
+pub struct Shape {
+    cells: Conventional<bool>,
+    /// The number of distinct orientations of this shape, it's 1 for the
+    /// square, 2 for shapes looking the same after rotating by 180° (e.g. the
+    /// stick) and 4 for the others.
+    orientations: u8,
+    /// The current orientation, 0 is the orientation the shape was created in
+    /// and it goes up by one every rotation.
+    orientation: u8,
+    /// The point the shape rotates around as `(row, col)` in its own cells,
+    /// it is doubled so it could be in the middle of two cells.
+    pivot: (isize, isize),
+}
+
+/// Return the cells rotated clock-wise by 90°.
+fn rotate_cells(cells: &Conventional<bool>) -> Conventional<bool> {
+    let mut new = Conventional::<bool>::new((cells.columns, cells.rows));
+    for row in 0..new.rows {
+        for col in 0..new.columns {
+            new[(row, col)] = cells[(col, new.rows - row - 1)];
+        }
+    }
+    new
+}
+
+/// The error returned when a [Shape] couldn't be created from a matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeError {
+    /// The matrix has no rows or no columns.
+    Empty,
+    /// The matrix has no cells set.
+    NoCells,
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShapeError::Empty => write!(f, "the shape has no rows or columns"),
+            ShapeError::NoCells => write!(f, "the shape has no cells"),
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {}
 
 impl Shape {
+    /// Return a shape with the cells of `matrix`, it must have at least one
+    /// cell set.
+    pub fn try_new(matrix: Conventional<bool>) -> Result<Self, ShapeError> {
+        if matrix.rows == 0 || matrix.columns == 0 {
+            return Err(ShapeError::Empty);
+        }
+        if !matrix.iter().any(|cell| *cell) {
+            return Err(ShapeError::NoCells);
+        }
+        Ok(Shape::new(matrix))
+    }
+
+    /// Return a shape with `cells` from the bottom-left, a row after another.
+    /// This is used by [shape!] which makes sure there is a cell for each
+    /// position.
+    #[doc(hidden)]
+    pub fn from_rows(size: (usize, usize), cells: &[bool]) -> Self {
+        let matrix = Conventional::from_vec(
+            size,
+            (0..size.1)
+                .flat_map(|col| (0..size.0).map(move |row| cells[row * size.1 + col]))
+                .collect(),
+        );
+        Shape::try_new(matrix).unwrap()
+    }
+
+    /// Return a shape with the cells of `matrix` without checking them, see
+    /// [Shape::try_new].
     fn new(matrix: Conventional<bool>) -> Self {
-        Shape(matrix)
+        let rotated = rotate_cells(&matrix);
+        let orientations = if rotated == matrix {
+            1
+        } else if rotate_cells(&rotated) == matrix {
+            2
+        } else {
+            4
+        };
+        // rotate around the center by default.
+        let pivot = (matrix.rows as isize - 1, matrix.columns as isize - 1);
+        Shape {
+            cells: matrix,
+            orientations,
+            orientation: 0,
+            pivot,
+        }
+    }
+
+    /// Set the cell the shape rotates around.
+    fn with_pivot(mut self, pivot: (isize, isize)) -> Self {
+        self.pivot = (pivot.0 * 2, pivot.1 * 2);
+        self
     }
 
     /// Return the width of this shape
-    fn width(&self) -> usize {
-        self.0.columns
+    pub fn width(&self) -> usize {
+        self.cells.columns
     }
 
     /// Return the height of this shape
-    fn height(&self) -> usize {
-        self.0.rows
+    pub fn height(&self) -> usize {
+        self.cells.rows
     }
 
-    fn cells(&self) -> &Conventional<bool> {
-        &self.0
+    pub fn cells(&self) -> &Conventional<bool> {
+        &self.cells
     }
 
-    /// Rotate the shape clock-wise by 90°.
-    fn rotate(&mut self) {
-        let mut new = Conventional::<bool>::new((self.width(), self.height()));
-        for row in 0..new.rows {
-            for col in 0..new.columns {
-                new[(row, col)] = self.0[(col, new.rows - row - 1)];
-            }
+    /// Return the number of distinct orientations of the shape: 1 for the
+    /// square, 2 for the stick, S and Z and 4 for the others. It's found
+    /// when the shape is created, by rotating it until it looks the same.
+    pub fn rotation_count(&self) -> u8 {
+        self.orientations
+    }
+
+    /// Return the current orientation, it's 0 for the orientation the shape was
+    /// created in and it goes up by one every rotation until it wraps around.
+    pub fn orientation(&self) -> u8 {
+        self.orientation
+    }
+
+    /// Return the cells of a row as bits, the bit `n` is the cell in column `n`.
+    /// This is used by [BitBoard](super::board::BitBoard).
+    pub fn row_mask(&self, row: usize) -> u32 {
+        (0..self.width())
+            .filter(|col| self.cells[(row, *col)])
+            .fold(0, |mask, col| mask | (1 << col))
+    }
+
+    /// Rotate the shape clock-wise by 90°. This does nothing for a shape which
+    /// looks the same after the rotation, e.g. the square.
+    pub fn rotate(&mut self) {
+        self.rotate_around_pivot();
+    }
+
+    /// Rotate the shape like [Shape::rotate], it returns how the bottom-left
+    /// corner should be moved to keep the pivot where it is. A pivot in the
+    /// middle of two cells can't stay exactly, the offset is rounded toward
+    /// zero so rotating back and forth doesn't make the shape drift.
+    fn rotate_around_pivot(&mut self) -> (isize, isize) {
+        if self.orientations == 1 {
+            return (0, 0);
         }
-        self.0 = new;
+        // the cell (row, col) goes to (columns - 1 - col, row).
+        let old = self.pivot;
+        self.pivot = (2 * (self.width() as isize - 1) - old.1, old.0);
+        self.cells = rotate_cells(&self.cells);
+        self.orientation = (self.orientation + 1) % self.orientations;
+        ((old.0 - self.pivot.0) / 2, (old.1 - self.pivot.1) / 2)
+    }
+}
+
+/// Shapes are equal if they have the same cells, whatever orientation they are
+/// in.
+impl PartialEq for Shape {
+    fn eq(&self, other: &Self) -> bool {
+        self.cells == other.cells
     }
 }
 
 impl Eq for Shape {}
 
+/// Shapes are hashed by their size and cells, like they are compared, so the
+/// orientations of a shape looking the same are the same key.
+impl Hash for Shape {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cells.rows.hash(state);
+        self.cells.columns.hash(state);
+        for cell in self.cells.iter() {
+            cell.hash(state);
+        }
+    }
+}
+
+/// Shapes are ordered by their height, then their width and then their cells.
+impl Ord for Shape {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.cells.rows, self.cells.columns)
+            .cmp(&(other.cells.rows, other.cells.columns))
+            .then_with(|| self.cells.iter().cmp(other.cells.iter()))
+    }
+}
+
+impl PartialOrd for Shape {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shapes are displayed as a grid of `#` (cell) and `.` (no cell), with the
+/// top row first.
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in (0..self.height()).rev() {
+            for col in 0..self.width() {
+                let ch = if self.cells[(row, col)] { '#' } else { '.' };
+                write!(f, "{}", ch)?;
+            }
+            if row > 0 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when parsing a [Shape] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseShapeError {
+    /// There are no rows in the input.
+    Empty,
+    /// A row has a different width than the first row.
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A character other than `#` or `.` was found.
+    InvalidChar { row: usize, col: usize, ch: char },
+    /// There are no `#` in the input.
+    NoCells,
+}
+
+impl fmt::Display for ParseShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseShapeError::Empty => write!(f, "the shape has no rows"),
+            ParseShapeError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} columns but {} were expected",
+                row, found, expected
+            ),
+            ParseShapeError::InvalidChar { row, col, ch } => write!(
+                f,
+                "invalid character {:?} at row {}, column {}",
+                ch, row, col
+            ),
+            ParseShapeError::NoCells => write!(f, "the shape has no cells"),
+        }
+    }
+}
+
+impl std::error::Error for ParseShapeError {}
+
+/// Shapes are serialized as the grid of their [fmt::Display] implementation.
+impl Serialize for Shape {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Shape {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parse a shape from the same `#`/`.` grid produced by its [fmt::Display]
+/// implementation. Blank lines before and after the grid are ignored, as is the
+/// whitespace around each row.
+impl FromStr for Shape {
+    type Err = ParseShapeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.lines().map(str::trim).collect();
+        let first = lines.iter().position(|l| !l.is_empty());
+        let last = lines.iter().rposition(|l| !l.is_empty());
+        let rows = match (first, last) {
+            (Some(first), Some(last)) => &lines[first..=last],
+            _ => return Err(ParseShapeError::Empty),
+        };
+
+        let width = rows[0].chars().count();
+        let mut cells = Conventional::new((rows.len(), width));
+        for (row, line) in rows.iter().enumerate() {
+            let found = line.chars().count();
+            if found != width {
+                return Err(ParseShapeError::RaggedRow {
+                    row,
+                    expected: width,
+                    found,
+                });
+            }
+            // rows are written top-first but the matrix keeps the bottom row first.
+            let m_row = rows.len() - row - 1;
+            for (col, ch) in line.chars().enumerate() {
+                cells[(m_row, col)] = match ch {
+                    '#' => true,
+                    '.' => false,
+                    _ => return Err(ParseShapeError::InvalidChar { row, col, ch }),
+                };
+            }
+        }
+
+        Shape::try_new(cells).map_err(|_| ParseShapeError::NoCells)
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
 macro_rules! count_shape_row {
     () => (0);
     ( $($acc:expr),+;) => (1);
-    ( $($head:expr),+; $($($tail:expr),+;)*) => (1 + count_shape_row!($($($tail),+;)*));
+    ( $($head:expr),+; $($($tail:expr),+;)*) => (1 + $crate::count_shape_row!($($($tail),+;)*));
 }
 
+#[doc(hidden)]
+#[macro_export]
 macro_rules! count_shape_col {
-    ( $($head_row:expr),+; $($($tail_row:expr),+;)+) => (count_shape_col!($($head_row),+));
+    ( $($head_row:expr),+; $($($tail_row:expr),+;)*) => ($crate::count_shape_col!($($head_row),+));
     () => (0);
     ( $head:expr ) => (1);
-    ( $head:expr, $($tail:expr),*) => (1+ count_shape_col!($($tail),*));
+    ( $head:expr, $($tail:expr),*) => (1+ $crate::count_shape_col!($($tail),*));
 }
 
+/// Return a [Shape](crate::game::Shape) with the given cells, a row ends
+/// with `;` and the first row is the top one. All rows must have the same
+/// number of cells, or it doesn't compile. [shape2!](crate::shape2) draws the
+/// same shapes with `o` and `_`.
+///
+/// ```
+/// use tetris::{shape, shape2};
+///
+/// let s = shape![
+///     false, true;
+///     true, true;
+/// ];
+/// assert_eq!(s.to_string(), ".#\n##");
+/// assert_eq!(s, shape2! { _o oo });
+/// ```
+#[macro_export]
 macro_rules! shape {
     ( $($head:expr),+; $($($tail:expr),+;)* ) => {
-        shape![ $($head),+; -> [$($($tail),+;)*] ]
+        $crate::shape![ $($head),+; -> [$($($tail),+;)*] ]
     };
     ( $($($acc:expr),+;)* -> [$($head:expr),+; $($($tail:expr),+;)*]) => {
-        shape![ $($head),+; $($($acc),+;)* -> [$($($tail),+;)*]]
+        $crate::shape![ $($head),+; $($($acc),+;)* -> [$($($tail),+;)*]]
     };
     ( $($($acc:expr),+;)* -> [] ) => {
         {
-            const ROWS: usize = count_shape_row!($($($acc),+;)*);
-            const COLS: usize = count_shape_col!($($($acc),+;)*);
+            const ROWS: usize = $crate::count_shape_row!($($($acc),+;)*);
+            const COLS: usize = $crate::count_shape_col!($($($acc),+;)*);
+            const _: () = {
+                $(
+                    assert!(
+                        $crate::count_shape_col!($($acc),+) == COLS,
+                        "all rows of a shape must have the same number of cells"
+                    );
+                )*
+            };
 
-            Shape::new(Conventional::from_vec(
-                (ROWS, COLS),
-                matrix![$($($acc),+;)*] ))
+            // the rows are from the bottom now.
+            $crate::game::Shape::from_rows((ROWS, COLS), &[$($($acc),+),*])
         }
     };
 }
 
+/// Return true if `shape` could be put at `pos` (its bottom-left corner) of
+/// `board`, i.e. it's inside the board and doesn't overlap any cells. Any board
+/// could be used, e.g. one an AI is trying placements on or a custom puzzle.
+pub fn shape_fits(board: &Conventional<bool>, shape: &Shape, pos: (isize, isize)) -> bool {
+    board.fits(shape, pos)
+}
 
 /// The state of the current game
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum State {
     Init,
     Playing,
@@ -85,21 +400,367 @@ pub enum State {
     End,
 }
 
+/// The number of entries kept by [Game::recent_history].
+#[cfg(feature = "event-log")]
+pub const EVENT_LOG_LEN: usize = 64;
+
+/// An event handled by a game, see [Game::recent_history].
+#[cfg(feature = "event-log")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The number of ticks played when the event was handled.
+    pub tick: u32,
+    pub event: Event,
+    /// The state of the game after the event.
+    pub state: State,
+}
+
 /// The event that could happen in a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
     Start,
     Left,
     Right,
     Rotate,
     Pause,
+    /// Drop the shape by one row, without waiting for the next tick.
+    SoftDrop,
+    /// Make the shape fall [SOFT_DROP_MULTIPLIER] times faster while the soft
+    /// drop key is held (true), until it's let go (false), see
+    /// [Game::tick_interval].
+    SoftDropHeld(bool),
+    /// Drop the shape to the floor and lock it at once.
+    HardDrop,
+    /// Put the shape aside and take the held one which has waited the longest
+    /// (or a new one if fewer than [GameConfig::hold_capacity] are held), this
+    /// could be done once per shape.
+    Hold,
+    /// Go back to just before the last shape was locked, if
+    /// [GameConfig::undo] is set.
+    Undo,
+    /// Move the shape sideways as far as it could go, until it's against the
+    /// wall or the cells in the way.
+    MoveToWall(Direction),
+    /// Move the upcoming shape at the given index of the preview (0 is the
+    /// next one) to the front of the queue so it spawns next, the shapes it
+    /// skipped stay behind it in their order. It needs
+    /// [GameConfig::pick_preview].
+    PickPreview(usize),
+    /// Put the falling shape back where it spawned in its spawn orientation,
+    /// to try it again before it's locked, if [GameConfig::retry_piece] is
+    /// set. The rest of the level is left as it is.
+    RetryPiece,
+}
+
+/// A horizontal direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// Return the `(rows, columns)` offset of a move by one cell.
+    fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+/// The internal state of the random number generator of a [ShapesFactory],
+/// restoring it makes the factory create the same shapes again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RngState {
+    seed: [u8; 32],
+    stream: u64,
+    word_pos: u128,
+    /// The kind of the randomizer and what it dealt so far, see
+    /// [Randomizer::state].
+    randomizer: Option<RandomizerKind>,
+    dealt: Vec<usize>,
+}
+
+/// The name of a standard shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum PieceKind {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+impl fmt::Display for PieceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Pick the shapes a [ShapesFactory] creates. The random numbers come from
+/// the factory, so a randomizer plays the same with the same seed and
+/// [ShapesFactory::rng_state] restores it, but it doesn't have to use them,
+/// e.g. a test could deal a fixed sequence.
+pub trait Randomizer: Send {
+    /// Return the index of the next shape of `shapes` to create.
+    fn next(&mut self, shapes: &[Shape], rng: &mut dyn RngCore) -> usize;
+
+    /// Forget what was dealt so far, e.g. the current bag.
+    fn reset(&mut self) {}
+
+    /// Return what was dealt so far which decides the next shapes, e.g. the
+    /// current bag, it's saved with the state of the random number generator.
+    fn state(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// Restore what was dealt to a state returned by [Randomizer::state] of
+    /// the same kind of randomizer, dealing from `shapes` shapes. It resets
+    /// instead if the state doesn't fit them, e.g. it has the index of a
+    /// shape out of them, and a randomizer with no state only resets.
+    fn set_state(&mut self, state: &[usize], shapes: usize) {
+        let _ = (state, shapes);
+        self.reset();
+    }
+
+    /// Return the kind of this randomizer, or None if it isn't one of
+    /// [RandomizerKind]. Only the state of the same kind is restored.
+    fn kind(&self) -> Option<RandomizerKind> {
+        None
+    }
+
+    /// Return the indexes of the shapes of the current bag in the order they
+    /// are dealt and the number of them dealt, or None if there are no bags.
+    fn current_bag(&self) -> Option<(&[usize], usize)> {
+        None
+    }
+
+    /// Return a boxed clone of this randomizer, so a [ShapesFactory] could be
+    /// cloned.
+    fn box_clone(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Pick each shape at random, a shape could be missing for a long time.
+#[derive(Debug, Clone, Default)]
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next(&mut self, shapes: &[Shape], rng: &mut dyn RngCore) -> usize {
+        rng.gen_range(0..shapes.len())
+    }
+
+    fn kind(&self) -> Option<RandomizerKind> {
+        Some(RandomizerKind::PureRandom)
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// Deal the shapes in bags of one of each shape in a random order (the
+/// "7-bag" of the standard shapes), so a shape never waits for long.
+#[derive(Debug, Clone, Default)]
+pub struct SevenBag {
+    /// The indexes of the shapes of the current bag in the order they are
+    /// dealt, and the number of them dealt.
+    bag: Vec<usize>,
+    dealt: usize,
+}
+
+impl Randomizer for SevenBag {
+    fn next(&mut self, shapes: &[Shape], rng: &mut dyn RngCore) -> usize {
+        if self.dealt == self.bag.len() {
+            self.bag = (0..shapes.len()).collect();
+            self.bag.shuffle(rng);
+            self.dealt = 0;
+        }
+        self.dealt += 1;
+        self.bag[self.dealt - 1]
+    }
+
+    fn reset(&mut self) {
+        self.bag.clear();
+        self.dealt = 0;
+    }
+
+    /// The number of shapes dealt and the bag.
+    fn state(&self) -> Vec<usize> {
+        std::iter::once(self.dealt)
+            .chain(self.bag.iter().copied())
+            .collect()
+    }
+
+    fn set_state(&mut self, state: &[usize], shapes: usize) {
+        match state.split_first() {
+            Some((dealt, bag)) if *dealt <= bag.len() && bag.iter().all(|i| *i < shapes) => {
+                self.bag = bag.to_vec();
+                self.dealt = *dealt;
+            }
+            _ => self.reset(),
+        }
+    }
+
+    fn current_bag(&self) -> Option<(&[usize], usize)> {
+        Some((&self.bag, self.dealt))
+    }
+
+    fn kind(&self) -> Option<RandomizerKind> {
+        Some(RandomizerKind::SevenBag)
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// Pick a shape at random, but roll again up to `tries` times while it's one
+/// of the last `len` shapes dealt, like the TGM games, so the same shapes
+/// rarely come close together while the order stays unpredictable.
+#[derive(Debug, Clone)]
+pub struct HistoryBased {
+    history: VecDeque<usize>,
+    len: usize,
+    tries: u32,
+}
+
+impl HistoryBased {
+    pub fn new(len: usize, tries: u32) -> Self {
+        HistoryBased {
+            history: VecDeque::with_capacity(len),
+            len,
+            tries,
+        }
+    }
+}
+
+/// The history of the first TGM game: the last 4 shapes and 4 tries.
+impl Default for HistoryBased {
+    fn default() -> Self {
+        Self::new(4, 4)
+    }
+}
+
+impl Randomizer for HistoryBased {
+    fn next(&mut self, shapes: &[Shape], rng: &mut dyn RngCore) -> usize {
+        let mut index = rng.gen_range(0..shapes.len());
+        for _ in 1..self.tries {
+            if !self.history.contains(&index) {
+                break;
+            }
+            index = rng.gen_range(0..shapes.len());
+        }
+        if self.len > 0 {
+            if self.history.len() == self.len {
+                self.history.pop_front();
+            }
+            self.history.push_back(index);
+        }
+        index
+    }
+
+    fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// The history, the oldest shape first.
+    fn state(&self) -> Vec<usize> {
+        self.history.iter().copied().collect()
+    }
+
+    fn set_state(&mut self, state: &[usize], shapes: usize) {
+        if state.iter().any(|i| *i >= shapes) {
+            self.reset();
+            return;
+        }
+        let start = state.len().saturating_sub(self.len);
+        self.history = state[start..].iter().copied().collect();
+    }
+
+    fn kind(&self) -> Option<RandomizerKind> {
+        Some(RandomizerKind::HistoryBased)
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// The randomizers a [GameConfig] could pick, see [Randomizer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    /// See [PureRandom].
+    #[default]
+    PureRandom,
+    /// See [SevenBag].
+    SevenBag,
+    /// See [HistoryBased], with its default history.
+    HistoryBased,
+}
+
+impl RandomizerKind {
+    /// Return a new randomizer of this kind.
+    pub fn build(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::PureRandom => Box::new(PureRandom),
+            RandomizerKind::SevenBag => Box::new(SevenBag::default()),
+            RandomizerKind::HistoryBased => Box::new(HistoryBased::default()),
+        }
+    }
 }
 
+/// Cloning a factory clones the state of its random number generator and its
+/// randomizer, so the clone creates the same shapes as the original.
+#[derive(Clone)]
 pub struct ShapesFactory {
     shapes: Vec<Shape>,
+    /// The kind of each shape, it's empty for custom shapes.
+    kinds: Vec<PieceKind>,
+    rng: ChaCha12Rng,
+    randomizer: Box<dyn Randomizer>,
+    /// The number of times each shape was drawn from the random number
+    /// generator.
+    draws: Vec<u32>,
+    /// The kinds of the current bag of the randomizer, it's empty for custom
+    /// shapes.
+    bag_kinds: Vec<PieceKind>,
+}
+
+impl Default for ShapesFactory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ShapesFactory {
     pub fn new() -> Self {
+        Self::with_rng(ChaCha12Rng::from_entropy())
+    }
+
+    /// Return a factory which always creates the same sequence of shapes for
+    /// the same seed.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(ChaCha12Rng::seed_from_u64(seed))
+    }
+
+    /// The shapes spawn in the guideline orientations, with their flat side
+    /// down, and rotate around these pivots, so they stay where they are like
+    /// in SRS:
+    /// - the square doesn't rotate.
+    /// - the stick rotates around its middle, between the second and the third
+    ///   cell.
+    /// - J, L, S, Z and T rotate around the middle cell of their 3 cells long
+    ///   side, e.g. the center cell of the T.
+    fn with_rng(rng: ChaCha12Rng) -> Self {
         let shapes = vec![
             // square
             shape![
@@ -108,344 +769,6841 @@ impl ShapesFactory {
             ],
             // stick
             shape2!{
-                o
-                o
-                o
-                o
+                oooo
             },
             // J
             shape2!{
                 o__
                 ooo
-            },
+            }
+            .with_pivot((0, 1)),
             // L
             shape2!{
                 __o
                 ooo
-            },
+            }
+            .with_pivot((0, 1)),
             // S
             shape2!{
                 _oo
                 oo_
-            },
+            }
+            .with_pivot((0, 1)),
             // Z
             shape2!{
                 oo_
                 _oo
-            },
+            }
+            .with_pivot((0, 1)),
             // T
             shape2!{
                 _o_
                 ooo
-            },
+            }
+            .with_pivot((0, 1)),
+        ];
+
+        let kinds = vec![
+            PieceKind::O,
+            PieceKind::I,
+            PieceKind::J,
+            PieceKind::L,
+            PieceKind::S,
+            PieceKind::Z,
+            PieceKind::T,
         ];
 
-        ShapesFactory { shapes }
+        ShapesFactory {
+            draws: vec![0; shapes.len()],
+            shapes,
+            kinds,
+            rng,
+            randomizer: Box::new(PureRandom),
+            bag_kinds: Vec::new(),
+        }
+    }
+
+    /// Pick the shapes with `randomizer` from now on.
+    pub fn set_randomizer(&mut self, randomizer: Box<dyn Randomizer>) {
+        self.randomizer = randomizer;
+        self.bag_kinds.clear();
+    }
+
+    /// Return all the shapes this factory could create.
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
     }
 
-    fn create_shape(&self) -> Shape {
-        let sel = thread_rng().gen_range(0..self.shapes.len());
+    /// Return the kind of each shape in [ShapesFactory::shapes].
+    pub fn kinds(&self) -> &[PieceKind] {
+        &self.kinds
+    }
+
+    /// Return the kind of the shape at `index` of [ShapesFactory::shapes], or
+    /// None if it's a custom shape.
+    pub fn kind(&self, index: usize) -> Option<PieceKind> {
+        self.kinds.get(index).copied()
+    }
+
+    pub fn create_shape(&mut self) -> Shape {
+        let sel = self.next_index();
         self.shapes[sel].clone()
     }
+
+    /// Return the index of the next shape to create.
+    fn next_index(&mut self) -> usize {
+        let index = self.randomizer.next(&self.shapes, &mut self.rng);
+        assert!(index < self.shapes.len(), "no shape at index {}", index);
+        if let Some((_, 1)) = self.randomizer.current_bag() {
+            self.update_bag_kinds();
+        }
+        self.draws[index] += 1;
+        index
+    }
+
+    /// Set [ShapesFactory::current_bag] to the bag of the randomizer.
+    fn update_bag_kinds(&mut self) {
+        self.bag_kinds = match self.randomizer.current_bag() {
+            Some((bag, dealt)) if dealt > 0 => bag
+                .iter()
+                .filter_map(|i| self.kinds.get(*i).copied())
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    /// Return the kinds of the shapes of the current bag of a randomizer
+    /// dealing bags, e.g. [SevenBag], in the order they are dealt, the ones
+    /// dealt already and the ones left. It's empty until the first shape is
+    /// dealt, or if the bags aren't used or the shapes are custom ones.
+    pub fn current_bag(&self) -> &[PieceKind] {
+        &self.bag_kinds
+    }
+
+    /// Return the number of shapes of [ShapesFactory::current_bag] dealt.
+    pub fn bag_dealt(&self) -> usize {
+        self.randomizer.current_bag().map_or(0, |(_, dealt)| dealt)
+    }
+
+    /// Return the number of shapes drawn from the random number generator so
+    /// far, the shapes of [GameConfig::piece_sequence] aren't drawn.
+    pub fn draw_count(&self) -> u32 {
+        self.draws.iter().sum()
+    }
+
+    /// Return the number of shapes drawn of each kind, the kinds never drawn
+    /// are 0 and custom shapes aren't counted.
+    pub fn tally(&self) -> HashMap<PieceKind, u32> {
+        self.kinds
+            .iter()
+            .copied()
+            .zip(self.draws.iter().copied())
+            .collect()
+    }
+
+    /// Return the state of the random number generator.
+    pub fn rng_state(&self) -> RngState {
+        RngState {
+            seed: self.rng.get_seed(),
+            stream: self.rng.get_stream(),
+            word_pos: self.rng.get_word_pos(),
+            randomizer: self.randomizer.kind(),
+            dealt: self.randomizer.state(),
+        }
+    }
+
+    /// Restore the random number generator to a state returned by
+    /// [ShapesFactory::rng_state], with what the randomizer dealt, e.g. the
+    /// rest of the current bag is dealt before a new one is shuffled. The
+    /// randomizer starts over if the state is of another kind of randomizer.
+    pub fn set_rng_state(&mut self, state: &RngState) {
+        self.rng = ChaCha12Rng::from_seed(state.seed);
+        self.rng.set_stream(state.stream);
+        self.rng.set_word_pos(state.word_pos);
+        if state.randomizer == self.randomizer.kind() {
+            self.randomizer.set_state(&state.dealt, self.shapes.len());
+        } else {
+            self.randomizer.reset();
+        }
+        self.update_bag_kinds();
+    }
 }
 
 #[derive(Debug, Clone)]
 struct ShapeInLevel {
     /// The shape
     shape: Shape,
+    /// The index of the shape in [ShapesFactory], the shape could be rotated so
+    /// this is used to get its spawn orientation.
+    index: usize,
     /// The position in the level. Note the position indicates where the
     /// bottom-left corner of the shape is in the level.
     pos: (isize, isize),
 }
 
-/// A game represents a game
-pub struct Game {
-    shape: Option<ShapeInLevel>,
+impl ShapeInLevel {
+    /// Return the positions in the level of the cells of the shape.
+    fn cells(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
+        let cells = self.shape.cells();
+        (0..cells.rows)
+            .flat_map(move |hi| (0..cells.columns).map(move |wi| (hi, wi)))
+            .filter(move |pos| cells[*pos])
+            .map(move |(hi, wi)| (self.pos.0 + hi as isize, self.pos.1 + wi as isize))
+    }
+}
 
-    pub state: State,
-    /// What state the game is currently in.
+/// The offsets `(row, column)` tried in order when a rotated shape doesn't fit
+/// where it is: first sideways, then upward for shapes resting on the floor or
+/// the stack.
+const KICKS: [(isize, isize); 8] = [
+    (0, 0),
+    (0, -1),
+    (0, 1),
+    (0, -2),
+    (0, 2),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+];
 
-    /// This matrix represents the cells in a level.
-    pub level: Conventional<bool>,
+/// A place the shape could be locked in, see [Game::enumerate_placements].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// The number of times the shape is rotated.
+    pub rotation: u8,
+    /// How many columns the shape is moved by after it's rotated, to the
+    /// right if it's positive.
+    pub col_offset: isize,
+    /// Where the bottom-left corner of the rotated shape lands.
+    pub landing_pos: (isize, isize),
+}
 
-    /// This is used to create shapes
-    shapes_factory: ShapesFactory,
+/// The inputs used to place a shape, see [GameConfig::track_finesse].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Finesse {
+    /// The number of moves and rotations used before the shape was locked.
+    pub inputs: u32,
+    /// The fewest moves and rotations placing the shape there from where it
+    /// spawned, or None if it couldn't be placed there without dropping it
+    /// first, e.g. when it's tucked under an overhang.
+    pub optimal: Option<u32>,
 }
 
-impl Game {
-    /// Return a new Game with the given height and width.
-    pub fn new(size: (usize, usize)) -> Game {
-        Game {
-            shape: None,
-            state: State::Init,
-            level: Conventional::new(size),
-            shapes_factory: ShapesFactory::new(),
+impl Finesse {
+    /// Return true if the shape was placed with the fewest inputs.
+    pub fn is_optimal(&self) -> bool {
+        self.optimal.is_some_and(|optimal| self.inputs <= optimal)
+    }
+}
+
+/// The reason a shape couldn't be moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// The shape would go out of the level.
+    OutOfBounds,
+    /// The shape would overlap cells in the level.
+    Collision,
+    /// The game isn't in [State::Playing].
+    NotPlaying,
+    /// There is no shape, i.e. the next one hasn't spawned yet.
+    NoShape,
+    /// The game plays with custom shapes, which have no kind.
+    UnknownPiece { kind: PieceKind },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::OutOfBounds => write!(f, "the shape would go out of the level"),
+            MoveError::Collision => write!(f, "the shape would overlap cells in the level"),
+            MoveError::NotPlaying => write!(f, "the game isn't being played"),
+            MoveError::NoShape => write!(f, "there is no shape to move"),
+            MoveError::UnknownPiece { kind } => {
+                write!(f, "the piece {} can't be played with custom shapes", kind)
+            }
         }
     }
+}
 
-    /// Handle a game event, it returns false if we should quit the game.
-    pub fn handle_event(&mut self, e: Event) -> bool {
-        match e {
-            Event::Start => match self.state {
-                State::Init | State::End => {
-                    self.reset();
-                    true
-                }
-                State::Paused => {
-                    self.state = State::Playing;
-                    true
+impl std::error::Error for MoveError {}
+
+/// The `(height, width)` of the level of [GameConfig::default], the standard
+/// 20 rows of 10 cells. Sizes are always given as `(rows, columns)`.
+pub const DEFAULT_SIZE: (usize, usize) = (20, 10);
+
+/// The maximum number of upcoming shapes that could be shown.
+pub const MAX_PREVIEW: usize = 5;
+
+/// The number of lines to clear to go to the next level.
+const LINES_PER_LEVEL: u32 = 10;
+
+/// The maximum number of locks that could be undone.
+pub const UNDO_LIMIT: usize = 20;
+
+/// How many times faster the shapes fall while the soft drop is held, see
+/// [Event::SoftDropHeld].
+pub const SOFT_DROP_MULTIPLIER: u32 = 20;
+
+/// The points scored in a game, see [GameConfig::score_table].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreTable {
+    /// The points for clearing 0 to 4 lines at once at level 0, they are
+    /// multiplied by the level + 1. Clearing more than 4 lines (with a custom
+    /// shape) scores like 4.
+    pub lines: [u32; 5],
+    /// The points for each row a shape is dropped by [Event::SoftDrop].
+    pub soft_drop: u32,
+    /// The points for each row a shape is dropped by [Event::HardDrop].
+    pub hard_drop: u32,
+}
+
+impl ScoreTable {
+    /// Return the points of the Tetris guideline.
+    pub const fn guideline() -> Self {
+        ScoreTable {
+            lines: [0, 100, 300, 500, 800],
+            soft_drop: 1,
+            hard_drop: 2,
+        }
+    }
+
+    /// Return the points for clearing `lines` lines at once at `level`.
+    fn clear(&self, lines: u32, level: u32) -> u32 {
+        self.lines[(lines as usize).min(self.lines.len() - 1)] * (level + 1)
+    }
+}
+
+impl Default for ScoreTable {
+    fn default() -> Self {
+        Self::guideline()
+    }
+}
+
+/// What happened in a tick or a drop.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TickOutcome {
+    /// The number of rows the shape was dropped by.
+    pub distance: u32,
+    /// Whether the shape was locked into the level.
+    pub locked: bool,
+    /// The number of lines cleared after the shape was locked.
+    pub lines: u32,
+    /// The rows cleared (0 is the bottom one), as they were in the level
+    /// before they were cleared, e.g. to flash them before the rows above
+    /// fall. With [ClearRule::Cascade] the rows of each pass follow those of
+    /// the pass before, as they were after it.
+    pub cleared_rows: Vec<usize>,
+    /// The number of holes (see [Game::hole_count]) the shape locked made,
+    /// counted before the rows are cleared.
+    pub new_holes: usize,
+}
+
+/// How the tick interval changes as the level goes up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GravityCurve {
+    /// The tick interval never changes.
+    Constant,
+    /// The tick interval gets shorter by `step` every level, but never shorter
+    /// than `min`. This is what the [Difficulty] presets use.
+    Linear {
+        #[serde(with = "millis")]
+        step: Duration,
+        #[serde(with = "millis")]
+        min: Duration,
+    },
+    /// The speeds of the classic NES game, see [CLASSIC_FRAMES]. The base
+    /// tick is ignored.
+    Classic,
+    /// The speeds of the Tetris guideline, `(0.8 - (n - 1) * 0.007)^(n - 1)`
+    /// seconds at the guideline level `n`, which is one more than the level
+    /// of the game. The base tick is ignored.
+    Guideline,
+    /// Any function of the level. It couldn't be serialized.
+    #[serde(skip)]
+    Custom(GravityFn),
+}
+
+/// A function returning the tick interval at a level for
+/// [GravityCurve::Custom]. Two of them are only equal if they are clones of
+/// each other.
+#[derive(Clone)]
+pub struct GravityFn(Arc<dyn Fn(u32) -> Duration + Send + Sync>);
+
+impl GravityFn {
+    pub fn new(f: impl Fn(u32) -> Duration + Send + Sync + 'static) -> Self {
+        GravityFn(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for GravityFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("GravityFn")
+    }
+}
+
+impl PartialEq for GravityFn {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for GravityFn {}
+
+/// The frames (at 60 frames per second) a shape takes to drop a row at each
+/// level of [GravityCurve::Classic], the last one is used for all levels after.
+pub const CLASSIC_FRAMES: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+
+impl GravityCurve {
+    /// Return the tick interval at the given level.
+    pub fn interval(&self, base_tick: Duration, level: u32) -> Duration {
+        match self {
+            GravityCurve::Constant => base_tick,
+            GravityCurve::Linear { step, min } => base_tick.saturating_sub(*step * level).max(*min),
+            GravityCurve::Classic => {
+                let frames = CLASSIC_FRAMES[(level as usize).min(CLASSIC_FRAMES.len() - 1)];
+                Duration::from_secs(1) * frames / 60
+            }
+            GravityCurve::Guideline => {
+                let n = level.min(30) as f64;
+                Duration::from_secs_f64((0.8 - n * 0.007).powf(n))
+            }
+            GravityCurve::Custom(f) => (f.0)(level),
+        }
+    }
+}
+
+/// The rules deciding when a game ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Play until the level is filled up.
+    #[default]
+    Marathon,
+    /// Clear the given number of lines as fast as possible.
+    Sprint { lines: u32 },
+    /// Play without an end: when a shape doesn't fit, the bottom rows are
+    /// thrown away until it does.
+    Zen,
+    /// Play until the level is filled up while a row of garbage rises from
+    /// the floor every `interval` of play time, see [Game::add_garbage].
+    Survival { interval: Duration, gap: GarbageGap },
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Sprint { .. } => "Sprint",
+            GameMode::Zen => "Zen",
+            GameMode::Survival { .. } => "Survival",
+        }
+    }
+}
+
+/// Which column of a garbage row is left empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GarbageGap {
+    /// A random column for every row, it's the same every game with
+    /// [GameConfig::seed].
+    Random,
+    /// Always the given column, from the left.
+    Fixed(usize),
+    /// A random column like [GarbageGap::Random], but never the same one for
+    /// more than `max_repeats` rows in a row, so the stack doesn't become a
+    /// single well.
+    Cheese { max_repeats: usize },
+}
+
+/// What happens to the cells above the rows cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClearRule {
+    /// All the rows above a cleared row move down by one, even if the cells
+    /// below them are empty.
+    #[default]
+    Naive,
+    /// The rows move down like [ClearRule::Naive], then every group of
+    /// connected cells falls on its own until it rests on another cell or
+    /// the floor, and the rows full after that are cleared too, until no more
+    /// cells fall (the "sticky" gravity).
+    Cascade,
+}
+
+/// Which way the shapes fall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GravityDirection {
+    /// The shapes spawn at the top and fall to the floor at the bottom.
+    #[default]
+    Down,
+    /// The shapes spawn at the bottom and rise to the top, which is the floor
+    /// the rows collapse towards (a mirror world).
+    Up,
+}
+
+impl GravityDirection {
+    /// Return the `(rows, columns)` offset of a drop by one row.
+    fn offset(self) -> (isize, isize) {
+        match self {
+            GravityDirection::Down => (-1, 0),
+            GravityDirection::Up => (1, 0),
+        }
+    }
+}
+
+/// The configuration of a [Game]. It is built with [GameConfig::new] and the
+/// builder methods, e.g.
+/// `GameConfig::new((22, 16)).base_tick(Duration::from_millis(300))`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameConfig {
+    /// The `(height, width)` of the level.
+    pub size: (usize, usize),
+    /// The tick interval at level 0.
+    #[serde(with = "millis")]
+    pub base_tick: Duration,
+    /// The level a game starts from.
+    pub start_level: u32,
+    /// How the tick interval changes as the level goes up.
+    pub gravity: GravityCurve,
+    /// The rules deciding when the game ends.
+    pub mode: GameMode,
+    /// The seed of the shapes, a random one is used if it's None.
+    pub seed: Option<u64>,
+    /// The number of upcoming shapes shown to the player, it's between 1 and
+    /// [MAX_PREVIEW].
+    pub preview_count: usize,
+    /// The number of ticks a shape waits on the floor before it's locked, 0
+    /// locks it on the first tick it couldn't drop.
+    pub lock_delay_ticks: u32,
+    /// How many times moving or rotating a shape could restart its lock delay,
+    /// the shape is locked when the delay runs out after that.
+    pub max_lock_resets: u32,
+    /// The number of ticks the game waits after a shape is locked (the entry
+    /// delay), there is no shape in the meantime and the next one spawns on
+    /// the tick after them. 0 spawns it right away.
+    pub spawn_delay_ticks: u32,
+    /// The number of ticks the full rows stay in the level after a shape is
+    /// locked before they are cleared, e.g. so they could be animated, see
+    /// [Game::pending_clear_rows]. The spawn delay starts after them. 0
+    /// clears them right away.
+    pub clear_delay_ticks: u32,
+    /// The number of ticks a game gets to make room for a shape which doesn't
+    /// fit when it spawns before it's over, every one of them lets the
+    /// floating cells fall by a row and clears the full rows. 0 ends the game
+    /// right away.
+    pub top_out_grace_ticks: u32,
+    /// The shapes to play with, the standard seven are used if it's None.
+    pub shapes: Option<Vec<Shape>>,
+    /// The cells the level starts with instead of an empty one, a row for
+    /// each row of the level from the bottom. It could have fewer rows than
+    /// the level, the rows above it are empty.
+    pub initial_board: Option<Vec<Vec<bool>>>,
+    /// The cells of the level which are blocked for the whole game (e.g. to
+    /// make an hourglass-shaped well), in the same rows as
+    /// [GameConfig::initial_board]. They are cells no shape could enter, but
+    /// they don't need to be filled to clear a row and they are never cleared
+    /// or moved.
+    pub blocked_mask: Option<Vec<Vec<bool>>>,
+    /// The indexes of the shapes to play in order instead of random ones, it
+    /// starts over when all of them are played.
+    pub piece_sequence: Option<Vec<usize>>,
+    /// Drop the shape to the floor on every tick instead of by one row (the
+    /// "20G" gravity), so only the lock delay leaves time to move it.
+    pub instant_drop: bool,
+    /// Keep the last [UNDO_LIMIT] games before a shape is locked so
+    /// [Event::Undo] could go back to them, this is meant for practice.
+    pub undo: bool,
+    /// What happens to the cells above the rows cleared.
+    pub clear_rule: ClearRule,
+    /// Which way the shapes fall, everything else follows it: where they
+    /// spawn, where they land and which way the rows collapse.
+    pub gravity_direction: GravityDirection,
+    /// The kind of the first shape of every game, the shapes after it are
+    /// random again. It needs the standard shapes.
+    pub first_piece: Option<PieceKind>,
+    /// The points scored for clearing lines and dropping shapes.
+    pub score_table: ScoreTable,
+    /// Let a shape moved sideways into a cell which is filled slide a row
+    /// down instead if it fits there, e.g. into a notch, rather than the move
+    /// failing.
+    pub slide_into_gaps: bool,
+    /// Lock a shape as soon as a soft drop puts it on the stack or the floor,
+    /// instead of waiting for the [GameConfig::lock_delay_ticks] like a shape
+    /// landing by itself.
+    pub lock_on_soft_drop_contact: bool,
+    /// How many shapes could be held at once, 0 disables [Event::Hold].
+    pub hold_capacity: usize,
+    /// Allow [Event::PickPreview], which isn't in the standard rules but
+    /// makes a casual game more forgiving.
+    pub pick_preview: bool,
+    /// Allow [Event::RetryPiece], for practicing.
+    pub retry_piece: bool,
+    /// Hide the locked cells from [Game::render] and [Game::rows] once they
+    /// have been in the level for this many ticks, they still block the
+    /// shapes like any other cell. The cells of the initial board and the
+    /// ones moved by a clear are hidden at once.
+    pub invisible_after_ticks: Option<u32>,
+    /// With [GameConfig::invisible_after_ticks], show the whole level for
+    /// this many ticks after rows are cleared.
+    pub clear_flash_ticks: u32,
+    /// Count the moves and rotations used for each shape and compare them to
+    /// the fewest which place it where it's locked (the "finesse"), see
+    /// [Game::last_finesse].
+    pub track_finesse: bool,
+    /// How the shapes are picked, e.g. in bags of one of each shape with
+    /// [RandomizerKind::SevenBag].
+    pub randomizer: RandomizerKind,
+}
+
+/// The standard game: a level of [DEFAULT_SIZE], i.e. 20 rows and 10 columns,
+/// with [GravityCurve::Classic], 3 upcoming shapes shown and random shapes.
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig::new(DEFAULT_SIZE).gravity(GravityCurve::Classic)
+    }
+}
+
+impl GameConfig {
+    /// Return the default configuration for a level with the given height and
+    /// width.
+    pub fn new(size: (usize, usize)) -> Self {
+        Difficulty::Normal.configure(GameConfig {
+            size,
+            base_tick: Duration::ZERO,
+            start_level: 0,
+            gravity: GravityCurve::Constant,
+            mode: GameMode::Marathon,
+            seed: None,
+            preview_count: 3,
+            lock_delay_ticks: 0,
+            max_lock_resets: 15,
+            spawn_delay_ticks: 0,
+            clear_delay_ticks: 0,
+            top_out_grace_ticks: 0,
+            shapes: None,
+            initial_board: None,
+            blocked_mask: None,
+            piece_sequence: None,
+            instant_drop: false,
+            undo: false,
+            clear_rule: ClearRule::Naive,
+            gravity_direction: GravityDirection::Down,
+            first_piece: None,
+            score_table: ScoreTable::guideline(),
+            slide_into_gaps: false,
+            lock_on_soft_drop_contact: false,
+            hold_capacity: 1,
+            pick_preview: false,
+            retry_piece: false,
+            invisible_after_ticks: None,
+            clear_flash_ticks: 0,
+            track_finesse: false,
+            randomizer: RandomizerKind::PureRandom,
+        })
+    }
+
+    pub fn base_tick(mut self, base_tick: Duration) -> Self {
+        self.base_tick = base_tick;
+        self
+    }
+
+    pub fn start_level(mut self, start_level: u32) -> Self {
+        self.start_level = start_level;
+        self
+    }
+
+    pub fn gravity(mut self, gravity: GravityCurve) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn mode(mut self, mode: GameMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the number of upcoming shapes shown, it's clamped to between 1 and
+    /// [MAX_PREVIEW].
+    pub fn preview_count(mut self, preview_count: usize) -> Self {
+        self.preview_count = preview_count.clamp(1, MAX_PREVIEW);
+        self
+    }
+
+    pub fn lock_delay_ticks(mut self, lock_delay_ticks: u32) -> Self {
+        self.lock_delay_ticks = lock_delay_ticks;
+        self
+    }
+
+    pub fn max_lock_resets(mut self, max_lock_resets: u32) -> Self {
+        self.max_lock_resets = max_lock_resets;
+        self
+    }
+
+    pub fn spawn_delay_ticks(mut self, spawn_delay_ticks: u32) -> Self {
+        self.spawn_delay_ticks = spawn_delay_ticks;
+        self
+    }
+
+    pub fn clear_delay_ticks(mut self, clear_delay_ticks: u32) -> Self {
+        self.clear_delay_ticks = clear_delay_ticks;
+        self
+    }
+
+    pub fn top_out_grace_ticks(mut self, top_out_grace_ticks: u32) -> Self {
+        self.top_out_grace_ticks = top_out_grace_ticks;
+        self
+    }
+
+    pub fn shapes(mut self, shapes: Vec<Shape>) -> Self {
+        self.shapes = Some(shapes);
+        self
+    }
+
+    pub fn initial_board(mut self, initial_board: Vec<Vec<bool>>) -> Self {
+        self.initial_board = Some(initial_board);
+        self
+    }
+
+    pub fn blocked_mask(mut self, blocked_mask: Vec<Vec<bool>>) -> Self {
+        self.blocked_mask = Some(blocked_mask);
+        self
+    }
+
+    pub fn piece_sequence(mut self, piece_sequence: Vec<usize>) -> Self {
+        self.piece_sequence = Some(piece_sequence);
+        self
+    }
+
+    pub fn instant_drop(mut self, instant_drop: bool) -> Self {
+        self.instant_drop = instant_drop;
+        self
+    }
+
+    pub fn undo(mut self, undo: bool) -> Self {
+        self.undo = undo;
+        self
+    }
+
+    pub fn clear_rule(mut self, clear_rule: ClearRule) -> Self {
+        self.clear_rule = clear_rule;
+        self
+    }
+
+    pub fn gravity_direction(mut self, gravity_direction: GravityDirection) -> Self {
+        self.gravity_direction = gravity_direction;
+        self
+    }
+
+    pub fn first_piece(mut self, first_piece: PieceKind) -> Self {
+        self.first_piece = Some(first_piece);
+        self
+    }
+
+    pub fn score_table(mut self, score_table: ScoreTable) -> Self {
+        self.score_table = score_table;
+        self
+    }
+
+    pub fn slide_into_gaps(mut self, slide_into_gaps: bool) -> Self {
+        self.slide_into_gaps = slide_into_gaps;
+        self
+    }
+
+    pub fn lock_on_soft_drop_contact(mut self, lock_on_soft_drop_contact: bool) -> Self {
+        self.lock_on_soft_drop_contact = lock_on_soft_drop_contact;
+        self
+    }
+
+    pub fn hold_capacity(mut self, hold_capacity: usize) -> Self {
+        self.hold_capacity = hold_capacity;
+        self
+    }
+
+    pub fn pick_preview(mut self, pick_preview: bool) -> Self {
+        self.pick_preview = pick_preview;
+        self
+    }
+
+    pub fn retry_piece(mut self, retry_piece: bool) -> Self {
+        self.retry_piece = retry_piece;
+        self
+    }
+
+    pub fn invisible_after_ticks(mut self, invisible_after_ticks: u32) -> Self {
+        self.invisible_after_ticks = Some(invisible_after_ticks);
+        self
+    }
+
+    pub fn clear_flash_ticks(mut self, clear_flash_ticks: u32) -> Self {
+        self.clear_flash_ticks = clear_flash_ticks;
+        self
+    }
+
+    pub fn track_finesse(mut self, track_finesse: bool) -> Self {
+        self.track_finesse = track_finesse;
+        self
+    }
+
+    pub fn randomizer(mut self, randomizer: RandomizerKind) -> Self {
+        self.randomizer = randomizer;
+        self
+    }
+
+    /// Apply the preset of a difficulty.
+    pub fn difficulty(self, difficulty: Difficulty) -> Self {
+        difficulty.configure(self)
+    }
+
+    /// Check that a game could be played with this config in real time, e.g.
+    /// before starting the terminal UI. It finds the problems
+    /// [Game::with_config] would and also checks the gravity gives the
+    /// shapes time to fall, one [ConfigError::Multiple] lists them all if
+    /// there is more than one.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = self.problems();
+        if let Some(level) = (self.start_level..self.start_level + CLASSIC_FRAMES.len() as u32)
+            .find(|level| self.gravity.interval(self.base_tick, *level).is_zero())
+        {
+            problems.push(ConfigError::GravityTooFast { level });
+        }
+        match problems.len() {
+            0 => Ok(()),
+            1 => Err(problems.remove(0)),
+            _ => Err(ConfigError::Multiple(problems)),
+        }
+    }
+
+    /// Return the problems keeping a game from being created with this config.
+    fn problems(&self) -> Vec<ConfigError> {
+        let mut problems = Vec::new();
+        if self.size.0 == 0 || self.size.1 == 0 {
+            problems.push(ConfigError::EmptyLevel { size: self.size });
+        }
+        let standard;
+        let shapes = match &self.shapes {
+            Some(shapes) => shapes,
+            None => {
+                standard = ShapesFactory::with_seed(0).shapes;
+                &standard
+            }
+        };
+        let checks = [
+            check_shapes(shapes, self.size),
+            check_preview_count(self.preview_count),
+            self.initial_board
+                .as_ref()
+                .map_or(Ok(()), |board| check_initial_board(board, self.size)),
+            self.blocked_mask
+                .as_ref()
+                .map_or(Ok(()), |mask| check_blocked_mask(mask, self.size)),
+            self.piece_sequence
+                .as_ref()
+                .map_or(Ok(()), |sequence| check_sequence(sequence, shapes)),
+        ];
+        problems.extend(checks.into_iter().filter_map(Result::err));
+        if let (Some(kind), Some(_)) = (self.first_piece, &self.shapes) {
+            problems.push(ConfigError::UnknownFirstPiece { kind });
+        }
+        if let GameMode::Survival { interval, gap } = self.mode {
+            if interval.is_zero() {
+                problems.push(ConfigError::ZeroGarbageInterval);
+            }
+            match gap {
+                GarbageGap::Fixed(column) if column >= self.size.1 => {
+                    problems.push(ConfigError::GarbageGapOutOfLevel {
+                        column,
+                        columns: self.size.1,
+                    });
                 }
-                _ => true,
+                GarbageGap::Cheese { max_repeats } if max_repeats == 0 || self.size.1 < 2 => {
+                    problems.push(ConfigError::InvalidCheese {
+                        max_repeats,
+                        columns: self.size.1,
+                    });
+                }
+                _ => {}
+            }
+        }
+        problems
+    }
+}
+
+/// The reason a [GameConfig] couldn't be played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// There are no shapes to play with.
+    NoShapes,
+    /// The shape with the given index doesn't fit in the level in any
+    /// orientation.
+    ShapeTooLarge {
+        index: usize,
+        shape: Shape,
+        size: (usize, usize),
+    },
+    /// The initial board has more rows than the level.
+    InitialBoardTooHigh { rows: usize, max: usize },
+    /// A row of the initial board doesn't have as many cells as the level has
+    /// columns.
+    InitialBoardRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The blocked mask has more rows than the level.
+    BlockedMaskTooHigh { rows: usize, max: usize },
+    /// A row of the blocked mask doesn't have as many cells as the level has
+    /// columns.
+    BlockedMaskRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The piece sequence is empty.
+    EmptySequence,
+    /// The piece sequence has an index with no shape.
+    InvalidSequenceIndex { position: usize, index: usize },
+    /// The level has no rows or no columns.
+    EmptyLevel { size: (usize, usize) },
+    /// The number of upcoming shapes shown isn't between 1 and [MAX_PREVIEW].
+    InvalidPreviewCount { count: usize },
+    /// The shapes fall without any interval at the given level, see
+    /// [GameConfig::validate].
+    GravityTooFast { level: u32 },
+    /// [GameConfig::first_piece] is set but the game plays with custom shapes,
+    /// which have no kind.
+    UnknownFirstPiece { kind: PieceKind },
+    /// The garbage of [GameMode::Survival] rises without any interval.
+    ZeroGarbageInterval,
+    /// The fixed gap of the garbage is out of the level's columns.
+    GarbageGapOutOfLevel { column: usize, columns: usize },
+    /// [GarbageGap::Cheese] can't move the gap, it must be allowed at least
+    /// once and the level needs at least 2 columns.
+    InvalidCheese { max_repeats: usize, columns: usize },
+    /// More than one of the other problems, found by [GameConfig::validate].
+    Multiple(Vec<ConfigError>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NoShapes => write!(f, "there are no shapes to play with"),
+            ConfigError::ShapeTooLarge { index, shape, size } => write!(
+                f,
+                "shape {} doesn't fit in a level of {} rows and {} columns in any orientation:\n{}",
+                index, size.0, size.1, shape
+            ),
+            ConfigError::InitialBoardTooHigh { rows, max } => write!(
+                f,
+                "the initial board has {} rows, but the level has only {}",
+                rows, max
+            ),
+            ConfigError::InitialBoardRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} of the initial board has {} cells, expected {}",
+                row, found, expected
+            ),
+            ConfigError::BlockedMaskTooHigh { rows, max } => write!(
+                f,
+                "the blocked mask has {} rows, but the level has only {}",
+                rows, max
+            ),
+            ConfigError::BlockedMaskRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} of the blocked mask has {} cells, expected {}",
+                row, found, expected
+            ),
+            ConfigError::EmptySequence => write!(f, "the piece sequence is empty"),
+            ConfigError::InvalidSequenceIndex { position, index } => write!(
+                f,
+                "piece {} of the sequence is shape {}, which doesn't exist",
+                position, index
+            ),
+            ConfigError::EmptyLevel { size } => write!(
+                f,
+                "the level of {} rows and {} columns has no cells",
+                size.0, size.1
+            ),
+            ConfigError::InvalidPreviewCount { count } => write!(
+                f,
+                "{} upcoming shapes can't be shown, it must be between 1 and {}",
+                count, MAX_PREVIEW
+            ),
+            ConfigError::GravityTooFast { level } => write!(
+                f,
+                "the shapes fall without waiting at level {}, is the base tick set?",
+                level
+            ),
+            ConfigError::UnknownFirstPiece { kind } => write!(
+                f,
+                "the first piece {} can't be played with custom shapes",
+                kind
+            ),
+            ConfigError::ZeroGarbageInterval => {
+                write!(f, "the garbage rises without waiting, the interval is 0")
+            }
+            ConfigError::GarbageGapOutOfLevel { column, columns } => write!(
+                f,
+                "the garbage gap is in column {}, but the level has only {}",
+                column, columns
+            ),
+            ConfigError::InvalidCheese {
+                max_repeats,
+                columns,
+            } => write!(
+                f,
+                "the garbage gap can't repeat at most {} times in {} columns",
+                max_repeats, columns
+            ),
+            ConfigError::Multiple(problems) => {
+                write!(f, "there are {} problems:", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n- {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The error returned by [Game::from_rle].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRleError {
+    /// A character other than a digit, `#`, `.` or `/` was found in a row.
+    InvalidChar { row: usize, ch: char },
+    /// A run of a row has no count, or a count of 0, before its cell.
+    MissingCount { row: usize },
+    /// A row ends with a count but no cell after it.
+    TrailingCount { row: usize },
+    /// The cells don't fit in the level, e.g. a row is too long.
+    Board(ConfigError),
+}
+
+impl fmt::Display for ParseRleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseRleError::InvalidChar { row, ch } => {
+                write!(f, "invalid character {:?} in row {}", ch, row)
+            }
+            ParseRleError::MissingCount { row } => {
+                write!(f, "a run of row {} has no count", row)
+            }
+            ParseRleError::TrailingCount { row } => {
+                write!(f, "row {} ends with a count but no cell", row)
+            }
+            ParseRleError::Board(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseRleError {}
+
+impl From<ConfigError> for ParseRleError {
+    fn from(e: ConfigError) -> Self {
+        ParseRleError::Board(e)
+    }
+}
+
+/// The error returned by [Game::set_playfield_bits] when the bytes aren't as
+/// many as [Game::playfield_bits] returns for the level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayfieldBitsError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for PlayfieldBitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the playfield has {} bytes, expected {}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for PlayfieldBitsError {}
+
+/// Return the cells of a row of [Game::to_rle], `row` is its index for the
+/// errors. It stops at `max` cells, so a huge count isn't allocated.
+fn parse_rle_row(runs: &str, row: usize, max: usize) -> Result<Vec<bool>, ParseRleError> {
+    let mut cells = Vec::new();
+    let mut count: Option<usize> = None;
+    for ch in runs.chars() {
+        match ch {
+            '0'..='9' => {
+                let digit = ch.to_digit(10).unwrap() as usize;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            }
+            '#' | '.' => match count.take() {
+                Some(n) if n > 0 => {
+                    if cells.len() + n > max {
+                        return Err(ConfigError::InitialBoardRow {
+                            row,
+                            expected: max,
+                            found: cells.len().saturating_add(n),
+                        }
+                        .into());
+                    }
+                    cells.resize(cells.len() + n, ch == '#');
+                }
+                _ => return Err(ParseRleError::MissingCount { row }),
             },
-            Event::Left => {
-                if self.state != State::Playing {
-                    return true;
+            _ => return Err(ParseRleError::InvalidChar { row, ch }),
+        }
+    }
+    match count {
+        Some(_) => Err(ParseRleError::TrailingCount { row }),
+        None => Ok(cells),
+    }
+}
+
+/// Check that every shape fits in a level of the given size in at least one
+/// orientation.
+fn check_shapes(shapes: &[Shape], size: (usize, usize)) -> Result<(), ConfigError> {
+    if shapes.is_empty() {
+        return Err(ConfigError::NoShapes);
+    }
+    for (index, shape) in shapes.iter().enumerate() {
+        let mut s = shape.clone();
+        let fits = (0..s.orientations).any(|_| {
+            let fits = s.height() <= size.0 && s.width() <= size.1;
+            s.rotate();
+            fits
+        });
+        if !fits {
+            return Err(ConfigError::ShapeTooLarge {
+                index,
+                shape: shape.clone(),
+                size,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check that the number of upcoming shapes shown is one [GameConfig] allows.
+fn check_preview_count(count: usize) -> Result<(), ConfigError> {
+    if (1..=MAX_PREVIEW).contains(&count) {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidPreviewCount { count })
+    }
+}
+
+/// Check that the piece sequence only has indexes of the given shapes.
+fn check_sequence(sequence: &[usize], shapes: &[Shape]) -> Result<(), ConfigError> {
+    if sequence.is_empty() {
+        return Err(ConfigError::EmptySequence);
+    }
+    match sequence.iter().position(|index| *index >= shapes.len()) {
+        Some(position) => Err(ConfigError::InvalidSequenceIndex {
+            position,
+            index: sequence[position],
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Check that the initial board fits in a level of the given size.
+fn check_initial_board(board: &[Vec<bool>], size: (usize, usize)) -> Result<(), ConfigError> {
+    if board.len() > size.0 {
+        return Err(ConfigError::InitialBoardTooHigh {
+            rows: board.len(),
+            max: size.0,
+        });
+    }
+    match board.iter().position(|row| row.len() != size.1) {
+        Some(row) => Err(ConfigError::InitialBoardRow {
+            row,
+            expected: size.1,
+            found: board[row].len(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Check that the blocked mask fits in a level of the given size like
+/// [check_initial_board].
+fn check_blocked_mask(mask: &[Vec<bool>], size: (usize, usize)) -> Result<(), ConfigError> {
+    check_initial_board(mask, size).map_err(|e| match e {
+        ConfigError::InitialBoardTooHigh { rows, max } => {
+            ConfigError::BlockedMaskTooHigh { rows, max }
+        }
+        ConfigError::InitialBoardRow {
+            row,
+            expected,
+            found,
+        } => ConfigError::BlockedMaskRow {
+            row,
+            expected,
+            found,
+        },
+        e => e,
+    })
+}
+
+/// Return a level of the given size with the cells in `rows`, a row for each
+/// row of the level from the bottom.
+fn level_from_rows(rows: &[Vec<bool>], size: (usize, usize)) -> Conventional<bool> {
+    let mut level = Conventional::new(size);
+    for (row, cells) in rows.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            level[(row, col)] = *cell;
+        }
+    }
+    level
+}
+
+/// Remove the `full` rows of `level` like [Board::clear_full_rows], but the
+/// `blocked` cells are left where they are: the cells of a column fall down
+/// past the blocked ones.
+fn clear_rows_with_mask(
+    level: &mut Conventional<bool>,
+    blocked: &Conventional<bool>,
+    full: &[usize],
+) {
+    for col in 0..level.columns {
+        let open: Vec<usize> = (0..level.rows)
+            .filter(|row| !blocked[(*row, col)])
+            .collect();
+        let kept: Vec<bool> = open
+            .iter()
+            .filter(|row| !full.contains(row))
+            .map(|row| level[(*row, col)])
+            .collect();
+        for (i, row) in open.iter().enumerate() {
+            level[(*row, col)] = kept.get(i).copied().unwrap_or(false);
+        }
+    }
+}
+
+/// Return the groups of connected filled cells of `level` as `(row, col)`,
+/// the lowest group first. The `blocked` cells aren't in any group.
+fn cell_groups(
+    level: &Conventional<bool>,
+    blocked: Option<&Conventional<bool>>,
+) -> Vec<Vec<(usize, usize)>> {
+    let is_open = |cell: (usize, usize)| level[cell] && !blocked.is_some_and(|b| b[cell]);
+    let mut seen = Conventional::<bool>::new((level.rows, level.columns));
+    let mut groups = Vec::new();
+    for row in 0..level.rows {
+        for col in 0..level.columns {
+            if seen[(row, col)] || !is_open((row, col)) {
+                continue;
+            }
+            seen[(row, col)] = true;
+            let mut group = vec![(row, col)];
+            let mut i = 0;
+            while i < group.len() {
+                let (r, c) = group[i];
+                let neighbours = [
+                    (r.wrapping_sub(1), c),
+                    (r + 1, c),
+                    (r, c.wrapping_sub(1)),
+                    (r, c + 1),
+                ];
+                for n in neighbours {
+                    if n.0 < level.rows && n.1 < level.columns && !seen[n] && is_open(n) {
+                        seen[n] = true;
+                        group.push(n);
+                    }
                 }
+                i += 1;
+            }
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+/// The difficulty presets a player could choose from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    /// Return the name of this difficulty
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// Set the base tick, start level and gravity curve of `config` for this
+    /// difficulty.
+    fn configure(&self, config: GameConfig) -> GameConfig {
+        let ms = Duration::from_millis;
+        match self {
+            Difficulty::Easy => {
+                config
+                    .base_tick(ms(300))
+                    .start_level(0)
+                    .gravity(GravityCurve::Linear {
+                        step: ms(10),
+                        min: ms(100),
+                    })
+            }
+            Difficulty::Normal => {
+                config
+                    .base_tick(ms(200))
+                    .start_level(0)
+                    .gravity(GravityCurve::Linear {
+                        step: ms(10),
+                        min: ms(50),
+                    })
+            }
+            Difficulty::Hard => {
+                config
+                    .base_tick(ms(150))
+                    .start_level(5)
+                    .gravity(GravityCurve::Linear {
+                        step: ms(15),
+                        min: ms(30),
+                    })
+            }
+        }
+    }
+}
+
+/// (De)serialize a [Duration] as a number of milliseconds, which is easier to
+/// read for other tools than seconds and nanoseconds.
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(d.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        u64::deserialize(d).map(Duration::from_millis)
+    }
+
+    /// Like [self], for an optional duration.
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        pub fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+            match d {
+                Some(d) => s.serialize_some(&(d.as_millis() as u64)),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+            Option::<u64>::deserialize(d).map(|ms| ms.map(Duration::from_millis))
+        }
+    }
+}
+
+/// The statistics of a game. It's serialized with the durations in
+/// milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Stats {
+    /// The number of lines cleared.
+    pub lines: u32,
+    /// The points scored by clearing lines.
+    pub score: u32,
+    /// The number of shapes locked into the level.
+    pub pieces: u32,
+    /// The time played, this is derived from the tick intervals so it doesn't
+    /// include the time the game is paused.
+    #[serde(with = "millis")]
+    pub play_time: Duration,
+    /// How long it took to clear all lines in [GameMode::Sprint], this is set
+    /// when the goal is reached.
+    #[serde(with = "millis::option")]
+    pub completion_time: Option<Duration>,
+    /// The number of shapes of each kind locked, custom shapes aren't
+    /// counted.
+    pieces_by_kind: HashMap<PieceKind, u32>,
+    /// The number of rows of garbage cleared, see [Game::add_garbage].
+    #[serde(default)]
+    pub garbage_cleared: u32,
+}
+
+impl Stats {
+    /// Return the number of shapes of `kind` locked into the level.
+    pub fn by_kind(&self, kind: PieceKind) -> u32 {
+        self.pieces_by_kind.get(&kind).copied().unwrap_or(0)
+    }
+
+    /// Return the number of shapes locked per minute of play time.
+    pub fn pieces_per_minute(&self) -> f64 {
+        if self.play_time.is_zero() {
+            return 0.0;
+        }
+        self.pieces as f64 * 60.0 / self.play_time.as_secs_f64()
+    }
+
+    /// Return the number of lines cleared per shape locked, or 0 before any
+    /// shape is locked. A shape has 4 cells and a line 10, so clearing every
+    /// cell placed is 0.4 in a standard level.
+    pub fn efficiency(&self) -> f32 {
+        if self.pieces == 0 {
+            return 0.0;
+        }
+        self.lines as f32 / self.pieces as f32
+    }
+
+    /// Return the number of rows of garbage cleared per shape locked (the
+    /// "downstack" efficiency), or 0 before any shape is locked.
+    pub fn downstack_efficiency(&self) -> f32 {
+        if self.pieces == 0 {
+            return 0.0;
+        }
+        self.garbage_cleared as f32 / self.pieces as f32
+    }
+}
+
+/// Why a game is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndReason {
+    /// A new shape couldn't be put in the level.
+    TopOut,
+    /// The goal of the mode is reached, e.g. all lines of [GameMode::Sprint]
+    /// are cleared.
+    GoalReached,
+}
+
+/// The result of a game, it's created when the game is over. It could be
+/// serialized to e.g. keep it in a scoreboard, the durations are in
+/// milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub score: u32,
+    /// The level the game ended at.
+    pub level: u32,
+    pub lines: u32,
+    pub stats: Stats,
+    pub mode: GameMode,
+    /// The time played.
+    #[serde(with = "millis")]
+    pub duration: Duration,
+    pub reason: EndReason,
+}
+
+/// The 64-bit FNV-1a hash, used by [Game::checksum] since it's the same on
+/// every machine and every run unlike the hasher of [HashMap]. Integers are
+/// hashed as little-endian bytes whatever the platform is.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+}
+
+/// A game represents a game. A cloned game is a separate copy of everything
+/// including the upcoming shapes, e.g. to simulate moves without touching the
+/// original.
+#[derive(Clone)]
+pub struct Game {
+    shape: Option<ShapeInLevel>,
+
+    pub state: State,
+    /// What state the game is currently in.
+
+    /// This matrix represents the cells in a level.
+    pub level: Conventional<bool>,
+
+    /// The cells of [GameConfig::blocked_mask], they are always filled in the
+    /// level.
+    blocked: Option<Conventional<bool>>,
+
+    /// This is used to create shapes
+    shapes_factory: ShapesFactory,
+
+    config: GameConfig,
+
+    /// The current level, it goes up every [LINES_PER_LEVEL] lines.
+    level_number: u32,
+
+    stats: Stats,
+
+    /// The indexes in [ShapesFactory] of the upcoming shapes, it's always at
+    /// least as long as the preview count once the game has started.
+    next_queue: VecDeque<usize>,
+
+    /// The indexes in [ShapesFactory] of the held shapes, the one held first
+    /// is at the front.
+    held: VecDeque<usize>,
+
+    /// Whether [Event::Hold] has been used for the current shape.
+    hold_used: bool,
+
+    /// The number of ticks played in this game.
+    elapsed_ticks: u32,
+
+    /// See [Game::tick_count].
+    tick_count: u64,
+
+    /// The tick each of the last shapes was locked at and the cells it was
+    /// locked into, oldest first, they are shown until
+    /// [GameConfig::invisible_after_ticks] pass.
+    recent_locks: VecDeque<(u32, Vec<(usize, usize)>)>,
+
+    /// The whole level is shown until this tick, after rows are cleared with
+    /// [GameConfig::clear_flash_ticks].
+    flash_until: u32,
+
+    /// The result of the game, it's set when the game is over.
+    summary: Option<GameSummary>,
+
+    /// The position in [GameConfig::piece_sequence] of the next shape to
+    /// queue.
+    sequence_pos: usize,
+
+    /// The games just before the last shapes were locked, the last one is
+    /// the latest. Their own history and recording are always empty.
+    history: VecDeque<Game>,
+
+    /// The ticks left before the shape is locked, it's None while the shape
+    /// could still drop.
+    lock_timer: Option<u32>,
+
+    /// The number of times the lock delay has been restarted for the current
+    /// shape.
+    lock_resets: u32,
+
+    /// The ticks left before the next shape spawns, it's None while there is
+    /// a shape.
+    spawn_timer: Option<u32>,
+
+    /// The full rows waiting for [GameConfig::clear_delay_ticks] and the
+    /// ticks left before they are cleared.
+    pending_clear: Option<(Vec<usize>, u32)>,
+
+    /// The index of the shape which didn't fit when it spawned and the ticks
+    /// left to make room for it, see [GameConfig::top_out_grace_ticks].
+    top_out_grace: Option<(usize, u32)>,
+
+    /// The time passed to [Game::on_frame] which hasn't made a tick yet.
+    frame_time: Duration,
+
+    /// Whether the soft drop is held, see [Event::SoftDropHeld].
+    soft_drop_held: bool,
+
+    /// The ticks of the held soft drop since the last gravity tick, it's a
+    /// gravity tick every [SOFT_DROP_MULTIPLIER] of them.
+    soft_drop_ticks: u32,
+
+    /// The shape as it spawned and the moves and rotations used on it since,
+    /// for [GameConfig::track_finesse].
+    piece_inputs: Option<(ShapeInLevel, u32)>,
+
+    /// The finesse of the last shape locked.
+    last_finesse: Option<Finesse>,
+
+    /// The play time since the last garbage row rose in
+    /// [GameMode::Survival].
+    garbage_time: Duration,
+
+    /// Picks the columns of [GarbageGap::Random], it's separate from the
+    /// shapes so the garbage doesn't change them.
+    garbage_rng: ChaCha12Rng,
+
+    /// The column of the last garbage gap and the number of rows in a row it
+    /// was in, for [GarbageGap::Cheese].
+    last_gap: Option<(usize, usize)>,
+
+    /// The number of rows of garbage at the floor which haven't been cleared.
+    garbage_height: usize,
+
+    /// See [Game::combo].
+    combo: i32,
+
+    /// The number of shapes locked without the fewest inputs.
+    finesse_faults: u32,
+
+    /// The last events handled, the latest one is the last.
+    #[cfg(feature = "event-log")]
+    event_log: VecDeque<LogEntry>,
+
+    /// The events handled before each tick since [Game::start_recording], the
+    /// last ones are the ones since the last tick. It's None if the game isn't
+    /// recorded.
+    recording: Option<Vec<Vec<Event>>>,
+
+    /// Whether the recording ended with [Game::restart] or [Game::stop].
+    recording_ended: bool,
+}
+
+/// A game with [GameConfig::default], a level of 20 rows and 10 columns.
+impl Default for Game {
+    fn default() -> Self {
+        Game::with_config(GameConfig::default()).unwrap()
+    }
+}
+
+impl Game {
+    /// Return a new Game with the given height and width, i.e. `(rows,
+    /// columns)` like [DEFAULT_SIZE].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is too small for any of the standard shapes, use
+    /// [Game::with_config] to handle it.
+    pub fn new(size: (usize, usize)) -> Game {
+        Game::with_config(GameConfig::new(size)).unwrap()
+    }
+
+    /// Return a new Game with the given configuration, or an error if it
+    /// couldn't be played, e.g. a shape never fits in the level.
+    pub fn with_config(config: GameConfig) -> Result<Game, ConfigError> {
+        let mut shapes_factory = match config.seed {
+            Some(seed) => ShapesFactory::with_seed(seed),
+            None => ShapesFactory::new(),
+        };
+        if let Some(shapes) = &config.shapes {
+            shapes_factory.shapes = shapes.clone();
+            shapes_factory.kinds.clear();
+            shapes_factory.draws = vec![0; shapes.len()];
+        }
+        shapes_factory.set_randomizer(config.randomizer.build());
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(problem);
+        }
+        let blocked = config
+            .blocked_mask
+            .as_ref()
+            .map(|mask| level_from_rows(mask, config.size));
+
+        let mut garbage_rng = match config.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        garbage_rng.set_stream(1);
+
+        Ok(Game {
+            shape: None,
+            state: State::Init,
+            level: Conventional::new(config.size),
+            blocked,
+            shapes_factory,
+            level_number: config.start_level,
+            stats: Stats::default(),
+            next_queue: VecDeque::new(),
+            held: VecDeque::new(),
+            hold_used: false,
+            elapsed_ticks: 0,
+            tick_count: 0,
+            recent_locks: VecDeque::new(),
+            flash_until: 0,
+            summary: None,
+            sequence_pos: 0,
+            history: VecDeque::new(),
+            lock_timer: None,
+            lock_resets: 0,
+            spawn_timer: None,
+            pending_clear: None,
+            top_out_grace: None,
+            frame_time: Duration::ZERO,
+            soft_drop_held: false,
+            soft_drop_ticks: 0,
+            piece_inputs: None,
+            last_finesse: None,
+            finesse_faults: 0,
+            garbage_time: Duration::ZERO,
+            garbage_rng,
+            last_gap: None,
+            garbage_height: 0,
+            combo: -1,
+            #[cfg(feature = "event-log")]
+            event_log: VecDeque::with_capacity(EVENT_LOG_LEN),
+            recording: None,
+            recording_ended: false,
+            config,
+        })
+    }
+
+    /// Return a game of the given size (rows, columns) in [State::Init] with
+    /// the cells of [Game::to_rle], they are also the initial board every
+    /// time it's started.
+    pub fn from_rle(rle: &str, size: (usize, usize)) -> Result<Game, ParseRleError> {
+        let rows = if rle.is_empty() {
+            Vec::new()
+        } else {
+            rle.split('/')
+                .enumerate()
+                .map(|(row, runs)| parse_rle_row(runs, row, size.1))
+                .collect::<Result<_, _>>()?
+        };
+        let mut g = Game::with_config(GameConfig::new(size).initial_board(rows))?;
+        g.clear();
+        Ok(g)
+    }
+
+    /// Return the current level.
+    pub fn level_number(&self) -> u32 {
+        self.level_number
+    }
+
+    /// Return the statistics of this game.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Return the result of this game, or None if it isn't over.
+    pub fn summary(&self) -> Option<&GameSummary> {
+        self.summary.as_ref()
+    }
+
+    /// Return the seed of the shapes, or None if a random one is used.
+    pub fn seed(&self) -> Option<u64> {
+        self.config.seed
+    }
+
+    /// Return the number of shapes in a row which cleared lines after the
+    /// first one (the "combo" or "REN"): it's 0 after a shape clears lines,
+    /// one more for each of the next shapes clearing lines too, and -1 once
+    /// a shape is locked without clearing any.
+    pub fn combo(&self) -> i32 {
+        self.combo
+    }
+
+    /// Return the rules deciding when this game ends.
+    pub fn mode(&self) -> GameMode {
+        self.config.mode
+    }
+
+    /// Return the number of lines left to clear in [GameMode::Sprint], or None
+    /// in other modes.
+    pub fn lines_remaining(&self) -> Option<u32> {
+        match self.config.mode {
+            GameMode::Sprint { lines } => Some(lines.saturating_sub(self.stats.lines)),
+            GameMode::Marathon | GameMode::Zen | GameMode::Survival { .. } => None,
+        }
+    }
+
+    /// Return up to `n` upcoming shapes, the first one is the next to come.
+    /// There are at least as many shapes as the configured preview count.
+    pub fn next_shapes(&self, n: usize) -> Vec<&Shape> {
+        self.next_queue
+            .iter()
+            .take(n)
+            .map(|i| &self.shapes_factory.shapes[*i])
+            .collect()
+    }
+
+    /// Return true while the next shape waits for the spawn delay, see
+    /// [GameConfig::spawn_delay_ticks].
+    pub fn spawn_pending(&self) -> bool {
+        self.spawn_timer.is_some()
+    }
+
+    /// Return the full rows (0 is the bottom one) waiting for
+    /// [GameConfig::clear_delay_ticks] before they are cleared, from the
+    /// bottom up. It's empty if there are none.
+    pub fn pending_clear_rows(&self) -> &[usize] {
+        self.pending_clear
+            .as_ref()
+            .map_or(&[], |(rows, _)| rows.as_slice())
+    }
+
+    /// Return the row of the level new shapes come in from, the top one (or
+    /// the bottom one with [GravityDirection::Up]).
+    pub fn spawn_edge(&self) -> usize {
+        match self.config.gravity_direction {
+            GravityDirection::Down => self.level.rows.saturating_sub(1),
+            GravityDirection::Up => 0,
+        }
+    }
+
+    /// Return the columns of the level `shape` has cells in when it spawns,
+    /// from the left.
+    pub fn spawn_columns(&self, shape: &Shape) -> Vec<usize> {
+        let (_, col) = self.spawn_position(shape);
+        (0..shape.width())
+            .filter(|&c| (0..shape.height()).any(|r| shape.cells()[(r, c)]))
+            .map(|c| (col + c as isize) as usize)
+            .collect()
+    }
+
+    /// Return the position (the bottom-left corner) a shape of `kind` would
+    /// spawn at in the level as it is now, without spawning it, e.g. to look
+    /// ahead at the upcoming shapes. It's in the columns of
+    /// [Game::spawn_columns], and out of the level if it overlaps any cells,
+    /// i.e. the game would top out. The standard shape is used if the shapes
+    /// are custom ones.
+    pub fn spawn_position_for(&self, kind: PieceKind) -> (isize, isize) {
+        let standard;
+        let factory = if self.shapes_factory.kinds.is_empty() {
+            standard = ShapesFactory::with_seed(0);
+            &standard
+        } else {
+            &self.shapes_factory
+        };
+        let index = factory.kinds.iter().position(|k| *k == kind).unwrap();
+        self.spawn_position(&factory.shapes[index])
+    }
+
+    /// Return the number of upcoming shapes shown to the player.
+    pub fn preview_count(&self) -> usize {
+        self.config.preview_count
+    }
+
+    /// Return the kind of the active shape, or None if there is no active
+    /// shape or it's a custom one.
+    pub fn current_piece_kind(&self) -> Option<PieceKind> {
+        let s = self.shape.as_ref()?;
+        self.shapes_factory.kind(s.index)
+    }
+
+    /// Return the active shape and its position in the level, i.e. where
+    /// its bottom-left corner is.
+    pub fn active_piece(&self) -> Option<(&Shape, (isize, isize))> {
+        self.shape.as_ref().map(|s| (&s.shape, s.pos))
+    }
+
+    /// Return the kind of `shape` in any orientation, or None if it isn't one
+    /// of the standard shapes of the game.
+    pub fn kind_of(&self, shape: &Shape) -> Option<PieceKind> {
+        let index = self.shapes_factory.shapes.iter().position(|s| {
+            let mut rotated = s.clone();
+            (0..4).any(|_| {
+                let same = rotated == *shape;
+                rotated.rotate();
+                same
+            })
+        })?;
+        self.shapes_factory.kind(index)
+    }
+
+    /// Return the held shape which is taken by the next [Event::Hold] once
+    /// the hold is full, in its spawn orientation.
+    pub fn held_shape(&self) -> Option<&Shape> {
+        self.held.front().map(|i| &self.shapes_factory.shapes[*i])
+    }
+
+    /// Return the held shapes in their spawn orientation, the one held first
+    /// comes first.
+    pub fn held_pieces(&self) -> Vec<&Shape> {
+        self.held
+            .iter()
+            .map(|i| &self.shapes_factory.shapes[*i])
+            .collect()
+    }
+
+    /// Return the height of each column of the level, which is the number of
+    /// rows from the floor to the highest cell of the column. The floor is
+    /// the top of the level with [GravityDirection::Up].
+    pub fn column_heights(&self) -> Vec<usize> {
+        (0..self.level.columns)
+            .map(|col| {
+                (0..self.level.rows)
+                    .rev()
+                    .find(|height| self.level[(self.floor_row(*height), col)])
+                    .map_or(0, |height| height + 1)
+            })
+            .collect()
+    }
+
+    /// Return the row of the level which is `height` rows from the floor.
+    fn floor_row(&self, height: usize) -> usize {
+        match self.config.gravity_direction {
+            GravityDirection::Down => height,
+            GravityDirection::Up => self.level.rows - 1 - height,
+        }
+    }
+
+    /// Return the height of the highest column, see [Game::column_heights].
+    pub fn stack_height(&self) -> usize {
+        self.column_heights().into_iter().max().unwrap_or(0)
+    }
+
+    /// Return the sum of the heights of all columns.
+    pub fn aggregate_height(&self) -> usize {
+        self.column_heights().iter().sum()
+    }
+
+    /// Return the number of empty cells with a cell above them in the same
+    /// column.
+    pub fn hole_count(&self) -> usize {
+        self.column_heights()
+            .iter()
+            .enumerate()
+            .map(|(col, height)| {
+                (0..*height)
+                    .filter(|h| !self.level[(self.floor_row(*h), col)])
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Return the number of filled cells of the level, the falling shape isn't
+    /// counted.
+    pub fn filled_cell_count(&self) -> usize {
+        self.level.iter().filter(|cell| **cell).count()
+    }
+
+    /// Return the ratio of the filled cells to all cells of the level, from
+    /// 0.0 for an empty level to 1.0, see [Game::filled_cell_count].
+    pub fn fill_ratio(&self) -> f32 {
+        self.filled_cell_count() as f32 / (self.level.rows * self.level.columns) as f32
+    }
+
+    /// Return the state of the random number generator creating shapes, it
+    /// could be saved with the level to resume the game later.
+    pub fn rng_state(&self) -> RngState {
+        self.shapes_factory.rng_state()
+    }
+
+    /// Restore the random number generator creating shapes to a state returned
+    /// by [Game::rng_state].
+    pub fn set_rng_state(&mut self, state: &RngState) {
+        self.shapes_factory.set_rng_state(state);
+    }
+
+    /// Return a hash of the cells of the level, the active shape (its kind,
+    /// cells and position), the score and the state, so two peers playing the
+    /// same game could compare them to find out they diverged. It's the same
+    /// on every machine.
+    pub fn checksum(&self) -> u64 {
+        let mut h = Fnv1a::new();
+        h.write_u64(self.level.rows as u64);
+        h.write_u64(self.level.columns as u64);
+        for cell in self.level.iter() {
+            h.write_u8(*cell as u8);
+        }
+        match &self.shape {
+            Some(s) => {
+                h.write_u64(s.index as u64);
+                h.write_u64(s.shape.height() as u64);
+                h.write_u64(s.shape.width() as u64);
+                for cell in s.shape.cells().iter() {
+                    h.write_u8(*cell as u8);
+                }
+                h.write_u64(s.pos.0 as u64);
+                h.write_u64(s.pos.1 as u64);
+            }
+            None => h.write_u64(u64::MAX),
+        }
+        h.write_u64(self.stats.score as u64);
+        h.write_u64(self.state as u64);
+        h.finish()
+    }
+
+    /// Return the factory creating the shapes of this game, e.g. to check how
+    /// many of each kind it drew.
+    pub fn shapes_factory(&self) -> &ShapesFactory {
+        &self.shapes_factory
+    }
+
+    /// Return the number of ticks played in this game.
+    pub fn elapsed_ticks(&self) -> u32 {
+        self.elapsed_ticks
+    }
+
+    /// Return the time played in this game, it doesn't include the time the
+    /// game is paused.
+    #[deprecated(note = "use `stats().play_time` instead")]
+    pub fn elapsed_time(&self) -> Duration {
+        self.stats.play_time
+    }
+
+    /// Return the number of calls to [Game::tick] since the game was started
+    /// or restarted, even while it's paused or over, unlike
+    /// [Game::elapsed_ticks]. It's the frame the events of a replay or of
+    /// another player are aligned to.
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    /// Return how long a tick should take at the current level.
+    pub fn gravity_interval(&self) -> Duration {
+        self.config
+            .gravity
+            .interval(self.config.base_tick, self.level_number)
+    }
+
+    /// Handle a game event, it returns false if we should quit the game.
+    pub fn handle_event(&mut self, e: Event) -> bool {
+        let res = self.apply_event(e);
+        self.log_event(e);
+        if let (Some(recording), false) = (&mut self.recording, self.recording_ended) {
+            recording.last_mut().unwrap().push(e);
+        }
+        res
+    }
+
+    /// Record the events and the ticks of this game from now on, see
+    /// [Game::recording]. Start it before the first event or tick of a new
+    /// game, so the replay starts from the same game.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(vec![Vec::new()]);
+        self.recording_ended = false;
+    }
+
+    /// Return the game recorded since [Game::start_recording] as a replay of
+    /// its events and its ticks, or None if it isn't recorded. The events
+    /// after the last tick aren't played by a replay, so they are left out.
+    /// The recording ends at [Game::restart] or [Game::stop], they aren't
+    /// events a replay could play.
+    pub fn recording(&self) -> Option<Replay> {
+        let recording = self.recording.as_ref()?;
+        let ticks = recording.len() - 1;
+        Some(Replay {
+            config: self.config.clone(),
+            events: recording[..ticks].to_vec(),
+        })
+    }
+
+    /// Handle an event from a front-end with its own event loop, which
+    /// decides when to quit by itself, see [Game::on_frame].
+    pub fn input(&mut self, e: Event) {
+        self.handle_event(e);
+    }
+
+    /// Add `e` to the events returned by [Game::recent_history].
+    #[cfg(feature = "event-log")]
+    fn log_event(&mut self, e: Event) {
+        if self.event_log.len() == EVENT_LOG_LEN {
+            self.event_log.pop_front();
+        }
+        self.event_log.push_back(LogEntry {
+            tick: self.elapsed_ticks,
+            event: e,
+            state: self.state,
+        });
+    }
+
+    #[cfg(not(feature = "event-log"))]
+    fn log_event(&mut self, _e: Event) {}
+
+    /// Return the last [EVENT_LOG_LEN] events handled by this game with the
+    /// tick they were handled in and the state they left the game in, the
+    /// oldest one first. It's meant to find out where a replay went another
+    /// way than the original game, restarting or undoing keeps the events.
+    #[cfg(feature = "event-log")]
+    pub fn recent_history(&self) -> Vec<LogEntry> {
+        self.event_log.iter().copied().collect()
+    }
+
+    fn apply_event(&mut self, e: Event) -> bool {
+        if let (
+            Event::Left | Event::Right | Event::Rotate | Event::MoveToWall(_),
+            State::Playing,
+            Some((_, inputs)),
+        ) = (e, self.state, &mut self.piece_inputs)
+        {
+            *inputs += 1;
+        }
+        match e {
+            Event::Start => match self.state {
+                State::Init | State::End => {
+                    self.reset();
+                    true
+                }
+                State::Paused => {
+                    self.state = State::Playing;
+                    true
+                }
+                _ => true,
+            },
+            Event::Left => {
+                if self.state != State::Playing {
+                    return true;
+                }
+
+                if self.try_move((0, -1)).is_ok() {
+                    self.reset_lock_timer();
+                }
+                true
+            }
+            Event::Right => {
+                if self.state != State::Playing {
+                    return true;
+                }
+
+                if self.try_move((0, 1)).is_ok() {
+                    self.reset_lock_timer();
+                }
+                true
+            }
+            Event::MoveToWall(dir) => {
+                if self.state != State::Playing {
+                    return true;
+                }
+
+                let mut moved = false;
+                while self.try_move(dir.offset()).is_ok() {
+                    moved = true;
+                }
+                if moved {
+                    self.reset_lock_timer();
+                }
+                true
+            }
+            Event::Pause => {
+                if self.state == State::Playing {
+                    self.state = State::Paused;
+                }
+                true
+            }
+            Event::SoftDrop => {
+                self.soft_drop();
+                true
+            }
+            Event::SoftDropHeld(held) => {
+                if held != self.soft_drop_held {
+                    self.soft_drop_held = held;
+                    self.soft_drop_ticks = 0;
+                }
+                true
+            }
+            Event::HardDrop => {
+                self.hard_drop();
+                true
+            }
+            Event::Hold => {
+                if self.state != State::Playing
+                    || self.hold_used
+                    || self.shape.is_none()
+                    || self.config.hold_capacity == 0
+                {
+                    return true;
+                }
+
+                let current = self.shape.take().unwrap().index;
+                self.held.push_back(current);
+                let next = if self.held.len() > self.config.hold_capacity {
+                    self.held.pop_front().unwrap()
+                } else {
+                    self.next_index()
+                };
+                self.spawn_shape(next);
+                self.hold_used = true;
+                if !self.shape_fits_level() {
+                    self.top_out();
+                }
+                true
+            }
+            Event::Undo => {
+                if self.state == State::Playing {
+                    self.undo();
+                }
+                true
+            }
+            Event::PickPreview(index) => {
+                if self.state == State::Playing
+                    && self.config.pick_preview
+                    && index < self.config.preview_count
+                {
+                    let picked = self.next_queue.remove(index).unwrap();
+                    self.next_queue.push_front(picked);
+                }
+                true
+            }
+            Event::RetryPiece => {
+                if self.state == State::Playing && self.config.retry_piece {
+                    self.retry_piece();
+                }
+                true
+            }
+            Event::Rotate => {
+                if self.try_rotate().is_ok() {
+                    self.reset_lock_timer();
+                }
+                true
+            }
+        }
+    }
+
+    /// Handle the events in order, see [Game::handle_event].
+    pub fn apply_events(&mut self, events: &[Event]) {
+        for e in events {
+            self.handle_event(*e);
+        }
+    }
+
+    /// Play `ticks` ticks of the game, handling `events[i]` before the tick
+    /// `i` (there are no events for the ticks after the last one), and return
+    /// the frames after each of them like [Game::rows], e.g. to record an
+    /// animation without the terminal UI. The ticks are played as the frames
+    /// are taken.
+    pub fn simulate<'a>(
+        &'a mut self,
+        events: &'a [&'a [Event]],
+        ticks: usize,
+    ) -> impl Iterator<Item = Vec<Vec<bool>>> + 'a {
+        (0..ticks).map(move |i| {
+            self.apply_events(events.get(i).copied().unwrap_or_default());
+            self.tick();
+            self.rows().map(Iterator::collect).collect()
+        })
+    }
+
+    /// Check that the game is consistent: the level has the configured size,
+    /// the active shape is inside it without overlapping any cells while the
+    /// game is being played and the upcoming shapes exist. This is meant for
+    /// tests, it only checks in debug builds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game isn't consistent.
+    pub fn assert_invariants(&self) {
+        debug_assert_eq!(self.level.dimensions(), self.config.size);
+        if matches!(self.state, State::Playing | State::Paused) {
+            // a game which topped out keeps the shape which didn't fit, and
+            // there is none while the full rows wait to be cleared.
+            debug_assert!(
+                self.shape.is_some()
+                    || self.spawn_timer.is_some()
+                    || self.top_out_grace.is_some()
+                    || self.pending_clear.is_some(),
+                "there is no shape in {:?}",
+                self.state
+            );
+            if let Some(s) = &self.shape {
+                debug_assert!(
+                    !self.check_shape_out_of_bound(Some(s)),
+                    "the shape at {:?} is out of the level:\n{}",
+                    s.pos,
+                    s.shape
+                );
+                debug_assert!(
+                    !self.check_collision(Some(s)),
+                    "the shape at {:?} overlaps the level:\n{}",
+                    s.pos,
+                    s.shape
+                );
+            }
+            debug_assert!(self.next_queue.len() >= self.config.preview_count);
+        }
+        let shapes = self.shapes_factory.shapes.len();
+        debug_assert!(self
+            .next_queue
+            .iter()
+            .chain(&self.held)
+            .all(|i| *i < shapes));
+        debug_assert!(self.held.len() <= self.config.hold_capacity);
+    }
+
+    /// Do one tick, it lasts [Game::tick_interval]. While the soft drop is
+    /// held it drops the shape by a row like [Event::SoftDrop], but only
+    /// every [SOFT_DROP_MULTIPLIER]th one counts as a gravity tick, for
+    /// [Game::elapsed_ticks] and the delays.
+    pub fn tick(&mut self) -> TickOutcome {
+        if let (Some(recording), false) = (&mut self.recording, self.recording_ended) {
+            recording.push(Vec::new());
+        }
+        if !self.soft_drop_held {
+            return self.tick_lasting(self.gravity_interval());
+        }
+        let interval = self.tick_interval();
+        self.soft_drop_ticks += 1;
+        let outcome = if self.soft_drop_ticks == SOFT_DROP_MULTIPLIER {
+            self.soft_drop_ticks = 0;
+            self.tick_lasting(interval)
+        } else {
+            self.soft_drop_fall(interval)
+        };
+        self.stats.score += outcome.distance * self.config.score_table.soft_drop;
+        self.lock_on_contact(outcome)
+    }
+
+    /// Return true while the soft drop is held, see [Event::SoftDropHeld].
+    pub fn soft_drop_held(&self) -> bool {
+        self.soft_drop_held
+    }
+
+    /// Return how long a tick should take: [Game::gravity_interval], or
+    /// [SOFT_DROP_MULTIPLIER] times shorter while the soft drop is held but
+    /// never zero, so the ticks are still paced.
+    pub fn tick_interval(&self) -> Duration {
+        let gravity = self.gravity_interval();
+        if self.soft_drop_held {
+            (gravity / SOFT_DROP_MULTIPLIER).max(Duration::from_millis(1))
+        } else {
+            gravity
+        }
+    }
+
+    /// Let `dt` pass, e.g. the time of a frame of a GUI drawing the game, and
+    /// do as many ticks as [Game::tick_interval] fits in the time passed so
+    /// far, the rest is kept for the next frames. It returns the outcome of
+    /// each tick. No time passes while the game isn't being played, and
+    /// without a gravity interval there is a tick per frame.
+    pub fn on_frame(&mut self, dt: Duration) -> Vec<TickOutcome> {
+        let mut outcomes = Vec::new();
+        if self.state != State::Playing {
+            self.frame_time = Duration::ZERO;
+            return outcomes;
+        }
+        self.frame_time += dt;
+        while self.state == State::Playing {
+            let interval = self.tick_interval();
+            if interval.is_zero() {
+                self.frame_time = Duration::ZERO;
+                outcomes.push(self.tick());
+                break;
+            }
+            if self.frame_time < interval {
+                break;
+            }
+            self.frame_time -= interval;
+            outcomes.push(self.tick());
+        }
+        outcomes
+    }
+
+    /// Handle `inputs` in order, then do `ticks` ticks, however long they
+    /// take: the game only depends on the events and the ticks, so a run
+    /// without a terminal (e.g. a test or a benchmark) plays the same every
+    /// time with [GameConfig::seed]. It returns the outcome of each tick.
+    pub fn advance(&mut self, ticks: u32, inputs: &[Event]) -> Vec<TickOutcome> {
+        self.apply_events(inputs);
+        (0..ticks).map(|_| self.tick()).collect()
+    }
+
+    /// Do a tick of the held soft drop between the gravity ticks, which took
+    /// `interval` of play time: the shape falls by a row if it could, nothing
+    /// else counts it.
+    fn soft_drop_fall(&mut self, interval: Duration) -> TickOutcome {
+        self.tick_count += 1;
+        if self.state != State::Playing {
+            return TickOutcome::default();
+        }
+        self.add_play_time(interval);
+        self.rise_garbage(interval);
+        if self.can_move(self.config.gravity_direction.offset()) {
+            self.step()
+        } else {
+            TickOutcome::default()
+        }
+    }
+
+    /// Do a tick which took `interval` of play time.
+    fn tick_lasting(&mut self, interval: Duration) -> TickOutcome {
+        self.tick_count += 1;
+        if self.state != State::Playing {
+            return TickOutcome::default();
+        }
+        self.elapsed_ticks += 1;
+        self.add_play_time(interval);
+        self.rise_garbage(interval);
+        if self.state != State::Playing {
+            return TickOutcome::default();
+        }
+        if let Some((index, left)) = self.top_out_grace {
+            return self.make_room(index, left);
+        }
+        if let Some((rows, left)) = self.pending_clear.take() {
+            if left > 1 {
+                self.pending_clear = Some((rows, left - 1));
+                return TickOutcome::default();
+            }
+            return self.clear_and_spawn();
+        }
+        match self.spawn_timer {
+            Some(0) => {
+                self.spawn_timer = None;
+                self.spawn_next();
+                TickOutcome::default()
+            }
+            Some(left) => {
+                self.spawn_timer = Some(left - 1);
+                TickOutcome::default()
+            }
+            None => self.step(),
+        }
+    }
+
+    /// Drop the shape by one row like a tick does, scoring
+    /// [ScoreTable::soft_drop] for the row.
+    pub fn soft_drop(&mut self) -> TickOutcome {
+        let outcome = self.step();
+        self.stats.score += outcome.distance * self.config.score_table.soft_drop;
+        self.lock_on_contact(outcome)
+    }
+
+    /// Lock the shape right away if the soft drop with `outcome` put it on
+    /// something and [GameConfig::lock_on_soft_drop_contact] is set, it
+    /// returns the outcome of the drop and the lock.
+    fn lock_on_contact(&mut self, outcome: TickOutcome) -> TickOutcome {
+        if !self.config.lock_on_soft_drop_contact
+            || outcome.distance == 0
+            || outcome.locked
+            || self.can_move(self.config.gravity_direction.offset())
+        {
+            return outcome;
+        }
+        TickOutcome {
+            distance: outcome.distance,
+            ..self.lock_and_spawn()
+        }
+    }
+
+    /// Drop the shape to the floor and lock it without any lock delay,
+    /// scoring [ScoreTable::hard_drop] for each row.
+    pub fn hard_drop(&mut self) -> TickOutcome {
+        if self.state != State::Playing || self.shape.is_none() {
+            return TickOutcome::default();
+        }
+
+        let mut distance = 0;
+        while self.move_shape(self.config.gravity_direction.offset()) {
+            distance += 1;
+        }
+        self.stats.score += distance * self.config.score_table.hard_drop;
+        TickOutcome {
+            distance,
+            ..self.lock_and_spawn()
+        }
+    }
+
+    /// Add the rows of garbage due in [GameMode::Survival] after `interval`
+    /// more of play time.
+    fn rise_garbage(&mut self, interval: Duration) {
+        let GameMode::Survival {
+            interval: every,
+            gap,
+        } = self.config.mode
+        else {
+            return;
+        };
+        self.garbage_time += interval;
+        while self.garbage_time >= every && self.state == State::Playing {
+            self.garbage_time -= every;
+            let column = self.next_gap(gap);
+            self.add_garbage(1, column);
+        }
+    }
+
+    /// Return the column of the gap of the next garbage row.
+    fn next_gap(&mut self, gap: GarbageGap) -> usize {
+        let columns = self.level.columns;
+        let column = match (gap, self.last_gap) {
+            (GarbageGap::Fixed(column), _) => column,
+            (GarbageGap::Cheese { max_repeats }, Some((last, repeats)))
+                if repeats >= max_repeats =>
+            {
+                // any column but the last one.
+                let column = self.garbage_rng.gen_range(0..columns - 1);
+                column + usize::from(column >= last)
+            }
+            (GarbageGap::Random | GarbageGap::Cheese { .. }, _) => {
+                self.garbage_rng.gen_range(0..columns)
+            }
+        };
+        self.last_gap = match self.last_gap {
+            Some((last, repeats)) if last == column => Some((column, repeats + 1)),
+            _ => Some((column, 1)),
+        };
+        column
+    }
+
+    /// Fill the `rows` of the level (0 is the bottom one) but for the
+    /// `except_cols` columns, which are emptied, e.g. to set up a line clear
+    /// in a test. Nothing is cleared until a shape is locked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a row is out of the level.
+    pub fn fill_rows(&mut self, rows: &[usize], except_cols: &[usize]) {
+        for &row in rows {
+            assert!(row < self.level.rows, "row {} is out of the level", row);
+            for col in 0..self.level.columns {
+                self.level[(row, col)] = !except_cols.contains(&col);
+            }
+        }
+    }
+
+    /// Push the cells of the level up by `rows` rows of garbage rising from
+    /// the floor, which are full but for the `gap` column. The shape is
+    /// pushed up too if it's in the way. The game is over if cells are
+    /// pushed out of the level or there is no room left for the shape.
+    pub fn add_garbage(&mut self, rows: usize, gap: usize) {
+        if self.state != State::Playing {
+            return;
+        }
+        let rows = rows.min(self.level.rows);
+        let overflow = self.with_floor_at_bottom(|g| {
+            let (height, width) = (g.level.rows, g.level.columns);
+            let overflow = (height - rows..height).any(|row| !g.is_row_empty(row));
+            for row in (rows..height).rev() {
+                for col in 0..width {
+                    g.level[(row, col)] = g.level[(row - rows, col)];
+                }
+            }
+            for row in 0..rows {
+                for col in 0..width {
+                    g.level[(row, col)] = col != gap;
+                }
+            }
+            if let Some(blocked) = &g.blocked {
+                for (cell, blocked) in g.level.iter_mut().zip(blocked.iter()) {
+                    *cell |= *blocked;
+                }
+            }
+            overflow
+        });
+        self.garbage_height = (self.garbage_height + rows).min(self.level.rows);
+        self.recent_locks.clear();
+        if overflow {
+            self.end(EndReason::TopOut);
+            return;
+        }
+        let offset = self.config.gravity_direction.offset();
+        // the full rows waiting to be cleared rose with the rest.
+        if let Some((pending, _)) = &mut self.pending_clear {
+            for row in pending {
+                *row = (*row as isize - offset.0 * rows as isize) as usize;
+            }
+        }
+        if self.shape.is_none() {
+            return;
+        }
+        while self.check_collision(None) {
+            self.shape.as_mut().unwrap().pos.0 -= offset.0;
+        }
+        if !self.shape_fits_level() {
+            self.top_out();
+        }
+    }
+
+    /// Add `interval` to the play time if the game is being played, the time
+    /// the game is paused or over isn't counted.
+    fn add_play_time(&mut self, interval: Duration) {
+        if self.state == State::Playing {
+            self.stats.play_time += interval;
+        }
+    }
+
+    /// Drop the shape by one row, or lock it and create a new one if it
+    /// couldn't be dropped any more.
+    fn step(&mut self) -> TickOutcome {
+        let row = match &self.shape {
+            Some(s) if self.state == State::Playing => s.pos.0,
+            _ => return TickOutcome::default(),
+        };
+        if self.drop_shape() {
+            let distance = row.abs_diff(self.shape.as_ref().map_or(row, |s| s.pos.0));
+            return TickOutcome {
+                distance: distance as u32,
+                ..TickOutcome::default()
+            };
+        }
+        self.lock_and_spawn()
+    }
+
+    /// Lock the shape, clear the full rows and create a new shape, or wait for
+    /// [GameConfig::clear_delay_ticks] before clearing them.
+    fn lock_and_spawn(&mut self) -> TickOutcome {
+        self.save_snapshot();
+        let holes = self.hole_count();
+        self.lock_piece();
+        let new_holes = self.hole_count().saturating_sub(holes);
+        if self.config.clear_delay_ticks > 0 {
+            let full: Vec<usize> = (0..self.level.rows)
+                .filter(|row| self.is_row_full(*row))
+                .collect();
+            if !full.is_empty() {
+                self.pending_clear = Some((full, self.config.clear_delay_ticks));
+                return TickOutcome {
+                    locked: true,
+                    new_holes,
+                    ..TickOutcome::default()
+                };
+            }
+        }
+        TickOutcome {
+            locked: true,
+            new_holes,
+            ..self.clear_and_spawn()
+        }
+    }
+
+    /// Clear the full rows and create a new shape, or wait for
+    /// [GameConfig::spawn_delay_ticks] before creating it.
+    fn clear_and_spawn(&mut self) -> TickOutcome {
+        let cleared_rows = self.eliminate_rows();
+        self.combo = match cleared_rows.len() {
+            0 => -1,
+            _ => self.combo + 1,
+        };
+        let outcome = TickOutcome {
+            lines: cleared_rows.len() as u32,
+            cleared_rows,
+            ..TickOutcome::default()
+        };
+        if self.state == State::End {
+            return outcome;
+        }
+        if self.config.spawn_delay_ticks > 0 {
+            self.spawn_timer = Some(self.config.spawn_delay_ticks);
+        } else {
+            self.spawn_next();
+        }
+        outcome
+    }
+
+    /// Create the next shape, the game tops out if it doesn't fit unless there
+    /// is a [GameConfig::top_out_grace_ticks].
+    fn spawn_next(&mut self) {
+        self.create_new_shape();
+        if !self.shape_fits_level() {
+            match self.config.top_out_grace_ticks {
+                ticks if ticks > 0 && self.config.mode != GameMode::Zen => {
+                    let index = self.shape.take().unwrap().index;
+                    self.top_out_grace = Some((index, ticks));
+                }
+                _ => self.top_out(),
+            }
+        }
+    }
+
+    /// Handle the shape not fitting in the level: the game is over, except in
+    /// [GameMode::Zen] where the bottom rows are thrown away and the shape
+    /// spawns again until it fits. It's only called with a shape which
+    /// doesn't fit.
+    fn top_out(&mut self) {
+        if self.config.mode != GameMode::Zen {
+            self.end(EndReason::TopOut);
+            return;
+        }
+        let index = self.shape.as_ref().unwrap().index;
+        while !self.shape_fits_level() {
+            if (0..self.level.rows).all(|row| self.is_row_empty(row)) {
+                // only the blocked cells are left in the way.
+                self.end(EndReason::TopOut);
+                return;
+            }
+            self.with_floor_at_bottom(|g| {
+                let blocked = g
+                    .blocked
+                    .clone()
+                    .unwrap_or_else(|| Conventional::new(g.config.size));
+                clear_rows_with_mask(&mut g.level, &blocked, &[0]);
+            });
+            self.garbage_height = self.garbage_height.saturating_sub(1);
+            self.recent_locks.clear();
+            self.spawn_shape(index);
+        }
+    }
+
+    /// Return true if the shape is inside the level without overlapping any
+    /// cells.
+    fn shape_fits_level(&self) -> bool {
+        !self.check_shape_out_of_bound(None) && !self.check_collision(None)
+    }
+
+    /// Do a tick of the top out grace: let the cells fall, clear the full rows
+    /// and spawn the shape with index `index` again, the game is over if it
+    /// still doesn't fit and there are no more ticks `left`.
+    fn make_room(&mut self, index: usize, left: u32) -> TickOutcome {
+        self.with_floor_at_bottom(Game::settle_cells);
+        let cleared_rows = self.eliminate_rows();
+        let outcome = TickOutcome {
+            lines: cleared_rows.len() as u32,
+            cleared_rows,
+            ..TickOutcome::default()
+        };
+        if self.state == State::End {
+            self.top_out_grace = None;
+            return outcome;
+        }
+
+        self.spawn_shape(index);
+        if self.shape_fits_level() {
+            self.top_out_grace = None;
+        } else if left <= 1 {
+            // the game keeps the shape which didn't fit, like any top out.
+            self.top_out_grace = None;
+            self.end(EndReason::TopOut);
+        } else {
+            self.shape = None;
+            self.top_out_grace = Some((index, left - 1));
+        }
+        outcome
+    }
+
+    /// Let every cell with an empty cell below it fall by one row, the blocked
+    /// cells stay where they are.
+    fn settle_cells(&mut self) {
+        for row in 1..self.level.rows {
+            for col in 0..self.level.columns {
+                let blocked = self.blocked.as_ref().is_some_and(|b| b[(row, col)]);
+                if self.level[(row, col)] && !self.level[(row - 1, col)] && !blocked {
+                    self.level[(row, col)] = false;
+                    self.level[(row - 1, col)] = true;
+                }
+            }
+        }
+        self.recent_locks.clear();
+    }
+
+    /// End the game and create its summary.
+    fn end(&mut self, reason: EndReason) {
+        self.state = State::End;
+        self.summary = Some(GameSummary {
+            score: self.stats.score,
+            level: self.level_number,
+            lines: self.stats.lines,
+            stats: self.stats.clone(),
+            mode: self.config.mode,
+            duration: self.stats.play_time,
+            reason,
+        });
+    }
+
+    /// drop the shape by single row (or to the floor with
+    /// [GameConfig::instant_drop]), or count down its lock delay if it's on
+    /// the floor. It returns false if the shape should be locked into the
+    /// level.
+    fn drop_shape(&mut self) -> bool {
+        let offset = self.config.gravity_direction.offset();
+        let dropped = self.move_shape(offset);
+        if dropped && self.config.instant_drop {
+            while self.move_shape(offset) {}
+        }
+        if dropped {
+            self.lock_timer = None;
+            return true;
+        }
+
+        let remaining = self.lock_timer.unwrap_or(self.config.lock_delay_ticks);
+        if remaining > 0 {
+            self.lock_timer = Some(remaining - 1);
+            return true;
+        }
+        false
+    }
+
+    /// Save the game to the history if [GameConfig::undo] is set.
+    fn save_snapshot(&mut self) {
+        if !self.config.undo {
+            return;
+        }
+        let history = std::mem::take(&mut self.history);
+        let recording = self.recording.take();
+        let snapshot = self.clone();
+        self.history = history;
+        self.recording = recording;
+        if self.history.len() == UNDO_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+    }
+
+    /// Rotate the cells of the level by 180°, the bottom row becomes the top
+    /// one and the left column the right one. The active shape stays where it
+    /// is if it still fits, or spawns again at the top, the game is over if it
+    /// doesn't fit there either. It returns false and does nothing if the
+    /// blocked cells of [GameConfig::blocked_mask] wouldn't stay where they
+    /// are.
+    pub fn flip_board(&mut self) -> bool {
+        let (rows, columns) = (self.level.rows, self.level.columns);
+        let flipped = |(row, col): (usize, usize)| (rows - 1 - row, columns - 1 - col);
+        if let Some(blocked) = &self.blocked {
+            let symmetric = (0..rows)
+                .flat_map(|row| (0..columns).map(move |col| (row, col)))
+                .all(|cell| blocked[cell] == blocked[flipped(cell)]);
+            if !symmetric {
+                return false;
+            }
+        }
+        let old = self.level.clone();
+        for row in 0..rows {
+            for col in 0..columns {
+                self.level[(row, col)] = old[flipped((row, col))];
+            }
+        }
+        self.recent_locks.clear();
+
+        if let Some(index) = self.shape.as_ref().map(|s| s.index) {
+            if !self.shape_fits_level() {
+                self.spawn_shape(index);
+                if !self.shape_fits_level() {
+                    self.top_out();
+                }
+            }
+        }
+        true
+    }
+
+    /// Go back to the game just before the last shape was locked, it returns
+    /// false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(mut snapshot) => {
+                snapshot.history = std::mem::take(&mut self.history);
+                // the recording goes on, with the undo in it.
+                snapshot.recording = self.recording.take();
+                snapshot.recording_ended = self.recording_ended;
+                #[cfg(feature = "event-log")]
+                {
+                    snapshot.event_log = std::mem::take(&mut self.event_log);
+                }
+                *self = snapshot;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Put the cells of the shape into the level, it's no longer the active
+    /// shape after this. It's only called with a shape.
+    fn lock_piece(&mut self) {
+        let s = self.shape.take().unwrap();
+        if let Some((spawned, inputs)) = self.piece_inputs.take() {
+            let finesse = Finesse {
+                inputs,
+                optimal: self.fewest_inputs(&spawned, &s),
+            };
+            if !finesse.is_optimal() {
+                self.finesse_faults += 1;
+            }
+            self.last_finesse = Some(finesse);
+        }
+        // the cells out of the level, e.g. of a shape locked at a top out,
+        // are lost.
+        let mut cells = Vec::new();
+        for (row, col) in s.cells() {
+            if self.level.try_set(row, col, true) {
+                cells.push((row as usize, col as usize));
+            }
+        }
+        if let Some(ticks) = self.config.invisible_after_ticks {
+            while self
+                .recent_locks
+                .front()
+                .is_some_and(|(tick, _)| tick + ticks <= self.elapsed_ticks)
+            {
+                self.recent_locks.pop_front();
+            }
+            self.recent_locks.push_back((self.elapsed_ticks, cells));
+        }
+        self.stats.pieces += 1;
+        if let Some(kind) = self.shapes_factory.kind(s.index) {
+            *self.stats.pieces_by_kind.entry(kind).or_insert(0) += 1;
+        }
+    }
+
+    /// Restart the lock delay after the shape is moved on the floor, unless
+    /// it has been restarted [GameConfig::max_lock_resets] times already.
+    /// Spawn the falling shape again, see [Event::RetryPiece]. It stays where
+    /// it is if there is no room for it where it spawns.
+    fn retry_piece(&mut self) {
+        let Some(index) = self.shape.as_ref().map(|s| s.index) else {
+            return;
+        };
+        let shape = self.shapes_factory.shapes[index].clone();
+        let pos = self.spawn_position(&shape);
+        let spawned = ShapeInLevel { shape, index, pos };
+        if !self.check_shape_out_of_bound(Some(&spawned)) && !self.check_collision(Some(&spawned)) {
+            self.spawn_shape(index);
+        }
+    }
+
+    fn reset_lock_timer(&mut self) {
+        if self.lock_timer.is_some() && self.lock_resets < self.config.max_lock_resets {
+            self.lock_timer = Some(self.config.lock_delay_ticks);
+            self.lock_resets += 1;
+        }
+    }
+
+    /// Clear the full rows and score them, it returns the rows cleared like
+    /// [TickOutcome::cleared_rows]. With [ClearRule::Cascade] the cells fall
+    /// and the rows full after that are cleared and scored again until no
+    /// more are.
+    fn eliminate_rows(&mut self) -> Vec<usize> {
+        let mut cleared = self.with_floor_at_bottom(|g| {
+            let mut all = Vec::new();
+            loop {
+                let cleared = g.clear_full_rows();
+                all.extend(&cleared);
+                if cleared.is_empty() || g.config.clear_rule == ClearRule::Naive {
+                    break;
+                }
+                g.cascade_cells();
+            }
+            all
+        });
+        if self.config.gravity_direction != GravityDirection::Down {
+            let rows = self.level.rows;
+            cleared.iter_mut().for_each(|row| *row = rows - 1 - *row);
+        }
+        if !cleared.is_empty() && self.lines_remaining() == Some(0) {
+            self.stats.completion_time = Some(self.stats.play_time);
+            self.end(EndReason::GoalReached);
+        }
+        cleared
+    }
+
+    /// Run `f` with the floor of the level at row 0, i.e. with the level and
+    /// the blocked cells upside down for [GravityDirection::Up], so the code
+    /// moving cells towards row 0 moves them towards the floor. The shape
+    /// isn't flipped, `f` must not use it.
+    fn with_floor_at_bottom<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        if self.config.gravity_direction == GravityDirection::Down {
+            return f(self);
+        }
+        self.flip_rows();
+        let res = f(self);
+        self.flip_rows();
+        res
+    }
+
+    /// Turn the level and the blocked cells upside down.
+    fn flip_rows(&mut self) {
+        let rows = self.level.rows;
+        let flip = |m: &mut Conventional<bool>| {
+            let old = m.clone();
+            for row in 0..rows {
+                for col in 0..m.columns {
+                    m[(row, col)] = old[(rows - 1 - row, col)];
+                }
+            }
+        };
+        flip(&mut self.level);
+        if let Some(blocked) = &mut self.blocked {
+            flip(blocked);
+        }
+    }
+
+    /// Clear the full rows at once and score them, the rows above them move
+    /// down. It returns the rows cleared, from the bottom up.
+    fn clear_full_rows(&mut self) -> Vec<usize> {
+        let full: Vec<usize> = (0..self.level.rows)
+            .filter(|row| self.is_row_full(*row))
+            .collect();
+        if full.is_empty() {
+            return full;
+        }
+        match &self.blocked {
+            Some(blocked) => clear_rows_with_mask(&mut self.level, blocked, &full),
+            None => {
+                self.level.clear_full_rows();
+            }
+        }
+        let cleared = full.len() as u32;
+        let garbage = full
+            .iter()
+            .filter(|row| **row < self.garbage_height)
+            .count();
+        self.garbage_height -= garbage;
+        self.stats.garbage_cleared += garbage as u32;
+        self.recent_locks.clear();
+        self.flash_until = self.elapsed_ticks + self.config.clear_flash_ticks;
+
+        self.stats.score += self.config.score_table.clear(cleared, self.level_number);
+        self.stats.lines += cleared;
+        self.level_number = self.config.start_level + self.stats.lines / LINES_PER_LEVEL;
+        full
+    }
+
+    /// Let every group of connected cells fall by a row at a time until it
+    /// rests on another cell, a blocked cell or the floor, for
+    /// [ClearRule::Cascade]. The groups are found again after every row, as
+    /// a group landing next to another one sticks to it.
+    fn cascade_cells(&mut self) {
+        loop {
+            let mut moved = false;
+            for group in cell_groups(&self.level, self.blocked.as_ref()) {
+                let falls = group.iter().all(|&(row, col)| {
+                    row > 0 && (!self.level[(row - 1, col)] || group.contains(&(row - 1, col)))
+                });
+                if !falls {
+                    continue;
+                }
+                for &cell in &group {
+                    self.level[cell] = false;
+                }
+                for &(row, col) in &group {
+                    self.level[(row - 1, col)] = true;
+                }
+                moved = true;
+            }
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    /// Return true if all cells of `row` (0 is the bottom one) are filled, i.e.
+    /// it's cleared when a shape is locked. The blocked cells of
+    /// [GameConfig::blocked_mask] don't count, a row with only blocked cells
+    /// is never full. The active shape isn't part of the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` isn't in the level.
+    pub fn is_row_full(&self, row: usize) -> bool {
+        let mut open = self.open_cells(row).peekable();
+        open.peek().is_some() && open.all(|cell| cell)
+    }
+
+    /// Return true if no cells of `row` (0 is the bottom one) are filled, the
+    /// blocked cells of [GameConfig::blocked_mask] don't count. The active
+    /// shape isn't part of the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` isn't in the level.
+    pub fn is_row_empty(&self, row: usize) -> bool {
+        !self.open_cells(row).any(|cell| cell)
+    }
+
+    /// Return the cells of `row` which aren't blocked, from the left.
+    fn open_cells(&self, row: usize) -> impl Iterator<Item = bool> + '_ {
+        assert!(row < self.level.rows, "the row {} isn't in the level", row);
+        (0..self.level.columns)
+            .filter(move |col| !self.blocked.as_ref().is_some_and(|b| b[(row, *col)]))
+            .map(move |col| self.level[(row, col)])
+    }
+
+    /// Return true if the any part of the shape is out of bound
+    fn check_shape_out_of_bound(&self, s: Option<&ShapeInLevel>) -> bool {
+        let s1 = s.or(self.shape.as_ref()).unwrap();
+        !board::in_bound(self.level.dimensions(), &s1.shape, s1.pos)
+    }
+
+    /// Return true if the shape collides with any cells in the level, a shape
+    /// out of bound never collides.
+    fn check_collision(&self, s: Option<&ShapeInLevel>) -> bool {
+        let s1 = s.or(self.shape.as_ref()).unwrap();
+        !self.check_shape_out_of_bound(Some(s1)) && !shape_fits(&self.level, &s1.shape, s1.pos)
+    }
+
+    /// Start a new game with the same configuration, whatever state the game
+    /// is in.
+    pub fn restart(&mut self) {
+        self.recording_ended = true;
+        self.reset();
+    }
+
+    /// Stop the game and go back to [State::Init], the level is cleared and
+    /// there is no shape until the game is started again.
+    pub fn stop(&mut self) {
+        self.recording_ended = true;
+        self.clear();
+        self.shape = None;
+        self.state = State::Init;
+    }
+
+    /// Reset game level and switch to state State::Playing
+    fn reset(&mut self) {
+        self.clear();
+        self.tick_count = 0;
+        let first = self
+            .config
+            .first_piece
+            .and_then(|kind| self.shapes_factory.kinds.iter().position(|k| *k == kind));
+        if let Some(index) = first {
+            self.next_queue.push_back(index);
+        }
+        self.create_new_shape();
+        self.state = State::Playing;
+        // the initial board could leave no room for the first shape.
+        if !self.shape_fits_level() {
+            self.top_out();
+        }
+    }
+
+    /// Reset the level and everything else of a game to how it starts.
+    fn clear(&mut self) {
+        self.level = match &self.config.initial_board {
+            Some(board) => level_from_rows(board, self.config.size),
+            None => Conventional::new(self.config.size),
+        };
+        if let Some(blocked) = &self.blocked {
+            for (cell, blocked) in self.level.iter_mut().zip(blocked.iter()) {
+                *cell |= *blocked;
+            }
+        }
+        self.stats = Stats::default();
+        self.held.clear();
+        self.next_queue.clear();
+        self.elapsed_ticks = 0;
+        self.recent_locks.clear();
+        self.flash_until = 0;
+        self.summary = None;
+        self.sequence_pos = 0;
+        self.history.clear();
+        self.spawn_timer = None;
+        self.pending_clear = None;
+        self.top_out_grace = None;
+        self.frame_time = Duration::ZERO;
+        self.soft_drop_held = false;
+        self.soft_drop_ticks = 0;
+        self.last_finesse = None;
+        self.finesse_faults = 0;
+        self.garbage_time = Duration::ZERO;
+        self.last_gap = None;
+        self.garbage_height = 0;
+        self.combo = -1;
+        self.level_number = self.config.start_level;
+    }
+
+    /// Take the index of the next shape from the queue, and refill the queue.
+    fn next_index(&mut self) -> usize {
+        while self.next_queue.len() <= self.config.preview_count {
+            let index = match &self.config.piece_sequence {
+                Some(sequence) => {
+                    let index = sequence[self.sequence_pos % sequence.len()];
+                    self.sequence_pos += 1;
+                    index
+                }
+                None => self.shapes_factory.next_index(),
+            };
+            self.next_queue.push_back(index);
+        }
+        self.next_queue.pop_front().unwrap()
+    }
+
+    fn create_new_shape(&mut self) {
+        let index = self.next_index();
+        self.spawn_shape(index);
+        self.hold_used = false;
+    }
+
+    /// Return where a new shape is put: in the middle of the top (or the
+    /// bottom with [GravityDirection::Up]), it's to the left if the shape
+    /// couldn't be centered exactly.
+    fn spawn_position(&self, shape: &Shape) -> (isize, isize) {
+        let row = match self.config.gravity_direction {
+            GravityDirection::Down => self.level.rows.saturating_sub(shape.height()),
+            GravityDirection::Up => 0,
+        };
+        let mut s = ShapeInLevel {
+            shape: shape.clone(),
+            index: 0,
+            pos: (
+                row as isize,
+                (self.level.columns.saturating_sub(shape.width()) as isize) / 2,
+            ),
+        };
+
+        // stop once the shape is out of the level, the caller finds it out of
+        // bound and ends the game.
+        let offset = self.config.gravity_direction.offset();
+        while !self.check_shape_out_of_bound(Some(&s)) && self.check_collision(Some(&s)) {
+            s.pos.0 -= offset.0;
+        }
+        s.pos
+    }
+
+    /// Make the shape with the given index in [ShapesFactory] the current
+    /// one, in its spawn orientation and position.
+    fn spawn_shape(&mut self, index: usize) {
+        let shape = self.shapes_factory.shapes[index].clone();
+        let pos = self.spawn_position(&shape);
+        self.shape = Some(ShapeInLevel { shape, index, pos });
+        if self.config.track_finesse {
+            self.piece_inputs = self.shape.clone().map(|s| (s, 0));
+        }
+        self.lock_timer = None;
+        self.lock_resets = 0;
+    }
+
+    /// Rotate the shape, moving it by the first of [KICKS] where it fits. It
+    /// returns false if the shape couldn't be rotated.
+    fn rotate_shape(&mut self) -> bool {
+        match self.rotated_shape() {
+            Some(s) => {
+                self.shape = Some(s);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return the shape rotated and moved by the first of [KICKS] where it
+    /// fits, or None if it doesn't fit anywhere.
+    fn rotated_shape(&self) -> Option<ShapeInLevel> {
+        self.rotated(self.shape.as_ref()?)
+    }
+
+    /// Return `s` rotated like [Game::rotated_shape] does for the shape.
+    fn rotated(&self, s: &ShapeInLevel) -> Option<ShapeInLevel> {
+        let mut new_s = s.clone();
+        let offset = new_s.shape.rotate_around_pivot();
+        let orig_pos = (new_s.pos.0 + offset.0, new_s.pos.1 + offset.1);
+        for kick in KICKS {
+            new_s.pos = (orig_pos.0 + kick.0, orig_pos.1 + kick.1);
+            if !self.check_shape_out_of_bound(Some(&new_s)) && !self.check_collision(Some(&new_s)) {
+                return Some(new_s);
+            }
+        }
+        None
+    }
+
+    /// Return every distinct place the shape could be locked in by rotating it
+    /// (after letting it drop if there is no room to rotate it), moving it
+    /// sideways and then dropping it, without changing the game.
+    /// Placements with the same cells in the same position are only listed
+    /// once, e.g. the two orientations of S lying flat.
+    pub fn enumerate_placements(&self) -> Vec<Placement> {
+        let mut res = Vec::new();
+        let mut seen = HashSet::new();
+        let mut s = match &self.shape {
+            Some(s) if self.state == State::Playing => s.clone(),
+            _ => return res,
+        };
+        for rotation in 0..s.shape.orientations {
+            if rotation > 0 {
+                // let it drop until there is room to rotate it.
+                let mut rotated = self.rotated(&s);
+                let row = self.config.gravity_direction.offset().0;
+                while rotated.is_none()
+                    && shape_fits(&self.level, &s.shape, (s.pos.0 + row, s.pos.1))
+                {
+                    s.pos.0 += row;
+                    rotated = self.rotated(&s);
+                }
+                s = match rotated {
+                    Some(rotated) => rotated,
+                    None => break,
+                };
+            }
+            let fits = |c| shape_fits(&self.level, &s.shape, (s.pos.0, c));
+            let left = (0..=s.pos.1).rev().take_while(|c| fits(*c)).last();
+            let right = (s.pos.1..).take_while(|c| fits(*c)).last();
+            let (Some(left), Some(right)) = (left, right) else {
+                continue;
+            };
+            for c in left..=right {
+                let landing_pos = self.landing_position(&s.shape, (s.pos.0, c));
+                if seen.insert((s.shape.clone(), landing_pos)) {
+                    res.push(Placement {
+                        rotation,
+                        col_offset: c - s.pos.1,
+                        landing_pos,
+                    });
+                }
+            }
+        }
+        res
+    }
+
+    /// Return the shape rotated the way [Event::Rotate] would and its position,
+    /// without rotating it, or None if it couldn't be rotated.
+    pub fn rotated_piece(&self) -> Option<(Shape, (isize, isize))> {
+        if !self.can_rotate() {
+            return None;
+        }
+        self.rotated_shape().map(|s| (s.shape, s.pos))
+    }
+
+    /// Return where the active shape would land if it's dropped, it's drawn as
+    /// the ghost of the shape.
+    pub fn ghost_position(&self) -> Option<(isize, isize)> {
+        self.active_piece()
+            .map(|(shape, pos)| self.landing_position(shape, pos))
+    }
+
+    /// Return where `shape` at `pos` of the level lands if it's dropped, i.e.
+    /// the furthest position from `pos` in the [GameConfig::gravity_direction]
+    /// it fits in all the way.
+    pub fn landing_position(&self, shape: &Shape, pos: (isize, isize)) -> (isize, isize) {
+        let row = self.config.gravity_direction.offset().0;
+        let mut pos = pos;
+        while shape_fits(&self.level, shape, (pos.0 + row, pos.1)) {
+            pos.0 += row;
+        }
+        pos
+    }
+
+    /// Return the finesse of the last shape locked, if
+    /// [GameConfig::track_finesse] is set.
+    pub fn last_finesse(&self) -> Option<Finesse> {
+        self.last_finesse
+    }
+
+    /// Return the number of shapes locked with more inputs than needed, if
+    /// [GameConfig::track_finesse] is set.
+    pub fn finesse_faults(&self) -> u32 {
+        self.finesse_faults
+    }
+
+    /// Return the fewest moves and rotations taking `from` to where it lands
+    /// in the same cells as `to`, without dropping it on the way, or None if
+    /// it couldn't get there.
+    fn fewest_inputs(&self, from: &ShapeInLevel, to: &ShapeInLevel) -> Option<u32> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert((from.shape.clone(), from.pos));
+        queue.push_back((from.clone(), 0));
+        while let Some((s, inputs)) = queue.pop_front() {
+            if s.shape == to.shape && self.landing_position(&s.shape, s.pos) == to.pos {
+                return Some(inputs);
+            }
+            let fits = |pos| shape_fits(&self.level, &s.shape, pos);
+            let mut next: Vec<ShapeInLevel> = self.rotated(&s).into_iter().collect();
+            let at = |col| ShapeInLevel {
+                pos: (s.pos.0, col),
+                ..s.clone()
+            };
+            for dir in [-1, 1] {
+                if fits((s.pos.0, s.pos.1 + dir)) {
+                    next.push(at(s.pos.1 + dir));
+                }
+                let mut wall = s.pos.1;
+                while fits((s.pos.0, wall + dir)) {
+                    wall += dir;
+                }
+                next.push(at(wall));
+            }
+            for n in next {
+                if seen.insert((n.shape.clone(), n.pos)) {
+                    queue.push_back((n, inputs + 1));
+                }
+            }
+        }
+        None
+    }
+
+    /// Return true if the shape could be rotated, maybe with a kick, without
+    /// rotating it.
+    pub fn can_rotate(&self) -> bool {
+        self.state == State::Playing && self.shape.is_some() && self.rotated_shape().is_some()
+    }
+
+    /// Move the shape, it returns true if the shape is moved without
+    /// collisions.
+    fn move_shape(&mut self, dir: (isize, isize)) -> bool {
+        self.try_move(dir).is_ok()
+    }
+
+    /// Move the shape by `dir` (rows, columns), the shape is left where it is
+    /// if the move fails. With [GameConfig::slide_into_gaps], a sideways move
+    /// colliding with the level moves the shape a row down too if it fits
+    /// there.
+    pub fn try_move(&mut self, dir: (isize, isize)) -> Result<(), MoveError> {
+        if self.state != State::Playing {
+            return Err(MoveError::NotPlaying);
+        }
+
+        let s = self.slid_shape(dir)?;
+        self.shape = Some(s);
+        Ok(())
+    }
+
+    /// Rotate the shape like [Event::Rotate], moving it by the first of
+    /// [KICKS] where it fits. It fails with [MoveError::Collision] if it
+    /// doesn't fit with any of them, the shape is left as it is then.
+    pub fn try_rotate(&mut self) -> Result<(), MoveError> {
+        if self.state != State::Playing {
+            return Err(MoveError::NotPlaying);
+        }
+        if self.shape.is_none() {
+            return Err(MoveError::NoShape);
+        }
+        if self.rotate_shape() {
+            Ok(())
+        } else {
+            Err(MoveError::Collision)
+        }
+    }
+
+    /// Make the standard shape `kind` the falling one, rotated `rotations`
+    /// times from its spawn orientation with its bottom-left corner at `pos`,
+    /// e.g. to set up a puzzle or a test. The shape replaces the one falling,
+    /// or the one waiting to spawn, and it's left as it is if the new one is
+    /// out of the level or overlaps its cells.
+    pub fn place_active(
+        &mut self,
+        kind: PieceKind,
+        pos: (isize, isize),
+        rotations: u8,
+    ) -> Result<(), MoveError> {
+        if self.state != State::Playing {
+            return Err(MoveError::NotPlaying);
+        }
+        let index = self
+            .shapes_factory
+            .kinds
+            .iter()
+            .position(|k| *k == kind)
+            .ok_or(MoveError::UnknownPiece { kind })?;
+        let mut shape = self.shapes_factory.shapes[index].clone();
+        for _ in 0..rotations % shape.orientations {
+            shape.rotate();
+        }
+        let s = ShapeInLevel { shape, index, pos };
+        if self.check_shape_out_of_bound(Some(&s)) {
+            return Err(MoveError::OutOfBounds);
+        }
+        if self.check_collision(Some(&s)) {
+            return Err(MoveError::Collision);
+        }
+        self.shape = Some(s);
+        self.spawn_timer = None;
+        self.top_out_grace = None;
+        self.lock_timer = None;
+        self.lock_resets = 0;
+        self.piece_inputs = None;
+        Ok(())
+    }
+
+    /// Return the shape moved by `dir` like [Game::moved_shape], or slid a row
+    /// down too for [GameConfig::slide_into_gaps].
+    fn slid_shape(&self, dir: (isize, isize)) -> Result<ShapeInLevel, MoveError> {
+        match self.moved_shape(dir) {
+            Err(MoveError::Collision) if dir.0 == 0 && self.config.slide_into_gaps => self
+                .moved_shape((-1, dir.1))
+                .map_err(|_| MoveError::Collision),
+            moved => moved,
+        }
+    }
+
+    /// Return the shape moved by `dir`, or why it couldn't be moved.
+    fn moved_shape(&self, dir: (isize, isize)) -> Result<ShapeInLevel, MoveError> {
+        let mut s = self.shape.as_ref().ok_or(MoveError::NoShape)?.clone();
+        s.pos = (s.pos.0 + dir.0, s.pos.1 + dir.1);
+
+        if self.check_shape_out_of_bound(Some(&s)) {
+            Err(MoveError::OutOfBounds)
+        } else if self.check_collision(Some(&s)) {
+            Err(MoveError::Collision)
+        } else {
+            Ok(s)
+        }
+    }
+
+    /// Return true if the shape could be moved by `dir`, without moving it.
+    pub fn can_move(&self, dir: (isize, isize)) -> bool {
+        self.state == State::Playing && self.shape.is_some() && self.slid_shape(dir).is_ok()
+    }
+
+    /// Return a matrix respresting cells for the level + shape
+    pub fn render(&self) -> Conventional<bool> {
+        let mut res = self.level.clone();
+        if self.config.invisible_after_ticks.is_some() {
+            for row in 0..res.rows {
+                for col in 0..res.columns {
+                    res[(row, col)] = self.is_cell_shown((row, col));
+                }
+            }
+        }
+        let s = match self.shape.as_ref() {
+            Some(s) => s,
+            None => return res,
+        };
+
+        for (row, col) in s.cells() {
+            res.try_set(row, col, true);
+        }
+        res
+    }
+
+    /// Return the cells of the level, without the shape, as a run-length
+    /// encoded string: the rows from the bottom separated by `/`, each is the
+    /// runs of cells from the left, a count followed by `#` for filled cells
+    /// or `.` for empty ones, e.g. `3#7./1.9#`. The empty rows at the top are
+    /// left out, so an empty level is an empty string.
+    pub fn to_rle(&self) -> String {
+        let rows = (0..self.level.rows)
+            .rposition(|row| (0..self.level.columns).any(|col| self.level[(row, col)]))
+            .map_or(0, |top| top + 1);
+        let mut rle = String::new();
+        for row in 0..rows {
+            if row > 0 {
+                rle.push('/');
+            }
+            let mut col = 0;
+            while col < self.level.columns {
+                let cell = self.level[(row, col)];
+                let run = (col..self.level.columns)
+                    .take_while(|&c| self.level[(row, c)] == cell)
+                    .count();
+                rle += &format!("{}{}", run, if cell { '#' } else { '.' });
+                col += run;
+            }
+        }
+        rle
+    }
+
+    /// Return the cells of the level, without the shape, packed into bits: the
+    /// rows from the bottom, each from the left, the cell `n` of them is the
+    /// bit `n % 8` of the byte `n / 8` (the lowest bit first). The bits after
+    /// the last cell of the last byte are 0, so there are `ceil(rows *
+    /// columns / 8)` bytes.
+    pub fn playfield_bits(&self) -> Vec<u8> {
+        let (rows, columns) = self.level.dimensions();
+        let mut bits = vec![0; (rows * columns).div_ceil(8)];
+        for row in 0..rows {
+            for col in 0..columns {
+                let n = row * columns + col;
+                bits[n / 8] |= u8::from(self.level[(row, col)]) << (n % 8);
+            }
+        }
+        bits
+    }
+
+    /// Replace the cells of the level with the ones of [Game::playfield_bits],
+    /// e.g. to show the level of another player. The blocked cells stay
+    /// filled, and the shape isn't checked against the new cells.
+    pub fn set_playfield_bits(&mut self, bits: &[u8]) -> Result<(), PlayfieldBitsError> {
+        let (rows, columns) = self.level.dimensions();
+        let expected = (rows * columns).div_ceil(8);
+        if bits.len() != expected {
+            return Err(PlayfieldBitsError {
+                expected,
+                found: bits.len(),
+            });
+        }
+        for row in 0..rows {
+            for col in 0..columns {
+                let n = row * columns + col;
+                let blocked = self.blocked.as_ref().is_some_and(|b| b[(row, col)]);
+                self.level[(row, col)] = bits[n / 8] & (1 << (n % 8)) != 0 || blocked;
+            }
+        }
+        self.recent_locks.clear();
+        Ok(())
+    }
+
+    /// Return the rows of the level from the top, each is the cells of a row
+    /// from the left including the cells of the shape, like [Game::render]
+    /// without building a matrix.
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = bool> + '_> + '_ {
+        (0..self.level.rows).rev().map(move |row| {
+            (0..self.level.columns)
+                .map(move |col| self.is_cell_shown((row, col)) || self.shape_at((row, col)))
+        })
+    }
+
+    /// Return true if the cell at `pos` of the level is filled and isn't
+    /// hidden by [GameConfig::invisible_after_ticks].
+    pub fn is_cell_shown(&self, pos: (usize, usize)) -> bool {
+        let ticks = match self.config.invisible_after_ticks {
+            Some(ticks) if self.elapsed_ticks >= self.flash_until => ticks,
+            _ => return self.level[pos],
+        };
+        self.level[pos]
+            && (self.blocked.as_ref().is_some_and(|b| b[pos])
+                || self
+                    .recent_locks
+                    .iter()
+                    .any(|(tick, cells)| self.elapsed_ticks < tick + ticks && cells.contains(&pos)))
+    }
+
+    /// Return true if the active shape has a cell at `pos` of the level.
+    fn shape_at(&self, pos: (usize, usize)) -> bool {
+        let s = match self.shape.as_ref() {
+            Some(s) => s,
+            None => return false,
+        };
+        let row = pos.0 as isize - s.pos.0;
+        let col = pos.1 as isize - s.pos.1;
+        s.shape.cells().get(row, col) == Some(true)
+    }
+
+    /// Return the cells of the level and the shape as text, a line for each
+    /// row from the top with `#` for a cell and `.` for an empty one.
+    pub fn render_ascii(&self) -> String {
+        self.ascii_lines().join("\n")
+    }
+
+    /// Return the lines of [Game::render_ascii].
+    fn ascii_lines(&self) -> Vec<String> {
+        self.rows()
+            .map(|row| row.map(|cell| if cell { '#' } else { '.' }).collect())
+            .collect()
+    }
+}
+
+/// The events of a game to play it again, `events[i]` are handled before the
+/// tick `i`. Set [GameConfig::seed] so the shapes are the same every time it's
+/// played. It's (de)serialized as its config and its events, the config is
+/// validated when it's deserialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "ReplayFile")]
+pub struct Replay {
+    config: GameConfig,
+    events: Vec<Vec<Event>>,
+}
+
+/// A [Replay] as it's deserialized, before its config is validated.
+#[derive(Deserialize)]
+struct ReplayFile {
+    config: GameConfig,
+    events: Vec<Vec<Event>>,
+}
+
+impl TryFrom<ReplayFile> for Replay {
+    type Error = ConfigError;
+
+    fn try_from(file: ReplayFile) -> Result<Self, Self::Error> {
+        Replay::new(file.config, file.events)
+    }
+}
+
+/// What clears the terminal and moves the cursor home before every frame of a
+/// cast.
+const CAST_CLEAR: &str = "\x1b[H\x1b[2J";
+
+impl Replay {
+    pub fn new(config: GameConfig, events: Vec<Vec<Event>>) -> Result<Replay, ConfigError> {
+        config.validate()?;
+        Ok(Replay { config, events })
+    }
+
+    /// Play the replay and return it as an asciinema v2 cast: the header line
+    /// and an event line for the frame after each tick like
+    /// [Game::render_ascii], the frames being `tick_rate` apart.
+    pub fn to_asciicast(&self, tick_rate: Duration) -> String {
+        let mut game = Game::with_config(self.config.clone()).expect("the config was validated");
+        let (rows, columns) = self.config.size;
+        let mut cast = format!(
+            "{{\"version\": 2, \"width\": {}, \"height\": {}}}\n",
+            columns, rows
+        );
+        for (i, events) in self.events.iter().enumerate() {
+            game.apply_events(events);
+            game.tick();
+            let text = format!("{}{}", CAST_CLEAR, game.ascii_lines().join("\r\n"));
+            let time = tick_rate.as_secs_f64() * i as f64;
+            cast.push_str(&format!(
+                "[{:.6}, \"o\", {}]\n",
+                time,
+                serde_json::to_string(&text).unwrap()
+            ));
+        }
+        cast
+    }
+}
+
+/// The number of ticks between the snapshots of a [Scrubber].
+const SCRUB_SNAPSHOT_TICKS: usize = 32;
+
+/// A [Replay] stepped through a tick at a time, forwards or backwards, e.g.
+/// to analyse the game. Going back plays the replay again from the closest
+/// snapshot before the tick, one is kept every [SCRUB_SNAPSHOT_TICKS] ticks.
+pub struct Scrubber {
+    replay: Replay,
+    game: Game,
+    /// The number of ticks played in `game`.
+    tick: usize,
+    /// The game after `i * SCRUB_SNAPSHOT_TICKS` ticks for each `i`.
+    snapshots: Vec<Game>,
+}
+
+impl Scrubber {
+    /// Return a scrubber at the start of `replay`, before the first tick.
+    pub fn new(replay: Replay) -> Self {
+        let game = Game::with_config(replay.config.clone()).expect("the config was validated");
+        Scrubber {
+            snapshots: vec![game.clone()],
+            replay,
+            game,
+            tick: 0,
+        }
+    }
+
+    /// Return the game after [Scrubber::tick] ticks of the replay.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Return the number of ticks played.
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    /// Return the number of ticks of the replay, the last one is the one
+    /// after the last events.
+    pub fn len(&self) -> usize {
+        self.replay.events.len()
+    }
+
+    /// Return true if the replay has no ticks.
+    pub fn is_empty(&self) -> bool {
+        self.replay.events.is_empty()
+    }
+
+    /// Go to the game after `tick` ticks, or after the last one if there are
+    /// fewer.
+    pub fn seek(&mut self, tick: usize) {
+        let tick = tick.min(self.len());
+        if tick < self.tick {
+            let snapshot = (tick / SCRUB_SNAPSHOT_TICKS).min(self.snapshots.len() - 1);
+            self.game = self.snapshots[snapshot].clone();
+            self.tick = snapshot * SCRUB_SNAPSHOT_TICKS;
+        }
+        while self.tick < tick {
+            self.game.apply_events(&self.replay.events[self.tick]);
+            self.game.tick();
+            self.tick += 1;
+            if self.tick.is_multiple_of(SCRUB_SNAPSHOT_TICKS)
+                && self.snapshots.len() == self.tick / SCRUB_SNAPSHOT_TICKS
+            {
+                self.snapshots.push(self.game.clone());
+            }
+        }
+    }
+
+    /// Go a tick forward, if it isn't at the end.
+    pub fn step_forward(&mut self) {
+        self.seek(self.tick + 1);
+    }
+
+    /// Go a tick back, if it isn't at the start.
+    pub fn step_back(&mut self) {
+        self.seek(self.tick.saturating_sub(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+    #[test]
+    fn rotate_shape1() {
+        let mut factory = ShapesFactory::new();
+        let mut s = factory.create_shape();
+        let s_orig = s.clone();
+        s.rotate();
+
+        assert_eq!(s_orig.height(), s.width());
+        assert_eq!(s_orig.width(), s.height());
+
+        s.rotate();
+        s.rotate();
+        s.rotate();
+        assert_eq!(s_orig, s);
+    }
+
+    #[test]
+    fn rotate_shape2() {
+        let mut factory = ShapesFactory::new();
+        let mut s = factory.create_shape();
+        let s_orig = s.clone();
+        s.rotate();
+        s.rotate();
+        s.rotate();
+        s.rotate();
+        assert_eq!(s_orig, s);
+    }
+
+    #[test]
+    fn seeded_shapes() {
+        let mut f1 = ShapesFactory::with_seed(42);
+        let mut f2 = ShapesFactory::with_seed(42);
+        for _ in 0..50 {
+            assert_eq!(f1.create_shape(), f2.create_shape());
+        }
+    }
+
+    #[test]
+    fn draw_tally() {
+        let mut factory = ShapesFactory::with_seed(3);
+        factory.set_randomizer(RandomizerKind::SevenBag.build());
+        assert_eq!(factory.draw_count(), 0);
+        assert!(factory.tally().values().all(|n| *n == 0));
+
+        // 10 bags of each kind.
+        for _ in 0..70 {
+            factory.create_shape();
+        }
+        assert_eq!(factory.draw_count(), 70);
+        let tally = factory.tally();
+        assert_eq!(tally.len(), 7);
+        for kind in factory.kinds() {
+            assert_eq!(tally[kind], 10, "{}", kind);
+        }
+
+        // the shapes of a sequence aren't drawn.
+        let config = GameConfig::new((22, 10)).piece_sequence(vec![0, 1]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(g.shapes_factory().draw_count(), 0);
+        let mut g = Game::with_config(GameConfig::new((22, 10)).seed(3)).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(
+            g.shapes_factory().draw_count() as usize,
+            1 + g.preview_count()
+        );
+    }
+
+    #[test]
+    fn restore_rng_state() {
+        let mut g = Game::with_config(GameConfig::new((22, 10)).seed(7)).unwrap();
+        g.handle_event(Event::Start);
+        for _ in 0..200 {
+            g.tick();
+        }
+        let state = g.rng_state();
+
+        let mut resumed = Game::with_config(GameConfig::new((22, 10)).seed(8)).unwrap();
+        resumed.set_rng_state(&state);
+        assert_eq!(resumed.rng_state(), state);
+        for _ in 0..50 {
+            assert_eq!(
+                g.shapes_factory.create_shape(),
+                resumed.shapes_factory.create_shape()
+            );
+        }
+    }
+
+    #[test]
+    fn restore_rng_state_of_bags() {
+        for kind in [RandomizerKind::SevenBag, RandomizerKind::HistoryBased] {
+            let config = GameConfig::new((22, 10)).seed(7).randomizer(kind);
+            let mut g = Game::with_config(config.clone()).unwrap();
+            g.handle_event(Event::Start);
+            // in the middle of a bag.
+            for _ in 0..3 {
+                g.shapes_factory.create_shape();
+            }
+            let state = g.rng_state();
+
+            let mut resumed = Game::with_config(config.seed(8)).unwrap();
+            resumed.set_rng_state(&state);
+            assert_eq!(resumed.rng_state(), state);
+            assert_eq!(
+                resumed.shapes_factory().current_bag(),
+                g.shapes_factory().current_bag()
+            );
+            for _ in 0..50 {
+                assert_eq!(
+                    g.shapes_factory.create_shape(),
+                    resumed.shapes_factory.create_shape(),
+                    "{:?}",
+                    kind
+                );
+            }
+        }
+
+        // a state of another randomizer or other shapes starts over.
+        let config = GameConfig::new((22, 10)).seed(7);
+        let mut bags =
+            Game::with_config(config.clone().randomizer(RandomizerKind::SevenBag)).unwrap();
+        bags.shapes_factory.create_shape();
+        let state = bags.rng_state();
+        let mut history =
+            Game::with_config(config.clone().randomizer(RandomizerKind::HistoryBased)).unwrap();
+        history.set_rng_state(&state);
+        assert!(history.shapes_factory.randomizer.state().is_empty());
+        let two: Vec<Shape> = vec!["#".parse().unwrap(), "##".parse().unwrap()];
+        for kind in [RandomizerKind::SevenBag, RandomizerKind::HistoryBased] {
+            let mut g = Game::with_config(config.clone().randomizer(kind)).unwrap();
+            for _ in 0..3 {
+                g.shapes_factory.create_shape();
+            }
+            let state = g.rng_state();
+            let mut few =
+                Game::with_config(config.clone().randomizer(kind).shapes(two.clone())).unwrap();
+            few.set_rng_state(&state);
+            for _ in 0..20 {
+                assert!(two.contains(&few.shapes_factory.create_shape()));
+            }
+        }
+    }
+
+    #[test]
+    fn hold_resets_orientation_and_position() {
+        let mut g = Game::with_config(GameConfig::new((22, 10)).seed(3)).unwrap();
+        g.handle_event(Event::Start);
+        let first = g.shape.as_ref().unwrap().index;
+
+        g.handle_event(Event::Rotate);
+        g.handle_event(Event::Left);
+        g.handle_event(Event::SoftDrop);
+        g.handle_event(Event::Hold);
+        assert_eq!(g.held_shape(), Some(&g.shapes_factory.shapes[first]));
+
+        // hold could only be used once per shape
+        let second = g.shape.as_ref().unwrap().index;
+        g.handle_event(Event::Hold);
+        assert_eq!(g.shape.as_ref().unwrap().index, second);
+
+        while g.hold_used {
+            g.handle_event(Event::SoftDrop);
+        }
+        g.handle_event(Event::Hold);
+        let s = g.shape.as_ref().unwrap();
+        assert_eq!(s.index, first);
+        assert_eq!(s.shape, g.shapes_factory.shapes[first]);
+        assert_eq!(s.pos, g.spawn_position(&s.shape));
+    }
+
+    /// Return a game with an empty 10x10 level and the given shape at `pos`.
+    fn game_with_shape(shape: &str, pos: (isize, isize)) -> Game {
+        let mut g = Game::new((10, 10));
+        g.handle_event(Event::Start);
+        let s = g.shape.as_mut().unwrap();
+        s.shape = shape.parse().unwrap();
+        s.pos = pos;
+        g
+    }
+
+    #[test]
+    fn shape_out_of_level() {
+        // e.g. a shape pushed out of the bottom with GravityDirection::Up.
+        let mut g = game_with_shape("##\n##", (-1, -1));
+        let mut level = Conventional::new((10, 10));
+        level[(0, 0)] = true;
+        assert_eq!(g.render(), level);
+        assert_eq!(g.rows().flatten().filter(|cell| *cell).count(), 1);
+        g.lock_piece();
+        assert_eq!(g.level, level);
+    }
+
+    #[test]
+    fn move_to_wall() {
+        let mut g = game_with_shape(".#.\n###", (0, 4));
+        g.handle_event(Event::MoveToWall(Direction::Right));
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 7));
+        g.handle_event(Event::MoveToWall(Direction::Right));
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 7));
+
+        // it stops flush against the stack.
+        g.level[(0, 1)] = true;
+        g.handle_event(Event::MoveToWall(Direction::Left));
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 2));
+    }
+
+    #[test]
+    fn shape_fits_board() {
+        // from the bottom: "##.." and "#..."
+        let mut board = Conventional::new((3, 4));
+        board[(0, 0)] = true;
+        board[(0, 1)] = true;
+        board[(1, 0)] = true;
+        let t: Shape = ".#.\n###".parse().unwrap();
+        let down: Shape = "###\n.#.".parse().unwrap();
+
+        assert!(shape_fits(&board, &t, (1, 1)));
+        assert!(!shape_fits(&board, &t, (0, 1)), "it overlaps a cell");
+        assert!(!shape_fits(&board, &t, (2, 1)), "it's above the board");
+        assert!(!shape_fits(&board, &t, (1, 2)), "it's right of the board");
+        assert!(!shape_fits(&board, &t, (1, -1)), "it's left of the board");
+        assert!(!shape_fits(&board, &t, (-1, 1)), "it's below the board");
+        // only cells matter, not the bounding box of the shape.
+        assert!(shape_fits(&board, &down, (0, 1)));
+        assert!(!shape_fits(&board, &down, (0, 0)));
+    }
+
+    #[test]
+    fn try_move() {
+        let mut g = game_with_shape("##\n##", (0, 0));
+        assert_eq!(g.try_move((0, -1)), Err(MoveError::OutOfBounds));
+        assert_eq!(g.try_move((-1, 0)), Err(MoveError::OutOfBounds));
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 0));
+
+        g.level[(1, 2)] = true;
+        assert_eq!(g.try_move((0, 1)), Err(MoveError::Collision));
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 0));
+        assert_eq!(g.try_move((1, 0)), Ok(()));
+        assert_eq!(g.shape.as_ref().unwrap().pos, (1, 0));
+
+        g.handle_event(Event::Pause);
+        assert_eq!(g.try_move((1, 0)), Err(MoveError::NotPlaying));
+        assert_eq!(
+            Game::new((10, 10)).try_move((0, 1)),
+            Err(MoveError::NotPlaying)
+        );
+    }
+
+    #[test]
+    fn slide_into_gaps() {
+        // a notch to the right of the shape, a row below it.
+        let notch = |slide| {
+            let mut g = game_with_shape("##", (1, 0));
+            g.config.slide_into_gaps = slide;
+            g.level[(0, 0)] = true;
+            g.level[(1, 2)] = true;
+            g
+        };
+        let mut g = notch(false);
+        assert_eq!(g.try_move((0, 1)), Err(MoveError::Collision));
+        assert_eq!(g.active_piece().unwrap().1, (1, 0));
+
+        let mut g = notch(true);
+        assert!(g.can_move((0, 1)));
+        assert_eq!(g.try_move((0, 1)), Ok(()));
+        assert_eq!(g.active_piece().unwrap().1, (0, 1));
+        // it doesn't slide when there is no room below either.
+        assert_eq!(g.try_move((0, -1)), Err(MoveError::Collision));
+        assert_eq!(g.active_piece().unwrap().1, (0, 1));
+        // nor out of the level or when the move doesn't collide.
+        let mut g = notch(true);
+        assert_eq!(g.try_move((0, -1)), Err(MoveError::OutOfBounds));
+        g.level[(1, 2)] = false;
+        assert_eq!(g.try_move((0, 1)), Ok(()));
+        assert_eq!(g.active_piece().unwrap().1, (1, 1));
+    }
+
+    #[test]
+    fn placements() {
+        let placements = |piece| {
+            let config = GameConfig::new((22, 10)).piece_sequence(vec![piece]);
+            let mut g = Game::with_config(config).unwrap();
+            g.handle_event(Event::Start);
+            g.enumerate_placements()
+        };
+
+        // the square only moves sideways, it fits in 9 columns.
+        let square = placements(0);
+        assert_eq!(square.len(), 9);
+        assert!(square
+            .iter()
+            .all(|p| p.rotation == 0 && p.landing_pos.0 == 0));
+        // the T lies in 8 columns and stands in 9, with each of its 4 sides down.
+        assert_eq!(placements(6).len(), 8 + 9 + 8 + 9);
+        // the upright and flat stick, turning it twice looks the same.
+        assert_eq!(placements(1).len(), 7 + 10);
+        // S and Z look the same turned by 180°.
+        assert_eq!(placements(4).len(), 8 + 9);
+
+        // the stack stops the square from moving to the right.
+        let config = GameConfig::new((22, 10)).piece_sequence(vec![0]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        let (_, pos) = g.active_piece().unwrap();
+        for row in 0..22 {
+            g.level[(row, 8)] = true;
+        }
+        let square = g.enumerate_placements();
+        assert_eq!(square.len(), 7);
+        assert_eq!(square.iter().map(|p| p.col_offset).max(), Some(6 - pos.1));
+        assert_eq!(g.active_piece().unwrap().1, pos);
+
+        g.handle_event(Event::Pause);
+        assert!(g.enumerate_placements().is_empty());
+    }
+
+    #[test]
+    fn ghost_and_rotated_piece() {
+        let mut g = game_with_shape("####", (6, 3));
+        for row in 0..3 {
+            g.level[(row, 4)] = true;
+        }
+        assert_eq!(g.ghost_position(), Some((3, 3)));
+
+        let (rotated, pos) = g.rotated_piece().unwrap();
+        assert_eq!(rotated.to_string(), "#\n#\n#\n#");
+        assert_eq!(pos, (5, 4));
+        assert_eq!(g.landing_position(&rotated, pos), (3, 4));
+        // the game is left as it is.
+        assert_eq!(g.shape.as_ref().unwrap().shape.to_string(), "####");
+        assert_eq!(g.shape.as_ref().unwrap().pos, (6, 3));
+
+        g.handle_event(Event::Pause);
+        assert_eq!(g.rotated_piece(), None);
+    }
+
+    #[test]
+    fn can_move_and_rotate() {
+        // a stick lying at the left wall with a stack on its right
+        let mut g = game_with_shape("####", (0, 0));
+        for row in 0..3 {
+            g.level[(row, 4)] = true;
+        }
+        g.level[(1, 0)] = true;
+        g.level[(1, 1)] = true;
+        g.level[(1, 2)] = true;
+        g.level[(1, 3)] = true;
+        g.level[(3, 1)] = true;
+        let before = g.shape.clone().unwrap();
+
+        assert!(!g.can_move((0, -1)), "blocked by the wall");
+        assert!(!g.can_move((0, 1)), "blocked by the stack");
+        assert!(!g.can_move((1, 0)), "blocked by the stack");
+        assert!(!g.can_rotate());
+        // there is room for the last upward kick now.
+        g.level[(3, 1)] = false;
+        assert!(g.can_rotate());
+
+        let after = g.shape.as_ref().unwrap();
+        assert_eq!(after.pos, before.pos);
+        assert_eq!(after.shape.to_string(), before.shape.to_string());
+
+        g.handle_event(Event::Pause);
+        assert!(!g.can_rotate());
+    }
+
+    #[test]
+    fn rotate_kicks_sideways() {
+        let mut g = game_with_shape("####", (5, 6));
+        g.handle_event(Event::Rotate);
+        g.handle_event(Event::Right);
+        let s = g.shape.as_ref().unwrap();
+        assert_eq!(s.pos, (4, 8));
+
+        // there is no room on the right so the shape is kicked to the left.
+        g.handle_event(Event::Rotate);
+        let s = g.shape.as_ref().unwrap();
+        assert_eq!(s.shape.to_string(), "####");
+        assert_eq!(s.pos, (5, 6));
+    }
+
+    #[test]
+    fn rotate_t_around_center() {
+        // the center of a T is the only cell with 3 neighbours.
+        let center = |g: &Game| {
+            let s = g.shape.as_ref().unwrap();
+            let cells = s.shape.cells();
+            let occupied = |r: isize, c: isize| {
+                r >= 0
+                    && c >= 0
+                    && (r as usize) < cells.rows
+                    && (c as usize) < cells.columns
+                    && cells[(r as usize, c as usize)]
+            };
+            let (r, c) = (0..cells.rows as isize)
+                .flat_map(|r| (0..cells.columns as isize).map(move |c| (r, c)))
+                .find(|&(r, c)| {
+                    occupied(r, c)
+                        && [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                            .iter()
+                            .filter(|(dr, dc)| occupied(r + dr, c + dc))
+                            .count()
+                            == 3
+                })
+                .unwrap();
+            (s.pos.0 + r, s.pos.1 + c)
+        };
+
+        // a parsed shape rotates around its center, so take the T of the
+        // factory for its pivot.
+        let mut g = game_with_shape("#", (4, 4));
+        g.shape.as_mut().unwrap().shape = g.shapes_factory.shapes[6].clone();
+        assert_eq!(center(&g), (4, 5));
+        for _ in 0..4 {
+            assert!(g.rotate_shape());
+            assert_eq!(center(&g), (4, 5));
+        }
+        assert_eq!(g.shape.as_ref().unwrap().pos, (4, 4));
+    }
+
+    #[test]
+    fn rotate_stick_on_floor() {
+        // the stick rotates around its middle, so rotated in place it would
+        // stick out under the floor.
+        let mut g = game_with_shape("####", (0, 3));
+        let s = g.shape.as_mut().unwrap();
+        s.shape = g.shapes_factory.shapes[1].clone();
+        s.index = 1;
+        let mut in_place = s.clone();
+        let offset = in_place.shape.rotate_around_pivot();
+        in_place.pos = (in_place.pos.0 + offset.0, in_place.pos.1 + offset.1);
+        assert!(g.check_shape_out_of_bound(Some(&in_place)));
+
+        assert!(g.rotate_shape());
+        let s = g.shape.as_ref().unwrap();
+        assert_eq!(s.shape.to_string(), "#\n#\n#\n#");
+        assert_eq!(s.pos.1, in_place.pos.1);
+        assert_eq!(s.pos.0, 0, "kicked up onto the floor");
+        assert!(!g.check_shape_out_of_bound(None));
+        assert!(!g.check_collision(None));
+    }
+
+    #[test]
+    fn rotate_kicks_upward() {
+        // a Z under an overhang with no room on either side
+        let mut g = game_with_shape("##.\n.##", (0, 0));
+        g.level[(0, 0)] = true;
+        g.level[(2, 2)] = true;
+        for row in 0..4 {
+            g.level[(row, 3)] = true;
+        }
+        assert!(g.rotate_shape());
+        let s = g.shape.as_ref().unwrap();
+        assert_eq!(s.shape.to_string(), ".#\n##\n#.");
+        assert_eq!(s.pos, (1, 0));
+        assert!(!g.check_collision(None));
+
+        // nothing fits after the kicks are exhausted
+        let mut g = game_with_shape("####", (0, 0));
+        for row in 1..4 {
+            g.level[(row, 0)] = true;
+            g.level[(row, 1)] = true;
+            g.level[(row, 2)] = true;
+        }
+        for col in 3..10 {
+            g.level[(1, col)] = true;
+        }
+        assert!(!g.rotate_shape());
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 0));
+    }
+
+    #[test]
+    fn next_shapes() {
+        let config = GameConfig::new((22, 10)).seed(5).preview_count(5);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+
+        let next: Vec<Shape> = g.next_shapes(5).into_iter().cloned().collect();
+        assert_eq!(next.len(), 5);
+        for (i, shape) in next.into_iter().enumerate() {
+            // lock the current shape on the floor, the next one in the queue
+            // should come.
+            let s = g.shape.as_mut().unwrap();
+            s.shape = "#".parse().unwrap();
+            s.pos = (0, i as isize);
+            g.tick();
+            assert_eq!(g.shape.as_ref().unwrap().shape, shape);
+            assert_eq!(g.next_shapes(MAX_PREVIEW).len(), 5);
+        }
+
+        // the preview count is clamped.
+        assert_eq!(GameConfig::new((22, 10)).preview_count(9).preview_count, 5);
+        assert_eq!(GameConfig::new((22, 10)).preview_count(0).preview_count, 1);
+    }
+
+    #[test]
+    fn defaults() {
+        let config = GameConfig::default();
+        assert_eq!(config.size, (20, 10));
+        assert_eq!(config.gravity, GravityCurve::Classic);
+        assert_eq!(config.preview_count, 3);
+        assert_eq!(config.seed, None);
+
+        let g = Game::default();
+        assert_eq!((g.level.rows, g.level.columns), (20, 10));
+        assert_eq!(g.config, config);
+    }
+
+    fn event() -> impl Strategy<Value = Event> {
+        prop_oneof![
+            Just(Event::Start),
+            Just(Event::Left),
+            Just(Event::Right),
+            Just(Event::Rotate),
+            Just(Event::Pause),
+            Just(Event::SoftDrop),
+            any::<bool>().prop_map(Event::SoftDropHeld),
+            Just(Event::HardDrop),
+            Just(Event::Hold),
+            Just(Event::Undo),
+            Just(Event::MoveToWall(Direction::Left)),
+            Just(Event::MoveToWall(Direction::Right)),
+            (0..MAX_PREVIEW + 1).prop_map(Event::PickPreview),
+            Just(Event::RetryPiece),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn random_events_keep_invariants(
+            seed in any::<u64>(),
+            spawn_delay in 0..3u32,
+            clear_delay in 0..3u32,
+            batches in prop::collection::vec(prop::collection::vec(event(), 0..8), 1..60),
+        ) {
+            let config = GameConfig::new((12, 8))
+                .seed(seed)
+                .undo(true)
+                .spawn_delay_ticks(spawn_delay)
+                .clear_delay_ticks(clear_delay);
+            let mut g = Game::with_config(config).unwrap();
+            g.assert_invariants();
+            g.handle_event(Event::Start);
+            for events in &batches {
+                g.apply_events(events);
+                g.assert_invariants();
+                g.tick();
+                g.assert_invariants();
+            }
+        }
+    }
+
+    #[test]
+    fn clone_game() {
+        let mut g = Game::with_config(GameConfig::new((22, 10)).seed(9)).unwrap();
+        g.handle_event(Event::Start);
+        for _ in 0..30 {
+            g.tick();
+        }
+
+        // the clone plays exactly like the original.
+        let mut branch = g.clone();
+        let script = [Event::Left, Event::Rotate, Event::HardDrop, Event::Right];
+        for _ in 0..4 {
+            g.apply_events(&script);
+            branch.apply_events(&script);
+            g.tick();
+            branch.tick();
+        }
+        assert_eq!(branch.render_ascii(), g.render_ascii());
+        assert_eq!(branch.stats(), g.stats());
+        assert_eq!(branch.rng_state(), g.rng_state());
+
+        // advancing the clone doesn't change the original.
+        assert_eq!(g.state, State::Playing);
+        let before = g.render_ascii();
+        branch.handle_event(Event::HardDrop);
+        assert_ne!(branch.render_ascii(), before);
+        assert_eq!(g.render_ascii(), before);
+    }
+
+    #[test]
+    fn undo_lock() {
+        let config = GameConfig::new((10, 10)).undo(true);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        let s = g.shape.as_mut().unwrap();
+        s.shape = "##".parse().unwrap();
+        s.pos = (3, 2);
+        g.handle_event(Event::HardDrop);
+        assert!(g.level[(0, 2)]);
+
+        g.handle_event(Event::Undo);
+        assert!(g.level.iter().all(|c| !c));
+        let s = g.shape.as_ref().unwrap();
+        assert_eq!(s.pos, (0, 2));
+        assert_eq!(s.shape.to_string(), "##");
+        assert_eq!(g.stats().pieces, 0);
+        assert!(!g.undo(), "there is nothing more to undo");
+    }
+
+    #[test]
+    fn undo_history_is_bounded() {
+        // each square fills two rows so the game never ends.
+        let config = GameConfig::new((10, 2)).undo(true).piece_sequence(vec![0]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        for _ in 0..UNDO_LIMIT + 5 {
+            g.handle_event(Event::HardDrop);
+        }
+        assert_eq!(g.state, State::Playing);
+        let mut undone = 0;
+        while g.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_LIMIT);
+        assert_eq!(g.stats().pieces, 5);
+
+        let mut g = Game::with_config(GameConfig::new((10, 10))).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::HardDrop);
+        g.handle_event(Event::Undo);
+        assert_eq!(g.stats().pieces, 1);
+    }
+
+    #[test]
+    fn simulate() {
+        let mut g = Game::with_config(GameConfig::new((10, 6)).seed(2)).unwrap();
+        let events: [&[Event]; 3] = [&[Event::Start], &[Event::Left, Event::Left], &[]];
+        let frames: Vec<_> = g.simulate(&events, 5).collect();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(g.elapsed_ticks(), 5);
+        assert!(frames.iter().all(|f| f.len() == 10 && f[0].len() == 6));
+        // the shape falls a row every frame.
+        let top = |frame: &Vec<Vec<bool>>| frame.iter().position(|row| row.contains(&true));
+        assert_eq!(top(&frames[3]), top(&frames[2]).map(|r| r + 1));
+
+        let display = g.render();
+        let last: Vec<Vec<bool>> = (0..display.rows)
+            .rev()
+            .map(|row| {
+                (0..display.columns)
+                    .map(|col| display[(row, col)])
+                    .collect()
+            })
+            .collect();
+        assert_eq!(frames[4], last);
+    }
+
+    #[test]
+    fn checksum() {
+        let start = || {
+            let mut g = Game::with_config(GameConfig::new((22, 10)).seed(4)).unwrap();
+            g.handle_event(Event::Start);
+            g
+        };
+        let (mut g1, mut g2) = (start(), start());
+        assert_eq!(g1.checksum(), g2.checksum());
+        for event in [Event::Left, Event::Rotate, Event::HardDrop, Event::Right] {
+            g1.handle_event(event);
+            g2.handle_event(event);
+            assert_eq!(g1.checksum(), g2.checksum());
+        }
+
+        let same = g1.checksum();
+        g2.level[(0, 9)] = !g2.level[(0, 9)];
+        assert_ne!(g2.checksum(), same);
+        g2.level[(0, 9)] = !g2.level[(0, 9)];
+        assert_eq!(g2.checksum(), same);
+
+        g2.handle_event(Event::Left);
+        assert_ne!(g2.checksum(), same);
+        g1.handle_event(Event::Pause);
+        assert_ne!(g1.checksum(), same);
+    }
+
+    #[test]
+    fn first_piece() {
+        let mut followed_by_t = 0;
+        for seed in 0..20 {
+            let config = GameConfig::new((22, 10)).seed(seed);
+            let mut random = Game::with_config(config.clone()).unwrap();
+            random.handle_event(Event::Start);
+            let mut g = Game::with_config(config.first_piece(PieceKind::T)).unwrap();
+            g.handle_event(Event::Start);
+            assert_eq!(g.current_piece_kind(), Some(PieceKind::T));
+
+            // the shapes are random again after the first one.
+            g.handle_event(Event::HardDrop);
+            assert_eq!(g.current_piece_kind(), random.current_piece_kind());
+            if g.current_piece_kind() == Some(PieceKind::T) {
+                followed_by_t += 1;
+            }
+
+            // and every game starts with it.
+            g.restart();
+            assert_eq!(g.current_piece_kind(), Some(PieceKind::T));
+        }
+        assert!(followed_by_t < 20);
+
+        let config = GameConfig::new((10, 10))
+            .shapes(vec!["##".parse().unwrap()])
+            .first_piece(PieceKind::I);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::UnknownFirstPiece { kind: PieceKind::I })
+        );
+    }
+
+    #[test]
+    fn piece_kinds() {
+        let factory = ShapesFactory::with_seed(1);
+        let kinds: HashSet<_> = factory.kinds().iter().copied().collect();
+        let expected = [
+            PieceKind::I,
+            PieceKind::O,
+            PieceKind::T,
+            PieceKind::S,
+            PieceKind::Z,
+            PieceKind::J,
+            PieceKind::L,
+        ];
+        assert_eq!(factory.kinds().len(), factory.shapes().len());
+        assert_eq!(kinds, expected.into_iter().collect());
+        assert_eq!(factory.kind(6), Some(PieceKind::T));
+        assert_eq!(factory.shapes()[6].to_string(), ".#.\n###");
+        assert_eq!(factory.kind(1), Some(PieceKind::I));
+        assert_eq!(factory.shapes()[1].to_string(), "####");
+
+        let mut g = game_with_shape("##", (3, 2));
+        g.shape.as_mut().unwrap().index = 6;
+        assert_eq!(g.current_piece_kind(), Some(PieceKind::T));
+        g.shape = None;
+        assert_eq!(g.current_piece_kind(), None);
+
+        let config = GameConfig::new((10, 10)).shapes(vec!["##".parse().unwrap()]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.current_piece_kind(), None);
+    }
+
+    #[test]
+    fn pieces_by_kind() {
+        let config = GameConfig::new((22, 10)).piece_sequence(vec![6, 1, 6, 0]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        for _ in 0..5 {
+            g.handle_event(Event::HardDrop);
+        }
+        assert_eq!(g.stats().pieces, 5);
+        assert_eq!(g.stats().by_kind(PieceKind::T), 3);
+        assert_eq!(g.stats().by_kind(PieceKind::I), 1);
+        assert_eq!(g.stats().by_kind(PieceKind::O), 1);
+        assert_eq!(g.stats().by_kind(PieceKind::S), 0);
+    }
+
+    #[test]
+    fn stop() {
+        let mut g = game_with_shape("##", (0, 0));
+        g.tick();
+        g.stop();
+        assert_eq!(g.state, State::Init);
+        assert!(g.render().iter().all(|c| !c));
+        g.tick();
+        assert_eq!(g.state, State::Init);
+        assert_eq!(g.stats().pieces, 0);
+
+        g.handle_event(Event::Start);
+        assert_eq!(g.state, State::Playing);
+        assert!(g.shape.is_some());
+    }
+
+    #[test]
+    fn restart_while_paused() {
+        let mut g = Game::with_config(GameConfig::new((10, 10)).seed(1)).unwrap();
+        g.handle_event(Event::Start);
+        clear_one_line(&mut g);
+        g.level[(3, 3)] = true;
+        g.handle_event(Event::Pause);
+        assert_eq!(g.state, State::Paused);
+
+        g.restart();
+        assert_eq!(g.state, State::Playing);
+        assert_eq!(g.stats(), &Stats::default());
+        assert_eq!(g.elapsed_ticks(), 0);
+        assert!(g.level.iter().all(|c| !c));
+    }
+
+    #[test]
+    fn height_profile() {
+        let mut g = Game::new((6, 4));
+        assert_eq!(g.column_heights(), vec![0, 0, 0, 0]);
+        assert_eq!(g.aggregate_height(), 0);
+        assert_eq!(g.hole_count(), 0);
+
+        // from the bottom: "##.#", ".#..", "#...", "...#"
+        for pos in [(0, 0), (0, 1), (0, 3), (1, 1), (2, 0), (3, 3)] {
+            g.level[pos] = true;
+        }
+        assert_eq!(g.column_heights(), vec![3, 2, 0, 4]);
+        assert_eq!(g.aggregate_height(), 9);
+        assert_eq!(g.hole_count(), 3);
+    }
+
+    #[test]
+    fn stack_height() {
+        let mut g = Game::new((6, 4));
+        assert_eq!(g.stack_height(), 0);
+
+        // a single cell floating in the middle counts from the floor.
+        g.level[(2, 1)] = true;
+        assert_eq!(g.stack_height(), 3);
+        g.level[(0, 3)] = true;
+        assert_eq!(g.stack_height(), 3);
+
+        for row in 0..6 {
+            g.level[(row, 0)] = true;
+        }
+        assert_eq!(g.stack_height(), 6);
+    }
+
+    #[test]
+    fn gravity_curve() {
+        let ms = Duration::from_millis;
+        let linear = GravityCurve::Linear {
+            step: ms(10),
+            min: ms(50),
+        };
+        assert_eq!(linear.interval(ms(200), 0), ms(200));
+        assert_eq!(linear.interval(ms(200), 5), ms(150));
+        assert_eq!(linear.interval(ms(200), 50), ms(50));
+        assert_eq!(GravityCurve::Constant.interval(ms(200), 50), ms(200));
+
+        let intervals = |curve: &GravityCurve| {
+            [0, 5, 15].map(|level| curve.interval(ms(200), level).as_micros())
+        };
+        assert_eq!(intervals(&linear), [200_000, 150_000, 50_000]);
+        assert_eq!(
+            intervals(&GravityCurve::Classic),
+            [800_000, 383_333, 66_666]
+        );
+        assert_eq!(
+            intervals(&GravityCurve::Guideline),
+            [1_000_000, 262_003, 4_263]
+        );
+        let custom = GravityCurve::Custom(GravityFn::new(move |level| ms(100) / (level + 1)));
+        assert_eq!(intervals(&custom), [100_000, 16_666, 6_250]);
+        assert_eq!(custom.clone(), custom);
+        assert_ne!(
+            custom,
+            GravityCurve::Custom(GravityFn::new(move |_| ms(100)))
+        );
+        assert_eq!(GravityCurve::Classic.interval(ms(200), 100), ms(1000) / 60);
+    }
+
+    #[test]
+    fn level_goes_up_with_lines() {
+        let config = GameConfig::new((6, 10)).difficulty(Difficulty::Hard);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.level_number(), 5);
+        let start_interval = g.gravity_interval();
+
+        for _ in 0..LINES_PER_LEVEL {
+            for col in 0..10 {
+                g.level[(0, col)] = true;
+            }
+            g.eliminate_rows();
+        }
+        assert_eq!(g.stats().lines, LINES_PER_LEVEL);
+        assert_eq!(g.level_number(), 6);
+        assert!(g.gravity_interval() < start_interval);
+    }
+
+    /// Fill the bottom row of the level and put a single cell shape right above
+    /// it, so the row is cleared on the next tick.
+    #[test]
+    fn level_too_narrow() {
+        let err = Game::with_config(GameConfig::new((22, 1))).err().unwrap();
+        assert!(matches!(err, ConfigError::ShapeTooLarge { index: 0, .. }));
+        assert!(Game::with_config(GameConfig::new((3, 3))).is_err());
+        // the stick fits lying down.
+        assert!(Game::with_config(GameConfig::new((3, 4))).is_ok());
+    }
+
+    #[test]
+    fn custom_shapes_must_fit() {
+        let stick: Shape = "#\n#\n#\n#".parse().unwrap();
+        let long: Shape = "#####".parse().unwrap();
+        let config = GameConfig::new((22, 1)).shapes(vec![stick.clone()]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.state, State::Playing);
+
+        let config = GameConfig::new((4, 4)).shapes(vec![stick, long.clone()]);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::ShapeTooLarge {
+                index: 1,
+                shape: long,
+                size: (4, 4)
+            })
+        );
+        let config = GameConfig::new((4, 4)).shapes(vec![]);
+        assert_eq!(Game::with_config(config).err(), Some(ConfigError::NoShapes));
+    }
+
+    #[test]
+    fn validate_config() {
+        let normal = || GameConfig::new((22, 10)).difficulty(Difficulty::Normal);
+        assert_eq!(normal().validate(), Ok(()));
+        // a game is created without a base tick, but it can't be played in
+        // real time.
+        let untimed = || {
+            normal()
+                .gravity(GravityCurve::Constant)
+                .base_tick(Duration::ZERO)
+        };
+        assert!(Game::with_config(untimed()).is_ok());
+        assert_eq!(
+            untimed().validate(),
+            Err(ConfigError::GravityTooFast { level: 0 })
+        );
+        assert_eq!(
+            GameConfig::new((0, 10))
+                .difficulty(Difficulty::Normal)
+                .validate(),
+            Err(ConfigError::Multiple(vec![
+                ConfigError::EmptyLevel { size: (0, 10) },
+                ConfigError::ShapeTooLarge {
+                    index: 0,
+                    shape: ShapesFactory::with_seed(0).shapes[0].clone(),
+                    size: (0, 10)
+                },
+            ]))
+        );
+        let mut config = normal();
+        config.preview_count = 0;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidPreviewCount { count: 0 })
+        );
+
+        let config = normal()
+            .initial_board(vec![vec![true; 9]])
+            .blocked_mask(vec![vec![false; 10]; 23])
+            .piece_sequence(vec![0, 7]);
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError::Multiple(vec![
+                ConfigError::InitialBoardRow {
+                    row: 0,
+                    expected: 10,
+                    found: 9
+                },
+                ConfigError::BlockedMaskTooHigh { rows: 23, max: 22 },
+                ConfigError::InvalidSequenceIndex {
+                    position: 1,
+                    index: 7
+                },
+            ])
+        );
+        assert!(err
+            .to_string()
+            .starts_with("there are 3 problems:\n- row 0"));
+        // a game reports the first one.
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::InitialBoardRow {
+                row: 0,
+                expected: 10,
+                found: 9
+            })
+        );
+
+        let zero = GravityFn::new(|level| {
+            Duration::from_millis(100).saturating_sub(Duration::from_millis(level as u64 * 10))
+        });
+        assert_eq!(
+            normal().gravity(GravityCurve::Custom(zero)).validate(),
+            Err(ConfigError::GravityTooFast { level: 10 })
+        );
+    }
+
+    #[test]
+    fn initial_board() {
+        // four rows with a hole in the first column, so the stick clears them.
+        let board = vec![(0..10).map(|col| col != 0).collect::<Vec<_>>(); 4];
+        let config = GameConfig::new((10, 10)).initial_board(board);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.aggregate_height(), 36);
+        assert_eq!(g.hole_count(), 0);
+
+        let s = g.shape.as_mut().unwrap();
+        s.shape = g.shapes_factory.shapes[1].clone();
+        s.shape.rotate();
+        s.pos = (5, 0);
+        for _ in 0..6 {
+            g.tick();
+        }
+        assert_eq!(g.stats().lines, 4);
+        assert!(g.level.iter().all(|c| !c));
+
+        // the game starts from the same board again.
+        g.restart();
+        assert_eq!(g.aggregate_height(), 36);
+    }
+
+    #[test]
+    fn blocked_mask() {
+        let mut mask = vec![vec![false; 10]; 2];
+        mask[0][4] = true;
+        mask[1] = vec![
+            true, false, false, false, false, false, false, false, false, true,
+        ];
+        let config = GameConfig::new((10, 10)).blocked_mask(mask);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert!(g.level[(0, 4)] && g.level[(1, 0)] && g.level[(1, 9)]);
+
+        // the shape rests on the blocked cell and couldn't move into it.
+        let s = g.shape.as_mut().unwrap();
+        s.shape = "#".parse().unwrap();
+        s.pos = (1, 4);
+        assert_eq!(g.try_move((-1, 0)), Err(MoveError::Collision));
+        g.shape.as_mut().unwrap().pos = (1, 0);
+        assert!(g.check_collision(None));
+
+        // the blocked cells aren't counted to clear a row, nor cleared, and
+        // the cells above them don't fall.
+        for col in 0..10 {
+            g.level[(0, col)] = true;
+        }
+        g.level[(2, 0)] = true;
+        g.level[(2, 4)] = true;
+        assert_eq!(g.eliminate_rows(), [0]);
+        assert_eq!(
+            g.render_ascii().lines().rev().take(3).collect::<Vec<_>>(),
+            vec!["#...#.....", "#........#", "....#....."]
+        );
+        assert_eq!(g.stats().lines, 1);
+    }
+
+    #[test]
+    fn row_queries() {
+        let board = vec![
+            vec![true; 4],
+            vec![true, false, true, true],
+            vec![false; 4],
+            vec![false, false, true, false],
+        ];
+        let config = GameConfig::new((6, 4)).initial_board(board);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        let full: Vec<_> = (0..6).map(|row| g.is_row_full(row)).collect();
+        let empty: Vec<_> = (0..6).map(|row| g.is_row_empty(row)).collect();
+        assert_eq!(full, [true, false, false, false, false, false]);
+        assert_eq!(empty, [false, false, true, false, true, true]);
+
+        // the blocked cells are neither filled nor empty cells.
+        let mask = vec![
+            vec![false; 4],
+            vec![false, true, false, false],
+            vec![true; 4],
+        ];
+        let config = GameConfig::new((6, 4)).blocked_mask(mask);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert!(g.is_row_empty(1) && !g.is_row_full(1));
+        assert!(g.is_row_empty(2) && !g.is_row_full(2));
+        for col in [0, 2, 3] {
+            g.level[(1, col)] = true;
+        }
+        assert!(g.is_row_full(1) && !g.is_row_empty(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn row_query_out_of_level() {
+        Game::new((6, 4)).is_row_empty(6);
+    }
+
+    #[test]
+    fn blocked_rows_never_clear() {
+        let config = GameConfig::new((10, 4))
+            .blocked_mask(vec![vec![true; 4], vec![true, false, false, true]]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.shape = None;
+        assert!(g.eliminate_rows().is_empty());
+        g.level[(1, 1)] = true;
+        assert!(g.eliminate_rows().is_empty());
+        g.level[(1, 2)] = true;
+        assert_eq!(g.eliminate_rows(), [1]);
+        assert!(g.level[(0, 1)] && g.level[(1, 0)] && !g.level[(1, 1)]);
+
+        let config = GameConfig::new((10, 4)).blocked_mask(vec![vec![true; 3]]);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::BlockedMaskRow {
+                row: 0,
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn clear_rules() {
+        // clearing the second row leaves a cell floating above the hole of
+        // the first one.
+        let board = vec![
+            vec![true, true, false, true],
+            vec![true, true, true, true],
+            vec![false, false, true, false],
+        ];
+        let clear = |rule| {
+            let config = GameConfig::new((6, 4))
+                .initial_board(board.clone())
+                .clear_rule(rule);
+            let mut g = Game::with_config(config).unwrap();
+            g.clear();
+            let cleared = g.eliminate_rows();
+            (cleared, g.to_rle(), g.stats().score)
+        };
+        assert_eq!(
+            clear(ClearRule::Naive),
+            (vec![1], "2#1.1#/2.1#1.".to_string(), 100)
+        );
+        // the cell falls into the hole and clears the first row too.
+        assert_eq!(clear(ClearRule::Cascade), (vec![1, 0], "".to_string(), 200));
+    }
+
+    #[test]
+    fn cascade_groups() {
+        // the L stays in one piece, it rests on the cell under its foot even
+        // though the column under its top is empty.
+        let mut g =
+            Game::with_config(GameConfig::new((6, 4)).clear_rule(ClearRule::Cascade)).unwrap();
+        g.level = level_from_rows(
+            &[
+                vec![false, false, true, false],
+                vec![false, false, false, false],
+                vec![false, false, true, false],
+                vec![false, true, true, false],
+                vec![false, false, false, true],
+            ],
+            (6, 4),
+        );
+        g.cascade_cells();
+        // the cell at the top falls until it sticks to the side of the L.
+        assert_eq!(g.to_rle(), "2.1#1./2.1#1./1.3#");
+    }
+
+    #[test]
+    fn flip_board() {
+        let mut g = Game::with_config(GameConfig::new((10, 6)).seed(3)).unwrap();
+        g.handle_event(Event::Start);
+        g.level = level_from_rows(
+            &[
+                vec![true, true, false, true, true, true],
+                vec![true, false, false, false, false, false],
+            ],
+            (10, 6),
+        );
+        let original = g.level.clone();
+
+        assert!(g.flip_board());
+        assert!(g.level[(9, 5)]);
+        assert!(!g.level[(9, 3)]);
+        assert!(g.level[(8, 5)]);
+        assert_eq!(g.level.iter().filter(|c| **c).count(), 6);
+        // the shape overlaps the cells now at the top and doesn't fit where it
+        // spawns either.
+        assert_eq!(g.state, State::End);
+        assert_eq!(g.summary().unwrap().reason, EndReason::TopOut);
+
+        assert!(g.flip_board());
+        assert_eq!(g.level, original);
+
+        // the shape stays where it is if it still fits.
+        let mut g = game_with_shape("##", (5, 2));
+        g.level[(9, 9)] = true;
+        assert!(g.flip_board());
+        assert!(g.level[(0, 0)]);
+        assert_eq!(g.active_piece().unwrap().1, (5, 2));
+
+        // the blocked cells must stay where they are.
+        let mask = vec![vec![true, false, false, false, false, false]];
+        let config = GameConfig::new((10, 6)).blocked_mask(mask);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.level[(1, 1)] = true;
+        let before = g.level.clone();
+        assert!(!g.flip_board());
+        assert_eq!(g.level, before);
+    }
+
+    #[test]
+    fn rle() {
+        let mut g = Game::new((10, 10));
+        assert_eq!(g.to_rle(), "");
+        g.level[(0, 0)] = true;
+        g.level[(0, 1)] = true;
+        g.level[(2, 9)] = true;
+        assert_eq!(g.to_rle(), "2#8./10./9.1#");
+        let sparse = Game::from_rle(&g.to_rle(), (10, 10)).unwrap();
+        assert_eq!(sparse.level, g.level);
+        assert_eq!(sparse.state, State::Init);
+
+        // a checkerboard has a run for every cell.
+        for (row, col) in (0..10).flat_map(|row| (0..10).map(move |col| (row, col))) {
+            g.level[(row, col)] = (row + col) % 2 == 0;
+        }
+        let rle = g.to_rle();
+        assert_eq!(rle.split('/').count(), 10);
+        assert!(rle.starts_with("1#1.1#"));
+        let mut dense = Game::from_rle(&rle, (10, 10)).unwrap();
+        assert_eq!(dense.level, g.level);
+        // the cells are the initial board of the game.
+        dense.handle_event(Event::Start);
+        assert_eq!(dense.to_rle(), rle);
+    }
+
+    #[test]
+    fn rle_errors() {
+        let parse = |rle| Game::from_rle(rle, (10, 4)).map(|_| ()).unwrap_err();
+        assert_eq!(
+            parse("2#2x"),
+            ParseRleError::InvalidChar { row: 0, ch: 'x' }
+        );
+        assert_eq!(parse("4./#3."), ParseRleError::MissingCount { row: 1 });
+        assert_eq!(parse("0#4."), ParseRleError::MissingCount { row: 0 });
+        assert_eq!(parse("4./4./2"), ParseRleError::TrailingCount { row: 2 });
+        assert_eq!(
+            parse("3#"),
+            ParseRleError::Board(ConfigError::InitialBoardRow {
+                row: 0,
+                expected: 4,
+                found: 3
+            })
+        );
+        assert_eq!(
+            parse("99999999999999999999999#"),
+            ParseRleError::Board(ConfigError::InitialBoardRow {
+                row: 0,
+                expected: 4,
+                found: usize::MAX
+            })
+        );
+        assert_eq!(
+            parse(&["4."; 11].join("/")),
+            ParseRleError::Board(ConfigError::InitialBoardTooHigh { rows: 11, max: 10 })
+        );
+    }
+
+    #[test]
+    fn initial_board_must_fit() {
+        let config = GameConfig::new((10, 10)).initial_board(vec![vec![false; 10]; 11]);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::InitialBoardTooHigh { rows: 11, max: 10 })
+        );
+        let config = GameConfig::new((10, 10)).initial_board(vec![vec![false; 10], vec![true; 9]]);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::InitialBoardRow {
+                row: 1,
+                expected: 10,
+                found: 9
+            })
+        );
+    }
+
+    #[test]
+    fn piece_sequence() {
+        for seed in [1, 2, 3] {
+            let config = GameConfig::new((10, 10))
+                .seed(seed)
+                .preview_count(1)
+                .piece_sequence(vec![6, 1, 0]);
+            let mut g = Game::with_config(config).unwrap();
+            g.handle_event(Event::Start);
+            let rng = g.rng_state();
+            let mut indexes = vec![];
+            for _ in 0..7 {
+                indexes.push(g.shape.as_ref().unwrap().index);
+                g.create_new_shape();
+            }
+            assert_eq!(indexes, vec![6, 1, 0, 6, 1, 0, 6]);
+            assert_eq!(g.rng_state(), rng);
+        }
+
+        let config = GameConfig::new((10, 10)).piece_sequence(vec![0, 7]);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::InvalidSequenceIndex {
+                position: 1,
+                index: 7
+            })
+        );
+        let config = GameConfig::new((10, 10)).piece_sequence(vec![]);
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::EmptySequence)
+        );
+    }
+
+    #[test]
+    fn instant_drop() {
+        let config = GameConfig::new((22, 10))
+            .instant_drop(true)
+            .lock_delay_ticks(1);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.tick();
+        assert_eq!(g.shape.as_ref().unwrap().pos.0, 0);
+
+        // the lock delay still leaves time to slide it.
+        g.tick();
+        assert!(g.handle_event(Event::Left));
+        assert!(g.level.iter().all(|c| !c));
+        g.tick();
+        g.tick();
+        assert!(g.level.iter().any(|c| *c));
+    }
+
+    #[test]
+    fn hard_drop() {
+        let mut g = game_with_shape("##", (9, 4));
+        assert_eq!(
+            g.hard_drop(),
+            TickOutcome {
+                distance: 9,
+                locked: true,
+                lines: 0,
+                cleared_rows: vec![],
+                new_holes: 0
+            }
+        );
+        assert!(g.level[(0, 4)] && g.level[(0, 5)]);
+        assert_eq!(g.stats().score, 18);
+        assert_eq!(g.stats().pieces, 1);
+    }
+
+    #[test]
+    fn soft_drop() {
+        let mut g = game_with_shape("##", (2, 4));
+        let dropped = TickOutcome {
+            distance: 1,
+            ..TickOutcome::default()
+        };
+        assert_eq!(g.soft_drop(), dropped);
+        assert_eq!(g.soft_drop(), dropped);
+        assert_eq!(g.stats().score, 2);
+        let outcome = g.soft_drop();
+        assert_eq!(outcome.distance, 0);
+        assert!(outcome.locked);
+        assert_eq!(g.stats().score, 2);
+
+        // falling by gravity isn't scored.
+        assert_eq!(g.tick().distance, 1);
+        assert_eq!(g.stats().score, 2);
+    }
+
+    #[test]
+    fn tick_outcome_lines() {
+        let mut g = Game::new((10, 10));
+        g.handle_event(Event::Start);
+        for col in 0..10 {
+            g.level[(0, col)] = true;
+            g.level[(1, col)] = true;
+        }
+        let s = g.shape.as_mut().unwrap();
+        s.shape = "#".parse().unwrap();
+        s.pos = (2, 0);
+        let outcome = g.hard_drop();
+        assert_eq!(outcome.distance, 0);
+        assert_eq!(outcome.lines, 2);
+        assert_eq!(g.stats().score, 300);
+    }
+
+    #[test]
+    fn lock_delay() {
+        let mut g = Game::with_config(GameConfig::new((10, 10)).lock_delay_ticks(2)).unwrap();
+        g.handle_event(Event::Start);
+        let s = g.shape.as_mut().unwrap();
+        s.shape = "##".parse().unwrap();
+        s.pos = (0, 4);
+
+        // it waits 2 ticks on the floor and is locked on the third.
+        g.tick();
+        g.tick();
+        assert_eq!(g.shape.as_ref().unwrap().pos, (0, 4));
+        assert!(!g.level[(0, 4)]);
+        g.tick();
+        assert!(g.level[(0, 4)] && g.level[(0, 5)]);
+    }
+
+    #[cfg(feature = "event-log")]
+    #[test]
+    fn recent_history() {
+        let mut g = Game::with_config(GameConfig::new((22, 10)).seed(3)).unwrap();
+        assert!(g.recent_history().is_empty());
+        g.handle_event(Event::Start);
+        g.handle_event(Event::Left);
+        g.tick();
+        g.tick();
+        g.handle_event(Event::Pause);
+        let expected = [
+            (0, Event::Start, State::Playing),
+            (0, Event::Left, State::Playing),
+            (2, Event::Pause, State::Paused),
+        ];
+        let history: Vec<_> = g
+            .recent_history()
+            .iter()
+            .map(|e| (e.tick, e.event, e.state))
+            .collect();
+        assert_eq!(history, expected);
+
+        // only the last events are kept.
+        g.handle_event(Event::Start);
+        for _ in 0..EVENT_LOG_LEN {
+            g.handle_event(Event::Rotate);
+        }
+        let history = g.recent_history();
+        assert_eq!(history.len(), EVENT_LOG_LEN);
+        assert!(history.iter().all(|e| e.event == Event::Rotate));
+    }
+
+    /// Return a started game of squares in a 6×4 level, the first square is
+    /// dropped on a stack so the next one doesn't fit. The cells on the
+    /// right fall into the gaps below them and fill the two bottom rows if
+    /// `floating` is set.
+    fn topped_out_game(grace: u32, floating: bool) -> Game {
+        let config = GameConfig::new((6, 4))
+            .piece_sequence(vec![0])
+            .top_out_grace_ticks(grace);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        for row in 0..4 {
+            g.level[(row, 0)] = true;
+            g.level[(row, 1)] = true;
+        }
+        g.level[(0, 2)] = true;
+        g.level[(1, 2)] = true;
+        g.level[(2, 3)] = floating;
+        g.level[(3, 3)] = floating;
+        g.handle_event(Event::Left);
+        g.handle_event(Event::HardDrop);
+        g
+    }
+
+    #[test]
+    fn zen_mode() {
+        let config = GameConfig::new((6, 4))
+            .piece_sequence(vec![0])
+            .mode(GameMode::Zen)
+            .top_out_grace_ticks(3);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        // the rows are never full, the last column is empty.
+        for row in 0..4 {
+            for col in 0..3 {
+                g.level[(row, col)] = true;
+            }
+        }
+        g.handle_event(Event::HardDrop);
+        // the square was locked on the top and the next one doesn't fit, so
+        // the two bottom rows are thrown away.
+        assert_eq!(g.state, State::Playing);
+        assert!(g.is_row_empty(5) && g.is_row_empty(4));
+        assert_eq!(g.level.iter().filter(|c| **c).count(), 10);
+        assert_eq!(g.active_piece().unwrap().1, (4, 1));
+        assert_eq!(g.stats().lines, 0);
+
+        for _ in 0..20 {
+            g.handle_event(Event::HardDrop);
+            assert_eq!(g.state, State::Playing);
+        }
+        assert_eq!(g.lines_remaining(), None);
+    }
+
+    #[test]
+    fn top_out_grace() {
+        // without a grace it's over right away.
+        let g = topped_out_game(0, true);
+        assert_eq!(g.state, State::End);
+
+        // it's over after the grace if nothing could fall.
+        let mut g = topped_out_game(2, false);
+        assert_eq!(g.state, State::Playing);
+        assert!(g.active_piece().is_none());
+        g.assert_invariants();
+        g.tick();
+        assert_eq!(g.state, State::Playing);
+        g.tick();
+        assert_eq!(g.state, State::End);
+        assert_eq!(g.summary().unwrap().reason, EndReason::TopOut);
+
+        // the cells on the right fill a row each tick, and the square fits
+        // once both are cleared.
+        let mut g = topped_out_game(1, true);
+        assert_eq!(g.tick().lines, 1);
+        assert_eq!(g.state, State::End);
+
+        let mut g = topped_out_game(2, true);
+        assert_eq!(g.tick().lines, 1);
+        assert!(g.active_piece().is_none());
+        assert_eq!(g.tick().lines, 1);
+        assert_eq!(g.state, State::Playing);
+        assert_eq!(g.active_piece().map(|(_, pos)| pos), Some((4, 1)));
+        assert_eq!(g.stats().lines, 2);
+        g.assert_invariants();
+    }
+
+    #[test]
+    fn custom_score_table() {
+        let table = ScoreTable {
+            lines: [0, 10, 20, 30, 1000],
+            soft_drop: 0,
+            hard_drop: 5,
+        };
+        // four rows with a hole in the first column for a standing stick.
+        let board = vec![(0..10).map(|col| col != 0).collect::<Vec<_>>(); 4];
+        let config = GameConfig::new((10, 10))
+            .initial_board(board)
+            .piece_sequence(vec![1])
+            .start_level(2)
+            .score_table(table);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        // drop the stick so it has room to stand up.
+        g.apply_events(&[Event::SoftDrop; 3]);
+        assert_eq!(g.stats().score, 0);
+        g.handle_event(Event::Rotate);
+        while g.can_move((0, -1)) {
+            g.handle_event(Event::Left);
+        }
+
+        let distance = g.hard_drop().distance;
+        assert_eq!(g.stats().lines, 4);
+        assert_eq!(g.stats().score, distance * 5 + 1000 * 3);
+    }
+
+    #[test]
+    fn soft_drop_contact() {
+        // a shape a row above the stack, with a lock delay of 2 ticks.
+        let above_stack = |lock_on_contact| {
+            let mut g = game_with_shape("##", (2, 0));
+            g.config.lock_delay_ticks = 2;
+            g.config.lock_on_soft_drop_contact = lock_on_contact;
+            g.level[(0, 0)] = true;
+            g
+        };
+
+        let mut g = above_stack(true);
+        let outcome = g.soft_drop();
+        assert_eq!((outcome.distance, outcome.locked), (1, true));
+        assert!(g.level[(1, 0)] && g.level[(1, 1)]);
+        assert_eq!(g.stats().pieces, 1);
+
+        let mut g = above_stack(false);
+        let outcome = g.soft_drop();
+        assert_eq!((outcome.distance, outcome.locked), (1, false));
+        assert_eq!(g.active_piece().unwrap().1, (1, 0));
+        // the lock delay runs out before it's locked.
+        assert!(!g.tick().locked);
+        assert!(!g.tick().locked);
+        assert!(g.tick().locked);
+        assert_eq!(g.stats().pieces, 1);
+
+        // a soft drop of a shape which still could fall doesn't lock it.
+        let mut g = above_stack(true);
+        g.level[(0, 0)] = false;
+        assert!(!g.soft_drop().locked);
+        assert_eq!(g.active_piece().unwrap().1, (1, 0));
+    }
+
+    #[test]
+    fn soft_drop_tick() {
+        let mut g = Game::with_config(GameConfig::new((22, 10)).seed(1)).unwrap();
+        g.handle_event(Event::Start);
+        let row = g.active_piece().unwrap().1 .0;
+        let gravity = g.gravity_interval();
+        g.handle_event(Event::SoftDropHeld(true));
+        let fast = g.tick_interval();
+        assert_eq!(fast * SOFT_DROP_MULTIPLIER, gravity);
+        for _ in 0..3 {
+            assert_eq!(g.tick().distance, 1);
+        }
+        assert_eq!(g.active_piece().unwrap().1 .0, row - 3);
+        assert_eq!(g.stats().score, 3 * ScoreTable::guideline().soft_drop);
+        assert_eq!(g.stats().play_time, fast * 3);
+        // only every SOFT_DROP_MULTIPLIERth tick is a gravity tick.
+        assert_eq!(g.elapsed_ticks(), 0);
+        for _ in 3..SOFT_DROP_MULTIPLIER {
+            g.tick();
+        }
+        assert_eq!(g.elapsed_ticks(), 1);
+        assert_eq!(g.stats().play_time, gravity);
+
+        // a normal tick doesn't score.
+        g.handle_event(Event::SoftDropHeld(false));
+        assert_eq!(g.tick_interval(), gravity);
+        let score = g.stats().score;
+        g.tick();
+        assert_eq!(g.stats().score, score);
+
+        // the lock delay only runs on the gravity ticks.
+        let config = GameConfig::new((4, 4)).seed(1).lock_delay_ticks(1);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::SoftDropHeld(true));
+        while g.can_move((-1, 0)) {
+            g.tick();
+        }
+        // letting it go and holding it again starts over from a gravity tick.
+        g.handle_event(Event::SoftDropHeld(false));
+        g.handle_event(Event::SoftDropHeld(true));
+        for _ in 0..SOFT_DROP_MULTIPLIER * 2 - 1 {
+            assert!(!g.tick().locked);
+        }
+        assert!(g.tick().locked);
+
+        // the fastest gravity still has ticks apart.
+        let config = GameConfig::new((10, 10))
+            .base_tick(Duration::ZERO)
+            .gravity(GravityCurve::Constant);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::SoftDropHeld(true));
+        assert_eq!(g.tick_interval(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn spawn_delay() {
+        let mut g = Game::with_config(GameConfig::new((10, 10)).spawn_delay_ticks(2)).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(g.stats().pieces, 1);
+
+        // the level is frozen without a shape for 2 ticks, it spawns on the
+        // third.
+        let level = g.render();
+        for _ in 0..2 {
+            assert!(g.active_piece().is_none());
+            g.apply_events(&[
+                Event::Left,
+                Event::Rotate,
+                Event::SoftDrop,
+                Event::HardDrop,
+                Event::Hold,
+            ]);
+            assert_eq!(g.try_move((0, 1)), Err(MoveError::NoShape));
+            assert_eq!(g.render(), level);
+            g.assert_invariants();
+            g.tick();
+        }
+        assert!(g.active_piece().is_none());
+        g.tick();
+        assert!(g.active_piece().is_some());
+        assert_eq!(g.stats().pieces, 1);
+        assert_eq!(g.held_shape(), None);
+    }
+
+    #[test]
+    fn multi_hold() {
+        let config = GameConfig::new((22, 10))
+            .piece_sequence(vec![0, 1, 2, 3, 4])
+            .hold_capacity(2);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        let current = |g: &Game| g.shape.as_ref().unwrap().index;
+        let shapes = g.shapes_factory.shapes.clone();
+        assert_eq!(current(&g), 0);
+
+        // the hold fills up with new shapes taken in place of the held ones
+        g.handle_event(Event::Hold);
+        assert_eq!(current(&g), 1);
+        assert_eq!(g.held_pieces(), vec![&shapes[0]]);
+        // hold could still only be used once per shape
+        g.handle_event(Event::Hold);
+        assert_eq!(current(&g), 1);
+        assert_eq!(g.held_pieces(), vec![&shapes[0]]);
+
+        g.handle_event(Event::HardDrop);
+        assert_eq!(current(&g), 2);
+        g.handle_event(Event::Hold);
+        assert_eq!(current(&g), 3);
+        assert_eq!(g.held_pieces(), vec![&shapes[0], &shapes[2]]);
+        g.assert_invariants();
+
+        // once it's full, the shape held first comes out
+        g.handle_event(Event::HardDrop);
+        assert_eq!(current(&g), 4);
+        g.handle_event(Event::Hold);
+        assert_eq!(current(&g), 0);
+        assert_eq!(g.held_pieces(), vec![&shapes[2], &shapes[4]]);
+        assert_eq!(g.held_shape(), Some(&shapes[2]));
+        g.handle_event(Event::Hold);
+        assert_eq!(current(&g), 0);
+
+        // the sequence starts over
+        g.handle_event(Event::HardDrop);
+        assert_eq!(current(&g), 0);
+        g.handle_event(Event::Hold);
+        assert_eq!(current(&g), 2);
+        assert_eq!(g.held_pieces(), vec![&shapes[4], &shapes[0]]);
+        g.assert_invariants();
+    }
+
+    #[test]
+    fn finesse() {
+        let config = GameConfig::new((22, 10))
+            .piece_sequence(vec![0, 0, 6, 6])
+            .track_finesse(true);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        // the square to the left wall is one input
+        g.apply_events(&[Event::MoveToWall(Direction::Left), Event::HardDrop]);
+        let finesse = g.last_finesse().unwrap();
+        assert_eq!(
+            finesse,
+            Finesse {
+                inputs: 1,
+                optimal: Some(1)
+            }
+        );
+        assert!(finesse.is_optimal());
+
+        g.apply_events(&[Event::Left; 4]);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(
+            g.last_finesse(),
+            Some(Finesse {
+                inputs: 4,
+                optimal: Some(1)
+            })
+        );
+        assert_eq!(g.finesse_faults(), 1);
+
+        // rotating the T all the way around is wasted
+        g.apply_events(&[Event::Rotate; 4]);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(
+            g.last_finesse(),
+            Some(Finesse {
+                inputs: 4,
+                optimal: Some(0)
+            })
+        );
+        // one step right and a rotation
+        g.apply_events(&[Event::Right, Event::Rotate, Event::HardDrop]);
+        assert!(g.last_finesse().unwrap().is_optimal());
+        assert_eq!(g.finesse_faults(), 2);
+    }
+
+    #[test]
+    fn gravity_up() {
+        let config = GameConfig::new((10, 4))
+            .shapes(vec!["##".parse().unwrap()])
+            .gravity_direction(GravityDirection::Up);
+        let mut g = Game::with_config(config.clone()).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.active_piece().unwrap().1, (0, 1));
+        assert_eq!(g.tick().distance, 1);
+        assert_eq!(g.active_piece().unwrap().1, (1, 1));
+        assert_eq!(g.ghost_position(), Some((9, 1)));
+
+        assert_eq!(g.hard_drop().distance, 8);
+        assert!(g.level[(9, 1)] && g.level[(9, 2)]);
+        assert_eq!(g.active_piece().unwrap().1, (0, 1));
+        assert_eq!(g.column_heights(), [0, 1, 1, 0]);
+        g.handle_event(Event::HardDrop);
+        assert!(g.level[(8, 1)]);
+        g.assert_invariants();
+
+        // the rows below the cleared one rise
+        let mut board = vec![vec![false; 4]; 8];
+        board.push(vec![true, false, false, false]);
+        board.push(vec![true, false, false, true]);
+        let mut g = Game::with_config(config.initial_board(board)).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.hole_count(), 0);
+        assert_eq!(g.hard_drop().lines, 1);
+        assert!(g.level[(9, 0)]);
+        assert!(!g.level[(9, 3)] && !g.level[(8, 0)]);
+        assert_eq!(g.column_heights(), [1, 0, 0, 0]);
+        g.assert_invariants();
+    }
+
+    #[test]
+    fn no_shape_before_start() {
+        let mut g = Game::new((22, 10));
+        for event in [
+            Event::Rotate,
+            Event::Left,
+            Event::Right,
+            Event::MoveToWall(Direction::Left),
+            Event::SoftDrop,
+            Event::HardDrop,
+            Event::Hold,
+            Event::Undo,
+            Event::PickPreview(0),
+        ] {
+            g.handle_event(event);
+        }
+        assert_eq!(g.state, State::Init);
+        assert_eq!(g.try_rotate(), Err(MoveError::NotPlaying));
+        assert_eq!(g.try_move((0, 1)), Err(MoveError::NotPlaying));
+        assert_eq!(g.soft_drop(), TickOutcome::default());
+        assert_eq!(g.hard_drop(), TickOutcome::default());
+        assert_eq!(g.tick(), TickOutcome::default());
+        assert_eq!(g.render(), Conventional::new((22, 10)));
+
+        // there is no shape while waiting for the next to spawn
+        let config = GameConfig::new((22, 10))
+            .spawn_delay_ticks(2)
+            .shapes(vec!["##".parse().unwrap()]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(g.try_rotate(), Err(MoveError::NoShape));
+        assert_eq!(g.soft_drop(), TickOutcome::default());
+    }
+
+    #[test]
+    fn on_frame() {
+        let config = GameConfig::new((22, 10))
+            .seed(1)
+            .base_tick(Duration::from_millis(100));
+        let mut g = Game::with_config(config).unwrap();
+        let ms = Duration::from_millis;
+        assert!(g.on_frame(ms(500)).is_empty());
+        g.input(Event::Start);
+
+        let ticks: Vec<usize> = [30, 30, 30, 30, 250, 16, 0]
+            .into_iter()
+            .map(|dt| g.on_frame(ms(dt)).len())
+            .collect();
+        assert_eq!(ticks, [0, 0, 0, 1, 2, 0, 0]);
+        assert_eq!(g.elapsed_ticks(), 3);
+        assert_eq!(g.stats().play_time, ms(300));
+
+        // the time left before the pause is dropped
+        g.input(Event::Pause);
+        assert!(g.on_frame(ms(500)).is_empty());
+        g.input(Event::Start);
+        assert!(g.on_frame(ms(90)).is_empty());
+        assert_eq!(g.on_frame(ms(10)).len(), 1);
+        assert_eq!(g.elapsed_ticks(), 4);
+
+        let config = GameConfig::new((22, 10))
+            .base_tick(Duration::ZERO)
+            .gravity(GravityCurve::Constant);
+        let mut g = Game::with_config(config).unwrap();
+        g.input(Event::Start);
+        assert_eq!(g.on_frame(ms(16)).len(), 1);
+    }
+
+    #[test]
+    fn invisible_cells() {
+        let config = GameConfig::new((6, 4))
+            .shapes(vec!["##".parse().unwrap()])
+            .invisible_after_ticks(2);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::HardDrop);
+        assert!(g.render()[(0, 1)]);
+        g.tick();
+        assert!(g.render()[(0, 1)]);
+        g.tick();
+        assert!(!g.render()[(0, 1)]);
+        assert!(g.level[(0, 1)]);
+        assert_eq!(g.render(), {
+            let mut level = Conventional::new((6, 4));
+            for (row, col) in g.shape.as_ref().unwrap().cells() {
+                level[(row as usize, col as usize)] = true;
+            }
+            level
+        });
+
+        // the next shape lands on the hidden cells
+        g.handle_event(Event::HardDrop);
+        assert!(g.level[(1, 1)] && g.level[(1, 2)]);
+        assert!(g.render()[(1, 1)] && !g.render()[(0, 1)]);
+        g.assert_invariants();
+    }
+
+    #[test]
+    fn clear_flash() {
+        let board = vec![
+            vec![true, false, false, true],
+            vec![true, false, false, false],
+        ];
+        let config = GameConfig::new((6, 4))
+            .shapes(vec!["##".parse().unwrap()])
+            .initial_board(board)
+            .invisible_after_ticks(5)
+            .clear_flash_ticks(1);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert!(!g.render()[(1, 0)]);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(g.stats().lines, 1);
+        assert!(g.render()[(0, 0)]);
+        g.tick();
+        assert!(!g.render()[(0, 0)]);
+        assert!(g.level[(0, 0)]);
+    }
+
+    #[test]
+    fn spawn_into_nearly_full_board() {
+        let mut row = vec![true; 10];
+        row[9] = false;
+        let config = GameConfig::new((6, 10)).initial_board(vec![row; 6]);
+        let mut g = Game::with_config(config.clone()).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.state, State::End);
+        assert_eq!(g.summary().unwrap().reason, EndReason::TopOut);
+        g.handle_event(Event::Start);
+        assert_eq!(g.state, State::End);
+        g.tick();
+        assert_eq!(g.state, State::End);
+
+        let mut g = Game::with_config(config.mode(GameMode::Zen)).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.state, State::Playing);
+        g.assert_invariants();
+    }
+
+    #[test]
+    fn pick_preview() {
+        let config = GameConfig::new((22, 10))
+            .piece_sequence(vec![0, 1, 2, 3, 4, 5, 6])
+            .preview_count(3);
+        let mut g = Game::with_config(config.clone()).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::PickPreview(2));
+        assert_eq!(g.next_queue, [1, 2, 3]);
+
+        let mut g = Game::with_config(config.pick_preview(true)).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::PickPreview(2));
+        assert_eq!(g.next_queue, [3, 1, 2]);
+        // only the shapes shown could be picked
+        g.handle_event(Event::PickPreview(3));
+        assert_eq!(g.next_queue, [3, 1, 2]);
+        g.assert_invariants();
+
+        g.handle_event(Event::HardDrop);
+        assert_eq!(g.shape.as_ref().unwrap().index, 3);
+        assert_eq!(g.next_queue, [1, 2, 4]);
+    }
+
+    #[test]
+    fn hold_disabled() {
+        let config = GameConfig::new((22, 10)).seed(3).hold_capacity(0);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        let first = g.shape.as_ref().unwrap().index;
+        g.handle_event(Event::Hold);
+        assert_eq!(g.shape.as_ref().unwrap().index, first);
+        assert!(g.held_pieces().is_empty());
+    }
+
+    #[test]
+    fn lock_resets_are_capped() {
+        let config = GameConfig::new((10, 10))
+            .lock_delay_ticks(2)
+            .max_lock_resets(3);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        let s = g.shape.as_mut().unwrap();
+        s.shape = "##".parse().unwrap();
+        s.pos = (0, 4);
+        g.tick();
+
+        // each move restarts the delay, so the shape is never locked.
+        for i in 0..3 {
+            g.handle_event(if i % 2 == 0 {
+                Event::Left
+            } else {
+                Event::Right
+            });
+            g.tick();
+            g.tick();
+            assert!(g.level.iter().all(|c| !c), "locked after {} moves", i + 1);
+        }
+
+        // the cap is hit, the delay has run out and moving doesn't restart it.
+        g.handle_event(Event::Left);
+        g.tick();
+        assert!(g.level[(0, 2)] && g.level[(0, 3)]);
+    }
+
+    fn clear_one_line(g: &mut Game) {
+        for col in 0..g.level.columns {
+            g.level[(0, col)] = true;
+        }
+        let s = g.shape.as_mut().unwrap();
+        s.shape = "#".parse().unwrap();
+        s.pos = (1, 0);
+        g.tick();
+    }
+
+    #[test]
+    fn sprint_summary() {
+        let config = GameConfig::new((10, 10))
+            .mode(GameMode::Sprint { lines: 2 })
+            .start_level(1);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        clear_one_line(&mut g);
+        assert_eq!(g.summary(), None);
+        clear_one_line(&mut g);
+
+        let summary = g.summary().unwrap();
+        assert_eq!(summary.reason, EndReason::GoalReached);
+        assert_eq!(summary.score, 400);
+        assert_eq!(summary.level, 1);
+        assert_eq!(summary.lines, 2);
+        assert_eq!(summary.stats.pieces, 2);
+        assert_eq!(summary.mode, GameMode::Sprint { lines: 2 });
+        assert_eq!(summary.duration, g.gravity_interval() * 2);
+
+        g.restart();
+        assert_eq!(g.summary(), None);
+    }
+
+    #[test]
+    fn summary_json() {
+        let mut stats = Stats {
+            lines: 40,
+            score: 12_000,
+            pieces: 101,
+            play_time: Duration::from_millis(83_456),
+            completion_time: Some(Duration::from_millis(83_456)),
+            ..Stats::default()
+        };
+        stats.pieces_by_kind.insert(PieceKind::T, 15);
+        stats.pieces_by_kind.insert(PieceKind::I, 11);
+        let summary = GameSummary {
+            score: stats.score,
+            level: 4,
+            lines: stats.lines,
+            mode: GameMode::Sprint { lines: 40 },
+            duration: stats.play_time,
+            reason: EndReason::GoalReached,
+            stats,
+        };
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains(r#""duration":83456"#), "{}", json);
+        assert!(json.contains(r#""completion_time":83456"#), "{}", json);
+        assert!(
+            json.contains(r#""mode":{"Sprint":{"lines":40}}"#),
+            "{}",
+            json
+        );
+        let parsed: GameSummary = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, summary);
+        assert_eq!(parsed.stats.by_kind(PieceKind::T), 15);
+
+        let stats: Stats =
+            serde_json::from_str(&serde_json::to_string(&Stats::default()).unwrap()).unwrap();
+        assert_eq!(stats, Stats::default());
+    }
+
+    #[test]
+    fn top_out_summary() {
+        let mut g = Game::with_config(GameConfig::new((10, 10)).seed(5)).unwrap();
+        g.handle_event(Event::Start);
+        while g.state != State::End {
+            g.tick();
+        }
+        let summary = g.summary().unwrap();
+        assert_eq!(summary.reason, EndReason::TopOut);
+        assert_eq!(summary.mode, GameMode::Marathon);
+        assert_eq!(summary.stats, *g.stats());
+        assert_eq!(summary.duration, g.stats().play_time);
+    }
+
+    #[test]
+    fn play_time_excludes_pauses() {
+        let config = GameConfig::new((10, 10)).gravity(GravityCurve::Constant);
+        let mut g = Game::with_config(config).unwrap();
+        let tick = g.gravity_interval();
+        g.handle_event(Event::Start);
+        g.tick();
+        g.tick();
+
+        g.handle_event(Event::Pause);
+        g.tick();
+        g.add_play_time(Duration::from_secs(5));
+        assert_eq!(g.stats().play_time, tick * 2);
+
+        g.handle_event(Event::Start);
+        g.add_play_time(Duration::from_secs(5));
+        g.tick();
+        assert_eq!(g.stats().play_time, tick * 3 + Duration::from_secs(5));
+        #[allow(deprecated)]
+        let elapsed = g.elapsed_time();
+        assert_eq!(elapsed, g.stats().play_time);
+    }
+
+    #[test]
+    fn pieces_per_minute() {
+        let mut stats = Stats::default();
+        assert_eq!(stats.pieces_per_minute(), 0.0);
+        stats.pieces = 30;
+        stats.play_time = Duration::from_secs(20);
+        assert_eq!(stats.pieces_per_minute(), 90.0);
+    }
 
-                self.move_shape((0, -1));
-                true
-            }
-            Event::Right => {
-                if self.state != State::Playing {
-                    return true;
-                }
+    #[test]
+    fn lines_remaining() {
+        let mut g = Game::new((10, 10));
+        assert_eq!(g.lines_remaining(), None);
 
-                self.move_shape((0, 1));
-                true
-            }
-            Event::Pause => {
-                if self.state == State::Playing {
-                    self.state = State::Paused;
-                }
-                true
-            }
-            Event::Rotate => {
-                if self.state != State::Playing {
-                    return true;
-                }
+        g = Game::with_config(GameConfig::new((10, 10)).mode(GameMode::Sprint { lines: 3 }))
+            .unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(g.lines_remaining(), Some(3));
+        g.stats.lines = 2;
+        assert_eq!(g.lines_remaining(), Some(1));
+        g.stats.lines = 5;
+        assert_eq!(g.lines_remaining(), Some(0));
+    }
 
-                let s = self.shape.as_ref().unwrap();
-                let mut new_s = s.clone();
-                new_s.shape.rotate();
-                if !self.check_shape_out_of_bound(Some(&new_s))
-                    && !self.check_collision(Some(&new_s))
-                {
-                    self.shape = Some(new_s);
-                }
-                true
-            }
-        }
+    #[test]
+    fn sprint_ends_at_goal() {
+        let config = GameConfig::new((10, 10)).mode(GameMode::Sprint { lines: 2 });
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+
+        clear_one_line(&mut g);
+        assert_eq!(g.state, State::Playing);
+        assert_eq!(g.lines_remaining(), Some(1));
+        assert_eq!(g.stats().completion_time, None);
+
+        clear_one_line(&mut g);
+        assert_eq!(g.state, State::End);
+        assert_eq!(g.lines_remaining(), Some(0));
+        assert_eq!(g.stats().completion_time, Some(g.stats().play_time));
+        assert_eq!(
+            g.stats().play_time,
+            g.gravity_interval() * g.elapsed_ticks()
+        );
+        // the game could still be rendered without an active shape.
+        g.render();
     }
 
-    /// Do one tick.
-    pub fn tick(&mut self) {
-        if self.state != State::Playing {
-            return;
+    #[test]
+    fn spawn_orientations() {
+        let factory = ShapesFactory::with_seed(0);
+        let expected = [
+            (PieceKind::O, "##\n##"),
+            (PieceKind::I, "####"),
+            (PieceKind::J, "#..\n###"),
+            (PieceKind::L, "..#\n###"),
+            (PieceKind::S, ".##\n##."),
+            (PieceKind::Z, "##.\n.##"),
+            (PieceKind::T, ".#.\n###"),
+        ];
+        for (i, (kind, cells)) in expected.into_iter().enumerate() {
+            assert_eq!(factory.kind(i), Some(kind));
+            assert_eq!(factory.shapes()[i].to_string(), cells, "{}", kind);
+            assert_eq!(factory.shapes()[i].orientation(), 0);
         }
+    }
 
-        let dropped = self.drop_shape();
-        if dropped {
-            return;
+    #[test]
+    fn kind_of_rotated() {
+        let g = Game::new((10, 10));
+        let mut j: Shape = "#..\n###".parse().unwrap();
+        for _ in 0..4 {
+            assert_eq!(g.kind_of(&j), Some(PieceKind::J));
+            j.rotate();
         }
+        assert_eq!(g.kind_of(&"##".parse().unwrap()), None);
+    }
 
-        self.eliminate_rows();
-        self.create_new_shape();
-        if self.check_shape_out_of_bound(None) || self.check_collision(None) {
-            self.state = State::End;
+    #[test]
+    fn shapes_as_keys() {
+        let factory = ShapesFactory::with_seed(0);
+        let mut shapes = HashSet::new();
+        let mut sorted = BTreeSet::new();
+        for shape in factory.shapes() {
+            let mut s = shape.clone();
+            for _ in 0..4 {
+                shapes.insert(s.clone());
+                sorted.insert(s.clone());
+                s.rotate();
+            }
         }
+        // 1 for O, 2 each for I, S and Z, 4 each for J, L and T.
+        assert_eq!(shapes.len(), 19);
+        assert_eq!(sorted.len(), 19);
+
+        let kinds: BTreeSet<_> = factory.kinds().iter().copied().collect();
+        assert_eq!(kinds.len(), 7);
+        assert_eq!(kinds.first(), Some(&PieceKind::I));
     }
 
-    /// drop the shape by single row, return false if the shape could not be
-    /// dropped any more.
-    fn drop_shape(&mut self) -> bool {
-        if self.move_shape((-1, 0)) {
-            return true;
-        }
+    #[test]
+    fn rotation_count() {
+        let factory = ShapesFactory::new();
+        let counts: HashMap<PieceKind, u8> = factory
+            .kinds()
+            .iter()
+            .zip(factory.shapes())
+            .map(|(kind, shape)| (*kind, shape.rotation_count()))
+            .collect();
+        assert_eq!(
+            counts,
+            HashMap::from([
+                (PieceKind::O, 1),
+                (PieceKind::I, 2),
+                (PieceKind::S, 2),
+                (PieceKind::Z, 2),
+                (PieceKind::T, 4),
+                (PieceKind::J, 4),
+                (PieceKind::L, 4),
+            ])
+        );
+        assert_eq!("#".parse::<Shape>().unwrap().rotation_count(), 1);
+        assert_eq!("##\n#.".parse::<Shape>().unwrap().rotation_count(), 4);
+    }
 
-        let s = self.shape.take().unwrap();
-        let s_width = s.shape.width() as isize;
-        let s_height = s.shape.height() as isize;
+    #[test]
+    fn rotate_square() {
+        let mut s = ShapesFactory::new().shapes()[0].clone();
+        let cells = s.cells().clone();
+        assert_eq!(s.orientations, 1);
+        s.rotate();
+        assert_eq!(s.cells(), &cells);
+        assert_eq!(s.orientation(), 0);
+    }
 
-        for hi in 0..s_height {
-            for wi in 0..s_width {
-                let s_pos = (hi as usize, wi as usize);
-                let l_pos = ((s.pos.0 + hi) as usize, (s.pos.1 + wi) as usize);
-                if s.shape.cells()[s_pos] {
-                    self.level[l_pos] = true;
-                }
-            }
+    #[test]
+    fn rotate_stick() {
+        let mut s = ShapesFactory::new().shapes()[1].clone();
+        assert_eq!(s.orientations, 2);
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            seen.push((s.orientation(), s.to_string()));
+            s.rotate();
         }
-        false
+        assert_eq!(seen[0], seen[2]);
+        assert_eq!(seen[1], seen[3]);
+        assert_ne!(seen[0], seen[1]);
+        assert_eq!(seen[0], (0, "####".to_string()));
+        assert_eq!(seen[1].1, "#\n#\n#\n#");
     }
 
-    fn eliminate_rows(&mut self) -> bool {
-        let mut rows_to_eliminate = VecDeque::<usize>::new();
-        for row in 0..self.level.rows {
-            if (0..self.level.columns)
-                .map(|col| self.level[(row, col)])
-                .all(identity)
-            {
-                rows_to_eliminate.push_back(row);
+    #[test]
+    fn rotate_degenerate_cells() {
+        // a standing stick of 4 rows and 1 column lies down as 1 row of 4
+        // columns, and stands up again.
+        let standing = Conventional::from_vec((4, 1), vec![true; 4]);
+        let lying = rotate_cells(&standing);
+        assert_eq!((lying.rows, lying.columns), (1, 4));
+        assert!((0..4).all(|col| lying[(0, col)]));
+        let back = rotate_cells(&lying);
+        assert_eq!((back.rows, back.columns), (4, 1));
+        assert_eq!(rotate_cells(&rotate_cells(&back)), standing);
+
+        // a single cell stays the same.
+        let dot = Conventional::from_vec((1, 1), vec![true]);
+        assert_eq!(rotate_cells(&dot), dot);
+
+        // every cell (row, col) of a non-square shape goes to
+        // (columns - 1 - col, row).
+        let mut s: Shape = "#.\n#.\n##".parse().unwrap();
+        let before = s.cells().clone();
+        s.rotate();
+        let after = s.cells();
+        assert_eq!((after.rows, after.columns), (2, 3));
+        for row in 0..before.rows {
+            for col in 0..before.columns {
+                assert_eq!(after[(before.columns - 1 - col, row)], before[(row, col)]);
             }
         }
-        if rows_to_eliminate.len() == 0 {
-            return false;
+        assert_eq!(s.to_string(), "###\n#..");
+        for _ in 0..3 {
+            s.rotate();
         }
+        assert_eq!(s.cells(), &before);
+    }
 
-        let mut new = Conventional::new(self.level.dimensions());
-        let mut row_src = 0;
-        for row in 0..self.level.rows {
-            while rows_to_eliminate.front().map_or(false, |r| *r == row_src) {
-                row_src += 1;
-                rows_to_eliminate.pop_front();
-            }
+    #[test]
+    fn lock_piece() {
+        let mut g = game_with_shape(".#\n##\n#.", (2, 3));
+        g.lock_piece();
+        assert!(g.shape.is_none());
+        assert_eq!(g.stats().pieces, 1);
 
-            for col in 0..self.level.columns {
-                new[(row, col)] = self.level[(row_src, col)];
-            }
+        let mut cells: Vec<_> = (0..10)
+            .flat_map(|row| (0..10).map(move |col| (row, col)))
+            .filter(|pos| g.level[*pos])
+            .collect();
+        cells.sort();
+        assert_eq!(cells, vec![(2, 3), (3, 3), (3, 4), (4, 4)]);
+    }
 
-            row_src += 1;
-            if row_src >= self.level.rows {
-                break;
+    #[test]
+    fn rows_match_render() {
+        let mut g = game_with_shape("#.\n##", (1, 1));
+        g.level[(0, 0)] = true;
+        g.level[(0, 9)] = true;
+        let rows: Vec<Vec<bool>> = g.rows().map(|row| row.collect()).collect();
+        let display = g.render();
+        let expected: Vec<Vec<bool>> = (0..display.rows)
+            .rev()
+            .map(|row| {
+                (0..display.columns)
+                    .map(|col| display[(row, col)])
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows, expected);
+        assert!(rows[8][1] && rows[8][2] && rows[7][1]);
+    }
+
+    #[test]
+    fn render_ascii() {
+        let mut g = game_with_shape("#.\n##", (1, 1));
+        g.level[(0, 0)] = true;
+        let expected = [
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            ".#........",
+            ".##.......",
+            "#.........",
+        ];
+        assert_eq!(g.render_ascii(), expected.join("\n"));
+    }
+
+    #[test]
+    fn shape_display_round_trip() {
+        let factory = ShapesFactory::new();
+        for shape in &factory.shapes {
+            let mut s = shape.clone();
+            for _ in 0..4 {
+                assert_eq!(s.to_string().parse::<Shape>(), Ok(s.clone()));
+                s.rotate();
             }
         }
+    }
 
-        self.level = new;
-        true
+    #[test]
+    fn parse_shape() {
+        let s: Shape = "\n  .#.\n  ###\n\n".parse().unwrap();
+        assert_eq!(s, shape2! { _o_ ooo });
+        assert_eq!(s.to_string(), ".#.\n###");
     }
 
-    /// Return true if the any part of the shape is out of bound
-    fn check_shape_out_of_bound(&self, s: Option<&ShapeInLevel>) -> bool {
-        let s1 = s.or_else(|| self.shape.as_ref()).unwrap();
-        let pos = s1.pos;
-        let s_width = s1.shape.width() as isize;
-        let s_height = s1.shape.height() as isize;
+    #[test]
+    fn parse_shape_errors() {
+        assert_eq!("".parse::<Shape>(), Err(ParseShapeError::Empty));
+        assert_eq!(" \n\n".parse::<Shape>(), Err(ParseShapeError::Empty));
+        assert_eq!(
+            "##\n#\n".parse::<Shape>(),
+            Err(ParseShapeError::RaggedRow {
+                row: 1,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            "#x".parse::<Shape>(),
+            Err(ParseShapeError::InvalidChar {
+                row: 0,
+                col: 1,
+                ch: 'x'
+            })
+        );
+        assert_eq!("..\n..".parse::<Shape>(), Err(ParseShapeError::NoCells));
+    }
+
+    #[test]
+    fn try_new_shape() {
+        assert_eq!(
+            Shape::try_new(Conventional::new((0, 3))),
+            Err(ShapeError::Empty)
+        );
+        assert_eq!(
+            Shape::try_new(Conventional::new((3, 0))),
+            Err(ShapeError::Empty)
+        );
+        assert_eq!(
+            Shape::try_new(Conventional::new((2, 2))),
+            Err(ShapeError::NoCells)
+        );
+        let shape = Shape::try_new(Conventional::from_vec((1, 2), vec![true, false])).unwrap();
+        assert_eq!(shape.to_string(), "#.");
+    }
+
+    #[test]
+    fn asciicast() {
+        let config = GameConfig::new((4, 3)).seed(7);
+        let replay =
+            Replay::new(config, vec![vec![Event::Start], vec![], vec![Event::Left]]).unwrap();
+        let cast = replay.to_asciicast(Duration::from_millis(250));
+        let mut lines = cast.lines();
+        assert_eq!(
+            lines.next(),
+            Some(r#"{"version": 2, "width": 3, "height": 4}"#)
+        );
+        let events: Vec<serde_json::Value> =
+            lines.map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(events.len(), 3);
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event[0].as_f64(), Some(0.25 * i as f64));
+            assert_eq!(event[1], "o");
+            let text = event[2].as_str().unwrap();
+            assert!(text.starts_with(CAST_CLEAR));
+            assert_eq!(text[CAST_CLEAR.len()..].split("\r\n").count(), 4);
+        }
+        // the frames are drawn like render_ascii.
+        let mut g = Game::with_config(GameConfig::new((4, 3)).seed(7)).unwrap();
+        g.advance(2, &[Event::Start]);
+        g.advance(1, &[Event::Left]);
+        let last = format!("{}{}", CAST_CLEAR, g.render_ascii().replace('\n', "\r\n"));
+        assert_eq!(events[2][2], last.as_str());
+        assert!(Replay::new(GameConfig::new((0, 3)), vec![]).is_err());
+    }
 
-        let l_width = self.level.columns as isize;
-        let l_height = self.level.rows as isize;
+    #[test]
+    fn spawn_columns() {
+        let g = game_with_shape("#", (0, 0));
+        assert_eq!(g.spawn_columns(&"##".parse().unwrap()), vec![4, 5]);
+        assert_eq!(g.spawn_columns(&"###".parse().unwrap()), vec![3, 4, 5]);
+        assert_eq!(g.spawn_columns(&"####".parse().unwrap()), vec![3, 4, 5, 6]);
+        assert_eq!(g.spawn_columns(&".#\n.#".parse().unwrap()), vec![5]);
+        assert_eq!(g.spawn_edge(), 9);
 
-        // Check if the shape is still in the level boundary
-        pos.0 < 0 || (pos.0 + s_height) > l_height || pos.1 < 0 || (pos.1 + s_width) > l_width
+        let mut g = Game::with_config(GameConfig::new((10, 10)).spawn_delay_ticks(2)).unwrap();
+        assert!(!g.spawn_pending());
+        g.handle_event(Event::Start);
+        g.handle_event(Event::HardDrop);
+        assert!(g.spawn_pending());
     }
 
-    /// Return true if the shape collides with any cells in the level.
-    fn check_collision(&self, s: Option<&ShapeInLevel>) -> bool {
-        let s1 = s.or_else(|| self.shape.as_ref()).unwrap();
-        let s_width = s1.shape.width() as isize;
-        let s_height = s1.shape.height() as isize;
-
-        // Check if the shape collides with existing cell in the level
-        for hi in 0..s_height {
-            for wi in 0..s_width {
-                let s_pos = (hi as usize, wi as usize);
-                let l_pos = ((s1.pos.0 + hi) as usize, (s1.pos.1 + wi) as usize);
-                if s1.shape.cells()[s_pos] && self.level[l_pos] {
-                    return true;
-                }
+    #[test]
+    fn survival_garbage() {
+        let survival = |gap| GameMode::Survival {
+            interval: Duration::from_millis(300),
+            gap,
+        };
+        let config = GameConfig::new((10, 10))
+            .base_tick(Duration::from_millis(100))
+            .gravity(GravityCurve::Constant)
+            .piece_sequence(vec![0]);
+        let mut g = Game::with_config(config.clone().mode(survival(GarbageGap::Fixed(2)))).unwrap();
+        g.handle_event(Event::Start);
+        g.tick();
+        g.tick();
+        assert!(g.is_row_empty(0));
+        g.tick();
+        let garbage: Vec<bool> = (0..10).map(|col| col != 2).collect();
+        assert_eq!(g.rows().last().unwrap().collect::<Vec<_>>(), garbage);
+        for _ in 0..3 {
+            g.tick();
+        }
+        assert_eq!(g.rows().nth(8).unwrap().collect::<Vec<_>>(), garbage);
+        g.assert_invariants();
+
+        // the garbage ends up pushing the stack out of the level.
+        while g.state == State::Playing && g.elapsed_ticks() < 100 {
+            g.tick();
+        }
+        assert_eq!(g.summary().unwrap().reason, EndReason::TopOut);
+
+        // the random gaps are the same with the same seed.
+        let config = config.seed(3).mode(survival(GarbageGap::Random));
+        let mut games = [1, 2].map(|_| Game::with_config(config.clone()).unwrap());
+        for g in &mut games {
+            g.handle_event(Event::Start);
+            for _ in 0..9 {
+                g.tick();
             }
         }
-        false
+        assert_eq!(games[0].level, games[1].level);
+
+        let config = GameConfig::new((10, 10)).mode(GameMode::Survival {
+            interval: Duration::ZERO,
+            gap: GarbageGap::Fixed(10),
+        });
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::Multiple(vec![
+                ConfigError::ZeroGarbageInterval,
+                ConfigError::GarbageGapOutOfLevel {
+                    column: 10,
+                    columns: 10
+                }
+            ]))
+        );
     }
 
-    /// Reset game level and switch to state State::Playing
-    fn reset(&mut self) {
-        for x in self.level.iter_mut() {
-            *x = false;
-        }
-        self.create_new_shape();
-        self.state = State::Playing;
+    #[test]
+    fn place_active() {
+        let mut g = Game::new((10, 10));
+        assert_eq!(
+            g.place_active(PieceKind::T, (0, 0), 0),
+            Err(MoveError::NotPlaying)
+        );
+        g.handle_event(Event::Start);
+        // a T-spin slot: the T points down into the hole under the overhang.
+        g.level = level_from_rows(
+            &["####.#####", "###...####", "####......"]
+                .map(|row| row.chars().map(|c| c == '#').collect()),
+            (10, 10),
+        );
+        assert_eq!(g.place_active(PieceKind::T, (0, 3), 2), Ok(()));
+        let (shape, pos) = g.active_piece().unwrap();
+        assert_eq!(shape.to_string(), "###\n.#.");
+        assert_eq!(pos, (0, 3));
+        assert_eq!(g.hard_drop().lines, 2);
+        g.assert_invariants();
+
+        g.restart();
+        g.level = level_from_rows(&[vec![true; 10]], (10, 10));
+        let before = g.active_piece().map(|(s, pos)| (s.clone(), pos));
+        assert_eq!(
+            g.place_active(PieceKind::T, (0, 3), 2),
+            Err(MoveError::Collision)
+        );
+        assert_eq!(
+            g.place_active(PieceKind::I, (5, 7), 0),
+            Err(MoveError::OutOfBounds)
+        );
+        assert_eq!(g.active_piece().map(|(s, pos)| (s.clone(), pos)), before);
+        assert_eq!(g.place_active(PieceKind::I, (5, 7), 1), Ok(()));
+
+        let config = GameConfig::new((10, 10)).shapes(vec!["##".parse().unwrap()]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        assert_eq!(
+            g.place_active(PieceKind::T, (0, 0), 0),
+            Err(MoveError::UnknownPiece { kind: PieceKind::T })
+        );
     }
 
-    fn create_new_shape(&mut self) {
-        // we create a new shape and put it in the middle of the top
-        let mut s = ShapeInLevel {
-            shape: self.shapes_factory.create_shape(),
-            pos: (0, 0),
+    #[test]
+    fn cheese_gaps() {
+        const MAX_REPEATS: usize = 2;
+        let gap = GarbageGap::Cheese {
+            max_repeats: MAX_REPEATS,
         };
-        s.pos = (
-            (self.level.rows - s.shape.height()) as isize,
-            (self.level.columns as isize) / 2,
+        let config = GameConfig::new((10, 4)).seed(11).mode(GameMode::Survival {
+            interval: Duration::from_secs(1),
+            gap,
+        });
+        let mut g = Game::with_config(config).unwrap();
+        let gaps: Vec<usize> = (0..10_000).map(|_| g.next_gap(gap)).collect();
+        let longest = gaps
+            .chunk_by(|a, b| a == b)
+            .map(<[usize]>::len)
+            .max()
+            .unwrap();
+        assert_eq!(longest, MAX_REPEATS);
+        for column in 0..4 {
+            let count = gaps.iter().filter(|c| **c == column).count();
+            assert!((2000..3000).contains(&count), "{}: {}", column, count);
+        }
+
+        let config = GameConfig::new((10, 1)).shapes(vec!["#".parse().unwrap()]);
+        let config = config.mode(GameMode::Survival {
+            interval: Duration::from_secs(1),
+            gap,
+        });
+        assert_eq!(
+            Game::with_config(config).err(),
+            Some(ConfigError::InvalidCheese {
+                max_repeats: MAX_REPEATS,
+                columns: 1
+            })
+        );
+    }
+
+    #[test]
+    fn retry_piece() {
+        let config = GameConfig::new((10, 10)).piece_sequence(vec![6, 1]);
+        let mut g = Game::with_config(config.clone()).unwrap();
+        g.handle_event(Event::Start);
+        g.handle_event(Event::Left);
+        g.handle_event(Event::RetryPiece);
+        assert_eq!(g.active_piece().unwrap().1, (8, 2));
+
+        let mut g = Game::with_config(config.retry_piece(true)).unwrap();
+        g.handle_event(Event::Start);
+        g.level[(0, 0)] = true;
+        let level = g.level.clone();
+        let spawned = g.active_piece().map(|(s, pos)| (s.to_string(), pos));
+        g.handle_event(Event::Left);
+        g.handle_event(Event::Rotate);
+        g.handle_event(Event::SoftDrop);
+        g.handle_event(Event::RetryPiece);
+        assert_eq!(
+            g.active_piece().map(|(s, pos)| (s.to_string(), pos)),
+            spawned
         );
+        assert_eq!(g.shape.as_ref().unwrap().index, 6);
+        assert_eq!(g.next_queue[0], 1);
+        assert_eq!(g.level, level);
+        g.assert_invariants();
+    }
+
+    #[test]
+    fn playfield_bits() {
+        let mut g = Game::from_rle("3#7./1.9#/1#9.", (20, 10)).unwrap();
+        g.handle_event(Event::Start);
+        let bits = g.playfield_bits();
+        assert_eq!(bits.len(), 25);
+        assert_eq!(bits[..4], [0b0000_0111, 0b1111_1000, 0b0001_1111, 0]);
+        assert!(bits[4..].iter().all(|b| *b == 0));
 
-        while self.check_collision(Some(&s)) {
-            s.pos.0 += 1;
+        let mut other = Game::new((20, 10));
+        other.set_playfield_bits(&bits).unwrap();
+        assert_eq!(other.level, g.level);
+        assert_eq!(other.to_rle(), "3#7./1.9#/1#9.");
+
+        // the last byte is padded.
+        for (size, len) in [((1, 1), 1), ((3, 3), 2), ((4, 4), 2), ((22, 16), 44)] {
+            let config = GameConfig::new(size).shapes(vec!["#".parse().unwrap()]);
+            let mut g = Game::with_config(config).unwrap();
+            g.level.iter_mut().for_each(|cell| *cell = true);
+            let bits = g.playfield_bits();
+            assert_eq!(bits.len(), len);
+            let mut empty = Game::with_config(g.config.clone()).unwrap();
+            empty.set_playfield_bits(&bits).unwrap();
+            assert_eq!(empty.level, g.level);
         }
-        self.shape = Option::Some(s);
+        assert_eq!(
+            other.set_playfield_bits(&[0; 24]),
+            Err(PlayfieldBitsError {
+                expected: 25,
+                found: 24
+            })
+        );
     }
 
-    /// Move the shape, it returns true if the shape is moved without
-    /// collisions.
-    fn move_shape(&mut self, dir: (isize, isize)) -> bool {
-        if self.state == State::Playing {
-            let mut s = self.shape.take().unwrap();
-            let orig_pos = s.pos;
-            s.pos = (s.pos.0 + dir.0, s.pos.1 + dir.1);
+    #[test]
+    fn current_bag() {
+        let config = GameConfig::new((60, 10))
+            .seed(9)
+            .preview_count(1)
+            .randomizer(RandomizerKind::SevenBag);
+        let mut g = Game::with_config(config.clone()).unwrap();
+        assert_eq!(g.shapes_factory().current_bag(), []);
+        g.handle_event(Event::Start);
+        let bag = g.shapes_factory().current_bag().to_vec();
+        let kinds: BTreeSet<_> = bag.iter().collect();
+        assert_eq!(kinds.len(), 7);
+        // reading it doesn't deal anything.
+        assert_eq!(g.shapes_factory().current_bag(), bag);
+        assert_eq!(g.shapes_factory().draw_count(), 2);
+        assert_eq!(g.shapes_factory().bag_dealt(), 2);
 
-            let ok = !self.check_shape_out_of_bound(Some(&s)) && !self.check_collision(Some(&s));
-            if !ok {
-                s.pos = orig_pos;
+        let mut dealt = vec![g.current_piece_kind().unwrap()];
+        let mut bags = vec![bag.clone()];
+        while dealt.len() < 14 {
+            g.hard_drop();
+            dealt.push(g.current_piece_kind().unwrap());
+            let current = g.shapes_factory().current_bag();
+            if current != bags.last().unwrap().as_slice() {
+                bags.push(current.to_vec());
             }
-            self.shape = Some(s);
-            return ok;
         }
-        false
+        assert_eq!(dealt[..7], bags[0]);
+        assert_eq!(dealt[7..], bags[1]);
+
+        let mut again = Game::with_config(config).unwrap();
+        again.handle_event(Event::Start);
+        assert_eq!(again.shapes_factory().current_bag(), bag);
     }
 
-    /// Return a matrix respresting cells for the level + shape
-    pub fn render(&self) -> Conventional<bool> {
-        let mut res = self.level.clone();
-        let s = self.shape.as_ref().unwrap();
-        let s_width = s.shape.width() as isize;
-        let s_height = s.shape.height() as isize;
+    #[test]
+    fn efficiency() {
+        assert_eq!(Stats::default().efficiency(), 0.0);
+        assert_eq!(Stats::default().downstack_efficiency(), 0.0);
 
-        for hi in 0..s_height {
-            let l_row = (s.pos.0 + hi) as usize;
-            if l_row >= self.level.rows {
-                break;
+        let config = GameConfig::new((10, 4)).shapes(vec!["#\n#".parse().unwrap()]);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        // two rows of garbage with the gap on the right, the first shape
+        // clears both.
+        g.add_garbage(2, 3);
+        g.handle_event(Event::Right);
+        g.handle_event(Event::Right);
+        g.hard_drop();
+        g.hard_drop();
+        assert_eq!((g.stats().lines, g.stats().garbage_cleared), (2, 2));
+        assert_eq!(g.stats().efficiency(), 1.0);
+        assert_eq!(g.stats().downstack_efficiency(), 1.0);
+
+        g.add_garbage(1, 0);
+        g.hard_drop();
+        assert_eq!(g.stats().pieces, 3);
+        assert_eq!(g.garbage_height, 1);
+        assert_eq!(g.stats().efficiency(), 2.0 / 3.0);
+        assert_eq!(g.stats().downstack_efficiency(), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn advance() {
+        let config = GameConfig::new((10, 10)).piece_sequence(vec![0, 1]);
+        let mut g = Game::with_config(config).unwrap();
+        let plan: [(u32, &[Event]); 4] = [
+            (
+                0,
+                &[
+                    Event::Start,
+                    Event::MoveToWall(Direction::Left),
+                    Event::HardDrop,
+                ],
+            ),
+            (2, &[Event::Rotate, Event::MoveToWall(Direction::Right)]),
+            (30, &[]),
+            (4, &[Event::Left, Event::Left, Event::Left]),
+        ];
+        let mut outcomes = Vec::new();
+        for (ticks, inputs) in plan {
+            outcomes.extend(g.advance(ticks, inputs));
+        }
+        assert_eq!(outcomes.len(), 36);
+        assert_eq!(outcomes.iter().filter(|o| o.locked).count(), 4);
+        assert_eq!(g.to_rle(), "2#2.6#/2#2.2#4./1.6#3./1.2#7.");
+        assert_eq!(g.active_piece().unwrap().1, (7, 3));
+
+        // the same plan plays the same game.
+        let config = GameConfig::new((10, 10)).piece_sequence(vec![0, 1]);
+        let mut again = Game::with_config(config).unwrap();
+        for (ticks, inputs) in plan {
+            again.advance(ticks, inputs);
+        }
+        assert_eq!(again.render_ascii(), g.render_ascii());
+    }
+
+    #[test]
+    fn cleared_rows() {
+        let mut g = game_with_shape("#\n#\n#\n#", (6, 9));
+        for col in 0..9 {
+            g.level[(0, col)] = true;
+            g.level[(3, col)] = true;
+        }
+        g.level[(1, 0)] = true;
+        let outcome = g.hard_drop();
+        assert_eq!(outcome.lines, 2);
+        assert_eq!(outcome.cleared_rows, [0, 3]);
+        assert_eq!(g.to_rle(), "1#8.1#/9.1#");
+    }
+
+    /// Deal `count` shapes of the standard ones with `randomizer`.
+    fn deal(mut randomizer: impl Randomizer, count: usize) -> Vec<usize> {
+        let shapes = ShapesFactory::new().shapes;
+        let mut rng = ChaCha12Rng::seed_from_u64(5);
+        (0..count)
+            .map(|_| randomizer.next(&shapes, &mut rng))
+            .collect()
+    }
+
+    /// Return how many of `dealt` are the same as one of the `window` shapes
+    /// before them.
+    fn repeats(dealt: &[usize], window: usize) -> usize {
+        (1..dealt.len())
+            .filter(|i| dealt[i.saturating_sub(window)..*i].contains(&dealt[*i]))
+            .count()
+    }
+
+    #[test]
+    fn randomizers() {
+        let random = deal(PureRandom, 7000);
+        for shape in 0..7 {
+            let count = random.iter().filter(|i| **i == shape).count();
+            assert!((850..1150).contains(&count), "{} of {}", count, shape);
+        }
+
+        // every bag has one of each shape.
+        let bags = deal(SevenBag::default(), 7000);
+        for bag in bags.chunks(7) {
+            assert_eq!(bag.iter().collect::<BTreeSet<_>>().len(), 7);
+        }
+        // so the same shape comes back after 12 others at most.
+        assert!(bags.windows(14).all(|w| w[1..].contains(&w[0])));
+
+        // the history makes repeats rare but every shape still comes.
+        let history = deal(HistoryBased::default(), 7000);
+        for shape in 0..7 {
+            assert!(history.iter().filter(|i| **i == shape).count() > 850);
+        }
+        assert!(repeats(&history, 4) * 3 < repeats(&random, 4));
+        assert!(repeats(&history, 1) * 3 < repeats(&random, 1));
+
+        // a randomizer needn't be random.
+        #[derive(Clone)]
+        struct Cycle(usize);
+        impl Randomizer for Cycle {
+            fn next(&mut self, shapes: &[Shape], _: &mut dyn rand::RngCore) -> usize {
+                self.0 = (self.0 + 1) % shapes.len();
+                self.0
             }
-            for wi in 0..s_width {
-                let l_col = (s.pos.1 + wi) as usize;
-                if l_col >= self.level.columns {
-                    break;
-                }
-                let s_pos = (hi as usize, wi as usize);
-                if s.shape.cells()[s_pos] {
-                    res[(l_row, l_col)] = true;
-                }
+            fn box_clone(&self) -> Box<dyn Randomizer> {
+                Box::new(self.clone())
             }
         }
-        res
+        let mut factory = ShapesFactory::new();
+        factory.set_randomizer(Box::new(Cycle(0)));
+        let shapes: Vec<_> = (0..8).map(|_| factory.next_index()).collect();
+        assert_eq!(shapes, [1, 2, 3, 4, 5, 6, 0, 1]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
     #[test]
-    fn rotate_shape1() {
-        let factory = ShapesFactory::new();
-        let mut s = factory.create_shape();
-        let s_orig = s.clone();
-        s.rotate();
+    fn spawn_position_for() {
+        for (columns, i, o) in [(10, 3, 4), (7, 1, 2), (4, 0, 1)] {
+            let g = Game::new((20, columns));
+            assert_eq!(g.spawn_position_for(PieceKind::I), (19, i));
+            assert_eq!(g.spawn_position_for(PieceKind::O), (18, o));
+        }
 
-        assert_eq!(s_orig.height(), s.width());
-        assert_eq!(s_orig.width(), s.height());
+        // a shape overlapping the cells would be pushed out of the level and
+        // top out, the other one still fits. Nothing is spawned.
+        let mut g = Game::new((20, 10));
+        g.handle_event(Event::Start);
+        let pos = g.active_piece().map(|p| p.1);
+        g.level[(19, 6)] = true;
+        assert_eq!(g.spawn_position_for(PieceKind::I), (20, 3));
+        assert_eq!(g.spawn_position_for(PieceKind::O), (18, 4));
+        assert_eq!(g.active_piece().map(|p| p.1), pos);
 
-        s.rotate();
-        s.rotate();
-        s.rotate();
-        assert_eq!(s_orig, s);
+        let config = GameConfig::new((20, 10)).gravity_direction(GravityDirection::Up);
+        let g = Game::with_config(config).unwrap();
+        assert_eq!(g.spawn_position_for(PieceKind::O), (0, 4));
     }
 
     #[test]
-    fn rotate_shape2() {
-        let factory = ShapesFactory::new();
-        let mut s = factory.create_shape();
-        let s_orig = s.clone();
-        s.rotate();
-        s.rotate();
-        s.rotate();
-        s.rotate();
-        assert_eq!(s_orig, s);
+    fn fill_rows() {
+        let mut g = game_with_shape("#\n#", (5, 5));
+        g.fill_rows(&[0], &[5]);
+        g.fill_rows(&[1, 2], &[0, 5, 9]);
+        assert_eq!(g.to_rle(), "5#1.4#/1.4#1.3#1./1.4#1.3#1.");
+
+        let outcome = g.hard_drop();
+        assert_eq!(outcome.cleared_rows, [0]);
+        // the shape filled the gap of the row above too.
+        assert_eq!(g.to_rle(), "1.8#1./1.4#1.3#1.");
+    }
+
+    #[test]
+    fn combo() {
+        let mut g = game_with_shape("#", (5, 9));
+        assert_eq!(g.combo(), -1);
+        for row in 0..3 {
+            g.fill_rows(&[row], &[9]);
+        }
+        for combo in 0..3 {
+            g.shape.as_mut().unwrap().shape = "#".parse().unwrap();
+            g.shape.as_mut().unwrap().pos = (5, 9);
+            assert_eq!(g.hard_drop().lines, 1);
+            assert_eq!(g.combo(), combo);
+        }
+        g.hard_drop();
+        assert_eq!(g.combo(), -1);
+        g.restart();
+        assert_eq!(g.combo(), -1);
+    }
+
+    #[test]
+    fn tick_count() {
+        let mut g = Game::new((10, 10));
+        g.tick();
+        assert_eq!(g.tick_count(), 1);
+        assert_eq!(g.elapsed_ticks(), 0);
+
+        // starting the game starts the count again.
+        g.handle_event(Event::Start);
+        assert_eq!(g.tick_count(), 0);
+        for _ in 0..4 {
+            g.tick();
+        }
+        // a tick of the held soft drop is a fraction of a gravity tick.
+        g.handle_event(Event::SoftDropHeld(true));
+        g.tick();
+        g.handle_event(Event::SoftDropHeld(false));
+        g.handle_event(Event::Pause);
+        g.tick();
+        assert_eq!(g.tick_count(), 6);
+        assert_eq!(g.elapsed_ticks(), 4);
+        // neither a hard drop nor a soft drop is a tick.
+        g.handle_event(Event::Start);
+        g.handle_event(Event::SoftDrop);
+        g.handle_event(Event::HardDrop);
+        assert_eq!(g.tick_count(), 6);
+
+        g.restart();
+        assert_eq!(g.tick_count(), 0);
+        g.tick();
+        assert_eq!(g.tick_count(), 1);
+    }
+
+    #[test]
+    fn clear_delay() {
+        let config = GameConfig::new((10, 10))
+            .piece_sequence(vec![1])
+            .clear_delay_ticks(2);
+        let mut g = Game::with_config(config).unwrap();
+        g.handle_event(Event::Start);
+        g.fill_rows(&[0], &[3, 4, 5, 6]);
+        g.level[(1, 0)] = true;
+        let locked = g.hard_drop();
+        assert!(locked.locked);
+        assert_eq!(locked.lines, 0);
+        assert_eq!(g.pending_clear_rows(), [0]);
+        assert!(g.active_piece().is_none());
+        g.assert_invariants();
+
+        // the full row stays for two ticks, and the moves wait too.
+        g.handle_event(Event::Left);
+        assert_eq!(g.tick(), TickOutcome::default());
+        assert_eq!(g.to_rle(), "10#/1#9.");
+        g.assert_invariants();
+        let cleared = g.tick();
+        assert_eq!(cleared.cleared_rows, [0]);
+        assert_eq!(g.to_rle(), "1#9.");
+        assert!(g.pending_clear_rows().is_empty());
+        assert_eq!(g.stats().lines, 1);
+        assert!(g.active_piece().is_some());
+
+        // a shape clearing nothing spawns the next one right away.
+        g.hard_drop();
+        assert!(g.pending_clear_rows().is_empty());
+        assert!(g.active_piece().is_some());
+
+        // garbage rising while the rows wait lifts them too.
+        let mut g = Game::with_config(
+            GameConfig::new((10, 10))
+                .piece_sequence(vec![1])
+                .clear_delay_ticks(2),
+        )
+        .unwrap();
+        g.handle_event(Event::Start);
+        g.fill_rows(&[0], &[3, 4, 5, 6]);
+        g.hard_drop();
+        g.add_garbage(2, 0);
+        assert_eq!(g.pending_clear_rows(), [2]);
+        g.assert_invariants();
+        g.tick();
+        assert_eq!(g.tick().cleared_rows, [2]);
+        assert_eq!(g.to_rle(), "1.9#/1.9#");
+    }
+
+    #[test]
+    fn new_holes() {
+        // the stick bridges the gap between two cells, covering the two cells
+        // of the gap.
+        let mut g = game_with_shape("####", (5, 3));
+        g.level[(0, 3)] = true;
+        g.level[(0, 6)] = true;
+        assert_eq!(g.hole_count(), 0);
+        assert_eq!(g.hard_drop().new_holes, 2);
+        assert_eq!(g.hole_count(), 2);
+
+        // the holes already there aren't counted again.
+        g.shape.as_mut().unwrap().shape = "#".parse().unwrap();
+        g.shape.as_mut().unwrap().pos = (5, 0);
+        assert_eq!(g.hard_drop().new_holes, 0);
+    }
+
+    #[test]
+    fn scrubber() {
+        let config = GameConfig::new((10, 10))
+            .seed(4)
+            .base_tick(Duration::from_millis(100))
+            .gravity(GravityCurve::Constant);
+        let mut events = vec![vec![Event::Start]];
+        for i in 1..100 {
+            events.push(match i % 7 {
+                0 => vec![Event::HardDrop],
+                3 => vec![Event::Left, Event::Rotate],
+                5 => vec![Event::Right],
+                // the held soft drop is played again too.
+                4 => vec![Event::SoftDropHeld(i % 2 == 0)],
+                _ => vec![],
+            });
+        }
+        let replay = Replay::new(config.clone(), events.clone()).unwrap();
+        let mut played = Game::with_config(config).unwrap();
+        let events: Vec<&[Event]> = events.iter().map(Vec::as_slice).collect();
+        let frames: Vec<_> = played.simulate(&events, events.len()).collect();
+
+        let mut scrubber = Scrubber::new(replay);
+        assert_eq!(scrubber.len(), 100);
+        for k in [70, 5, 33, 99, 32, 0, 64, 100] {
+            scrubber.seek(k);
+            assert_eq!(scrubber.tick(), k);
+            if k > 0 {
+                let rows: Vec<Vec<bool>> = scrubber.game().rows().map(Iterator::collect).collect();
+                assert_eq!(rows, frames[k - 1], "tick {}", k);
+            }
+        }
+        assert_eq!(scrubber.game().stats(), played.stats());
+
+        scrubber.step_forward();
+        assert_eq!(scrubber.tick(), 100);
+        scrubber.seek(1);
+        scrubber.step_back();
+        scrubber.step_back();
+        assert_eq!(scrubber.tick(), 0);
+        assert_eq!(scrubber.game().state, State::Init);
+        scrubber.step_forward();
+        assert_eq!(scrubber.game().state, State::Playing);
+    }
+
+    #[test]
+    fn record_replay() {
+        let config = GameConfig::new((10, 10)).seed(5).undo(true);
+        let mut g = Game::with_config(config).unwrap();
+        assert!(g.recording().is_none());
+        g.start_recording();
+        g.tick();
+        g.handle_event(Event::Start);
+        for i in 0..60 {
+            match i % 5 {
+                0 => g.apply_events(&[Event::Left, Event::Rotate]),
+                1 => g.apply_events(&[Event::SoftDropHeld(i % 2 == 0)]),
+                3 => g.apply_events(&[Event::HardDrop]),
+                _ => {}
+            }
+            if i == 33 {
+                g.handle_event(Event::Undo);
+            }
+            g.tick();
+        }
+
+        let json = serde_json::to_string(&g.recording().unwrap()).unwrap();
+        let mut scrubber = Scrubber::new(serde_json::from_str(&json).unwrap());
+        assert_eq!(scrubber.len(), 61);
+        scrubber.seek(61);
+        assert_eq!(scrubber.game().render_ascii(), g.render_ascii());
+        assert_eq!(scrubber.game().stats(), g.stats());
+
+        // the events after the last tick aren't in it.
+        g.handle_event(Event::Right);
+        assert_eq!(g.recording().unwrap().events.len(), 61);
+        // a restart isn't an event, the recording ends there.
+        g.restart();
+        g.tick();
+        assert_eq!(g.recording().unwrap().events.len(), 61);
+    }
+
+    #[test]
+    fn replay_json() {
+        let config = GameConfig::new((8, 6))
+            .seed(2)
+            .shapes(vec!["##\n#.".parse().unwrap(), "###".parse().unwrap()])
+            .gravity(GravityCurve::Linear {
+                step: Duration::from_millis(10),
+                min: Duration::from_millis(50),
+            });
+        let events = vec![
+            vec![Event::Start],
+            vec![
+                Event::MoveToWall(Direction::Left),
+                Event::SoftDropHeld(true),
+            ],
+            vec![],
+            vec![Event::HardDrop],
+        ];
+        let replay = Replay::new(config.clone(), events).unwrap();
+        let json = serde_json::to_string(&replay).unwrap();
+        let parsed: Replay = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.config, config);
+        assert_eq!(parsed.events, replay.events);
+        let tick = Duration::from_millis(100);
+        assert_eq!(parsed.to_asciicast(tick), replay.to_asciicast(tick));
+
+        // the config is validated.
+        let invalid = json.replace("\"preview_count\":3", "\"preview_count\":0");
+        assert_ne!(invalid, json);
+        assert!(serde_json::from_str::<Replay>(&invalid).is_err());
+        // a custom gravity couldn't be saved.
+        let custom = Replay::new(
+            config.gravity(GravityCurve::Custom(GravityFn::new(|_| {
+                Duration::from_secs(1)
+            }))),
+            vec![],
+        )
+        .unwrap();
+        assert!(serde_json::to_string(&custom).is_err());
+    }
+
+    #[test]
+    fn filled_cells() {
+        let mut g = game_with_shape("##\n##", (5, 5));
+        assert_eq!(g.filled_cell_count(), 0);
+        assert_eq!(g.fill_ratio(), 0.0);
+
+        g.fill_rows(&[0, 1], &[3]);
+        g.level[(4, 0)] = true;
+        assert_eq!(g.filled_cell_count(), 19);
+        assert_eq!(g.fill_ratio(), 0.19);
     }
 }
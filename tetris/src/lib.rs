@@ -0,0 +1,11 @@
+pub mod board;
+pub mod game;
+#[cfg(feature = "spectator")]
+pub mod spectator;
+pub mod text;
+pub mod ui;
+
+// lets the macros of tetris-macro refer to `::tetris` inside this crate too.
+extern crate self as tetris;
+
+pub use tetris_macro::{shape2, shapeset};
@@ -1,12 +1,187 @@
-#[macro_use]
-extern crate matrix;
+use std::{env, fs, io, process, time::Duration};
+use tetris::{
+    game::{Difficulty, GameMode, GarbageGap, Replay},
+    text,
+    ui::{self, settings::Settings, AutoRepeat, CellSize, DisplayOrientation, GhostStyle},
+};
 
-mod game;
-mod ui;
+const USAGE: &str = "usage: tetris [--skip-menu] [--sprint] [--preview <1-5>] [--grid]
+              [--zen] [--survival] [--seed <n>] [--text] [--auto-pause]
+              [--practice] [--flip <horizontal|vertical>] [--trail]
+              [--idle-pause <secs>] [--mini] [--ghost] [--ghost-fill]
+              [--hide-paused]
+              [--patterns] [--soft-drop-repeat <delay-ms>,<interval-ms>]
+              [--confirm-drop] [--mirror] [--title] [--sound]
+              [--invisible <ticks>] [--spectate <addr>] [--record <file>]
+              [--scrub <file>]
 
-use std::io;
+The keys, the theme and some of the options could also be set in
+$XDG_CONFIG_HOME/tetris/settings.json, or ~/.config/tetris/settings.json,
+the options given here are applied over them.
+
+    --skip-menu    start a Normal game without showing the start menu
+    --sprint       clear 40 lines as fast as possible
+    --zen          play without ever topping out, the bottom rows are thrown
+                   away when there is no room for a shape
+    --survival     a row of garbage rises from the bottom every 10 seconds
+    --preview      the number of upcoming shapes shown
+    --grid         draw a dot in each empty cell
+    --seed         the seed of the shapes, it's asked in the start menu
+                   otherwise
+    --text         play with commands read from stdin instead of the terminal UI
+    --auto-pause   pause the game when the terminal loses focus
+    --practice     allow undoing the last locked shapes with u, picking any
+                   upcoming shape with the number keys and trying the falling
+                   shape again with r
+    --flip         draw the level mirrored
+    --trail        draw a fading trail behind the falling shape
+    --idle-pause   pause the game when no key is pressed for this many seconds
+    --mini         draw two rows of the level in a row of the terminal
+    --ghost        show where the shape lands, or where it lands rotated while
+                   the rotate key is held
+    --ghost-fill   like --ghost, with dim cells instead of their outlines
+    --hide-paused  hide the level while the game is paused
+    --patterns     draw the falling and upcoming shapes with a pattern of their
+                   kind, so they could be told apart without colors
+    --soft-drop-repeat
+                   soft drop by a row every interval while the down key is held,
+                   starting after the delay, e.g. 150,30
+    --confirm-drop only hard drop when space is pressed twice quickly
+    --mirror       swap the left and the right keys
+    --title        show the score in the title of the terminal window and the
+                   level, the lines and the mode at the bottom
+    --sound        ring the bell when lines are cleared
+    --invisible    hide the locked cells after this many ticks, they're shown
+                   again for a moment when rows are cleared
+    --spectate     stream the game as JSON lines to the clients connecting to
+                   this address, e.g. 127.0.0.1:4000
+    --record       save the game as a replay in JSON to this file when quitting,
+                   from the start until it's restarted or stopped
+    --scrub        step through the replay saved as JSON in this file instead
+                   of playing, e.g. one saved by --record";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Parse an [AutoRepeat] written as `<delay-ms>,<interval-ms>`.
+fn parse_repeat(s: &str) -> Option<AutoRepeat> {
+    let (delay, interval) = s.split_once(',')?;
+    Some(AutoRepeat {
+        delay: Duration::from_millis(delay.parse().ok()?),
+        interval: Duration::from_millis(interval.parse().ok()?),
+    })
+}
+
+/// Return the replay saved as JSON in the file at `path`, or exit if it
+/// couldn't be read.
+fn load_replay(path: &str) -> Replay {
+    let json = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("tetris: {}: {}", path, e);
+        process::exit(2);
+    });
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        eprintln!("tetris: {}: {}", path, e);
+        process::exit(2);
+    })
+}
 
 fn main() -> Result<(), io::Error> {
-    ui::start()?;
-    Ok(())
+    let mut options = ui::Options::default();
+    let mut text_mode = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--skip-menu" => options.difficulty = Some(Difficulty::Normal),
+            "--sprint" => options.mode = GameMode::Sprint { lines: 40 },
+            "--zen" => options.mode = GameMode::Zen,
+            "--survival" => {
+                options.mode = GameMode::Survival {
+                    interval: Duration::from_secs(10),
+                    gap: GarbageGap::Cheese { max_repeats: 3 },
+                }
+            }
+            "--preview" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=5) => options.preview_count = Some(n),
+                _ => usage(),
+            },
+            "--grid" => options.theme.show_grid = true,
+            "--flip" => match args.next().as_deref() {
+                Some("horizontal") => {
+                    options.theme.orientation = DisplayOrientation::FlippedHorizontal
+                }
+                Some("vertical") => options.theme.orientation = DisplayOrientation::FlippedVertical,
+                _ => usage(),
+            },
+            "--seed" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(seed) => options.seed = Some(seed),
+                None => usage(),
+            },
+            "--trail" => options.theme.show_trail = true,
+            "--ghost" => options.theme.ghost = GhostStyle::Outline,
+            "--ghost-fill" => options.theme.ghost = GhostStyle::DimFill,
+            "--hide-paused" => options.theme.hide_board_on_pause = true,
+            "--patterns" => options.theme.patterns = true,
+            "--confirm-drop" => options.confirm_hard_drop = true,
+            "--mirror" => options.mirror_controls = true,
+            "--title" => options.window_title = true,
+            "--sound" => options.sound = true,
+            "--mini" => options.theme.cell_size = CellSize::HalfBlock,
+            "--text" => text_mode = true,
+            "--auto-pause" => options.pause_on_focus_lost = true,
+            "--practice" => options.practice = true,
+            "--spectate" if cfg!(not(feature = "spectator")) => {
+                eprintln!("tetris: --spectate needs the spectator feature");
+                process::exit(2);
+            }
+            "--spectate" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(addr) => options.spectate = Some(addr),
+                None => usage(),
+            },
+            "--soft-drop-repeat" => match args.next().as_deref().and_then(parse_repeat) {
+                Some(repeat) => options.soft_drop_repeat = Some(repeat),
+                None => usage(),
+            },
+            "--invisible" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(ticks) => options.invisible_after_ticks = Some(ticks),
+                None => usage(),
+            },
+            "--record" => match args.next() {
+                Some(path) => options.record = Some(path.into()),
+                None => usage(),
+            },
+            "--scrub" => match args.next() {
+                Some(path) => return ui::scrub(load_replay(&path)),
+                None => usage(),
+            },
+            "--idle-pause" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(secs) => options.idle_pause = Some(Duration::from_secs(secs)),
+                None => usage(),
+            },
+            _ => usage(),
+        }
+    }
+
+    let settings = Settings::load().unwrap_or_else(|e| {
+        eprintln!("tetris: {}", e);
+        process::exit(2);
+    });
+
+    if !text_mode {
+        return match ui::start(options, settings) {
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                eprintln!("tetris: {}", e);
+                process::exit(2);
+            }
+            res => res,
+        };
+    }
+    settings.apply(&mut options);
+    if let Err(e) = options.validate() {
+        eprintln!("tetris: {}", e);
+        process::exit(2);
+    }
+    let config = options.config(options.difficulty.unwrap_or(Difficulty::Normal));
+    text::run(config, io::stdin().lock(), io::stdout().lock())
 }
@@ -0,0 +1,230 @@
+//! Stream a game to spectators, e.g. a web page showing it. A client connects
+//! over TCP and gets the state of the game after every tick as a line of JSON.
+//! The game only queues the frames, they are sent by a thread of their own so
+//! a slow or gone client never holds the game up.
+
+use super::game::{Game, PieceKind, State};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The number of frames queued for the clients, the frames after them are
+/// dropped until the clients catch up.
+const QUEUE_LEN: usize = 16;
+
+/// How long sending a frame to a client could take before it's disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How often the listener checks for new clients, or tries again after an
+/// error, and so how long dropping [Spectators] could wait for it to stop.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The state of a game sent to the spectators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    /// The number of ticks played.
+    pub tick: u32,
+    pub state: State,
+    pub score: u32,
+    pub level: u32,
+    pub lines: u32,
+    /// The kind of the falling shape, it's None if there is no shape or it's a
+    /// custom one.
+    pub piece: Option<PieceKind>,
+    /// The rows of the level from the top with the falling shape, `#` for a
+    /// filled cell and `.` for an empty one.
+    pub rows: Vec<String>,
+}
+
+impl Frame {
+    /// Return the frame showing `g` as it is.
+    pub fn new(g: &Game) -> Self {
+        Frame {
+            tick: g.elapsed_ticks(),
+            state: g.state,
+            score: g.stats().score,
+            level: g.level_number(),
+            lines: g.stats().lines,
+            piece: g.current_piece_kind(),
+            rows: g
+                .rows()
+                .map(|row| row.map(|cell| if cell { '#' } else { '.' }).collect())
+                .collect(),
+        }
+    }
+}
+
+/// The clients watching a game, see [Spectators::bind].
+pub struct Spectators {
+    /// It's only None while this is dropped.
+    frames: Option<SyncSender<String>>,
+    addr: SocketAddr,
+    /// Set to stop accepting the clients.
+    shutdown: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Spectators {
+    /// Listen for clients on `addr`. The clients are accepted and the frames
+    /// are sent by threads of their own, they stop when this is dropped and
+    /// the clients are disconnected.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        // so the listener notices the shutdown.
+        listener.set_nonblocking(true)?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let (frames, frames_rx) = mpsc::sync_channel(QUEUE_LEN);
+        let (clients, clients_rx) = mpsc::channel();
+        let stop = shutdown.clone();
+        let threads = vec![
+            thread::spawn(move || accept(listener, clients, &stop)),
+            thread::spawn(move || send_frames(frames_rx, clients_rx)),
+        ];
+        Ok(Spectators {
+            frames: Some(frames),
+            addr,
+            shutdown,
+            threads,
+        })
+    }
+
+    /// Return the address the clients connect to, e.g. to find the port
+    /// chosen when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Queue a frame of `g` for the clients, it's dropped if too many frames
+    /// are waiting already. It never blocks.
+    pub fn serve(&self, g: &Game) {
+        let mut line = serde_json::to_string(&Frame::new(g)).unwrap();
+        line.push('\n');
+        if let Some(frames) = &self.frames {
+            let _ = frames.try_send(line);
+        }
+    }
+}
+
+impl Drop for Spectators {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // the frames end with the sender.
+        self.frames = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Accept the clients until `shutdown` is set or the frames aren't sent
+/// anymore.
+fn accept(listener: TcpListener, clients: Sender<TcpStream>, shutdown: &AtomicBool) {
+    while !shutdown.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            // the other errors could last too, e.g. running out of files, so
+            // they wait like no client does.
+            Err(_) => {
+                thread::sleep(ACCEPT_INTERVAL);
+                continue;
+            }
+        };
+        if stream.set_nonblocking(false).is_err()
+            || stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err()
+        {
+            continue;
+        }
+        if clients.send(stream).is_err() {
+            break;
+        }
+    }
+}
+
+/// Send every frame to all the clients connected so far, the clients failing
+/// to get one are disconnected.
+fn send_frames(frames: Receiver<String>, new_clients: Receiver<TcpStream>) {
+    let mut clients = Vec::new();
+    for frame in frames {
+        clients.extend(new_clients.try_iter());
+        clients.retain_mut(|client: &mut TcpStream| client.write_all(frame.as_bytes()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Event, GameConfig};
+    use std::io::{BufRead, BufReader, Read};
+
+    /// Serve `g` until `client` gets a whole frame and return it.
+    fn receive(spectators: &Spectators, g: &Game, client: &mut BufReader<TcpStream>) -> Frame {
+        let mut line = String::new();
+        for _ in 0..200 {
+            spectators.serve(g);
+            let _ = client.read_line(&mut line);
+            if line.ends_with('\n') {
+                return serde_json::from_str(&line).unwrap();
+            }
+        }
+        panic!("no frame received, got {:?}", line);
+    }
+
+    fn connect(addr: SocketAddr) -> BufReader<TcpStream> {
+        let client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(20)))
+            .unwrap();
+        BufReader::new(client)
+    }
+
+    #[test]
+    fn stream_frames() {
+        let spectators = Spectators::bind("127.0.0.1:0").unwrap();
+        let mut g = Game::with_config(GameConfig::new((10, 6)).seed(1)).unwrap();
+        g.handle_event(Event::Start);
+        g.tick();
+
+        let mut client = connect(spectators.local_addr());
+        let frame = receive(&spectators, &g, &mut client);
+        assert_eq!(frame, Frame::new(&g));
+        assert_eq!(frame.state, State::Playing);
+        assert_eq!(frame.tick, 1);
+        assert_eq!(frame.rows.len(), 10);
+        assert!(frame.rows.iter().any(|row| row.contains('#')));
+
+        // a client going away doesn't stop the others from getting frames.
+        drop(client);
+        let mut other = connect(spectators.local_addr());
+        g.tick();
+        let mut frame = receive(&spectators, &g, &mut other);
+        while frame.tick != 2 {
+            frame = receive(&spectators, &g, &mut other);
+        }
+        assert_eq!(frame, Frame::new(&g));
+    }
+
+    #[test]
+    fn stop_when_dropped() {
+        let spectators = Spectators::bind("127.0.0.1:0").unwrap();
+        let addr = spectators.local_addr();
+        let mut client = connect(addr);
+        let g = Game::with_config(GameConfig::new((10, 6)).seed(1)).unwrap();
+        receive(&spectators, &g, &mut client);
+
+        drop(spectators);
+        // the client is disconnected and nobody listens anymore.
+        let mut rest = Vec::new();
+        assert!(client.read_to_end(&mut rest).is_ok());
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! A front-end playing the game with plain text, it reads commands from any
+//! input (e.g. a pipe) and prints the level after each command, so it works
+//! without a real terminal.
+
+use super::game;
+use std::io::{self, BufRead, Write};
+
+/// The help printed for an unknown command.
+const HELP: &str =
+    "commands: a (left), d (right), A (left to the wall), D (right to the wall), w (rotate), s (drop), x (hard drop), c (hold), u (undo), r (retry the shape), 1-9 (pick an upcoming shape), p (pause), q (quit)";
+
+/// Return the event of a command, or None if it isn't one.
+fn event(command: char) -> Option<game::Event> {
+    match command {
+        'a' => Some(game::Event::Left),
+        'd' => Some(game::Event::Right),
+        'A' => Some(game::Event::MoveToWall(game::Direction::Left)),
+        'D' => Some(game::Event::MoveToWall(game::Direction::Right)),
+        'w' => Some(game::Event::Rotate),
+        's' => Some(game::Event::SoftDrop),
+        'x' => Some(game::Event::HardDrop),
+        'c' => Some(game::Event::Hold),
+        'u' => Some(game::Event::Undo),
+        'r' => Some(game::Event::RetryPiece),
+        'p' => Some(game::Event::Pause),
+        '1'..='9' => Some(game::Event::PickPreview(command as usize - '1' as usize)),
+        _ => None,
+    }
+}
+
+/// Print the level and the state of the game.
+fn print_game<W: Write>(output: &mut W, g: &game::Game) -> io::Result<()> {
+    writeln!(output, "{}", g.render_ascii())?;
+    match g.state {
+        game::State::Paused => writeln!(output, "Paused")?,
+        game::State::End => writeln!(output, "GAME OVER")?,
+        _ => {}
+    }
+    writeln!(output)
+}
+
+/// Play a game with the commands read from `input`, until it's over, `q` is
+/// read or there are no more commands. The game only goes on with the
+/// commands, shapes fall by `s` instead of by time. A paused game is resumed by
+/// any command.
+pub fn run<R: BufRead, W: Write>(
+    config: game::GameConfig,
+    input: R,
+    mut output: W,
+) -> io::Result<()> {
+    let mut g = game::Game::with_config(config)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    g.handle_event(game::Event::Start);
+    print_game(&mut output, &g)?;
+
+    for byte in input.bytes() {
+        let command = byte? as char;
+        if command.is_ascii_whitespace() {
+            continue;
+        }
+        if command == 'q' {
+            break;
+        }
+
+        if g.state == game::State::Paused {
+            g.handle_event(game::Event::Start);
+        } else {
+            match event(command) {
+                Some(e) => {
+                    g.handle_event(e);
+                }
+                None => {
+                    writeln!(output, "{}", HELP)?;
+                    continue;
+                }
+            }
+        }
+        print_game(&mut output, &g)?;
+        if g.state == game::State::End {
+            break;
+        }
+    }
+    output.flush()
+}
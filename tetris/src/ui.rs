@@ -1,96 +1,912 @@
+pub mod grid;
+pub mod settings;
+
+use super::board::Board;
 use super::game;
+use settings::{KeyBindings, Settings};
 
 use crossterm::{
-    event::{self, Event, KeyCode},
+    cursor,
+    event::{
+        self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+    },
 };
+use serde::{Deserialize, Serialize};
 use std::{
-    io,
+    collections::VecDeque,
+    fs, io,
+    io::Write,
+    net::SocketAddr,
+    panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
     time::{Duration, Instant},
 };
 use tui::{
-    backend::CrosstermBackend,
+    backend::{Backend, CrosstermBackend},
     buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     symbols,
-    text::Span,
-    widgets::{Block, Borders, Widget},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Widget},
     Terminal,
 };
 
+/// The options of the terminal UI.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Start the game with this difficulty instead of asking the player to
+    /// choose one in the start menu.
+    pub difficulty: Option<game::Difficulty>,
+    /// The rules deciding when the game ends.
+    pub mode: game::GameMode,
+    /// The number of upcoming shapes shown, the default one is used if it's
+    /// None.
+    pub preview_count: Option<usize>,
+    /// The seed of the shapes, a random one is used if it's None.
+    pub seed: Option<u64>,
+    /// How the game looks.
+    pub theme: Theme,
+    /// Pause the game when the terminal loses focus, not all terminals report
+    /// it.
+    pub pause_on_focus_lost: bool,
+    /// Let the player undo the last locks with `u`, pick any upcoming shape
+    /// with the number keys and try the falling shape again with `r`, see
+    /// [game::GameConfig::undo], [game::GameConfig::pick_preview] and
+    /// [game::GameConfig::retry_piece].
+    pub practice: bool,
+    /// Pause the game when no key is pressed for this long while it's being
+    /// played, so it isn't lost while the player is away.
+    pub idle_pause: Option<Duration>,
+    /// Stream the game to the spectators connecting to this address, see
+    /// [crate::spectator]. It needs the `spectator` feature.
+    pub spectate: Option<SocketAddr>,
+    /// Soft drop by a row at these rates while the down key is held, instead
+    /// of making the shape fall [game::SOFT_DROP_MULTIPLIER] times faster.
+    pub soft_drop_repeat: Option<AutoRepeat>,
+    /// Only hard drop when the key is pressed twice within
+    /// [CONFIRM_WINDOW], so a stray press doesn't slam the shape down.
+    pub confirm_hard_drop: bool,
+    /// Swap the left and the right keys, e.g. for left-handed players.
+    pub mirror_controls: bool,
+    /// Show the score in the title of the terminal window, see
+    /// [window_title], and the level, the lines and the mode in a line at
+    /// the bottom of the terminal, see [status_bar].
+    pub window_title: bool,
+    /// The keys the game is played with.
+    pub keys: KeyBindings,
+    /// The interval of the gravity at the first level, the one of the
+    /// difficulty is used if it's None.
+    pub base_tick: Option<Duration>,
+    /// Ring the bell of the terminal when lines are cleared.
+    pub sound: bool,
+    /// Hide the locked cells after this many ticks, see
+    /// [game::GameConfig::invisible_after_ticks].
+    pub invisible_after_ticks: Option<u32>,
+    /// Save the game as a [game::Replay] in JSON to this file when the player
+    /// quits, see [game::Game::recording] and [scrub].
+    pub record: Option<PathBuf>,
+}
+
+impl Options {
+    /// Return the configuration of a game with the given difficulty and these
+    /// options.
+    pub fn config(&self, difficulty: game::Difficulty) -> game::GameConfig {
+        let mut config = game::GameConfig::new((22, 16))
+            .difficulty(difficulty)
+            .mode(self.mode)
+            .undo(self.practice)
+            .pick_preview(self.practice)
+            .retry_piece(self.practice);
+        if let Some(preview_count) = self.preview_count {
+            config = config.preview_count(preview_count);
+        }
+        if let Some(seed) = self.seed {
+            config = config.seed(seed);
+        }
+        if let Some(base_tick) = self.base_tick {
+            config = config.base_tick(base_tick);
+        }
+        if let Some(ticks) = self.invisible_after_ticks {
+            config = config.invisible_after_ticks(ticks);
+        }
+        config
+    }
+
+    /// Check that a game could be played with these options at every
+    /// difficulty, see [game::GameConfig::validate].
+    pub fn validate(&self) -> Result<(), game::ConfigError> {
+        game::Difficulty::ALL
+            .iter()
+            .try_for_each(|difficulty| self.config(*difficulty).validate())
+    }
+}
+
+/// How the game is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Draw a faint dot in each empty cell of the level.
+    pub show_grid: bool,
+    /// Shown over the level when the game is over as the level is filled up.
+    pub game_over: Overlay,
+    /// Shown over the level when all lines of a sprint are cleared.
+    pub sprint_complete: Overlay,
+    /// Shown over the level when the game is paused.
+    pub paused: Overlay,
+    /// Shown over the level before the game is started.
+    pub press_start: Overlay,
+    /// How the level is mirrored on the screen.
+    pub orientation: DisplayOrientation,
+    /// Draw a fading [Trail] behind the falling shape.
+    pub show_trail: bool,
+    /// How much space a cell of the level takes.
+    pub cell_size: CellSize,
+    /// How the ghost of the shape is drawn where it would land, see [ghost].
+    pub ghost: GhostStyle,
+    /// The colors of the cells, the borders and the overlays.
+    pub palette: Palette,
+    /// Draw the level empty while the game is paused, with only the overlay,
+    /// so the player can't study it.
+    pub hide_board_on_pause: bool,
+    /// Mark the columns the next shape comes in at while it waits for the
+    /// spawn delay, see [spawn_marker].
+    pub show_spawn_preview: bool,
+    /// Draw the cells of the falling and the upcoming shapes with a pattern
+    /// of their kind, see [pattern], so they could be told apart without
+    /// colors. It's only drawn with [CellSize::Wide].
+    pub patterns: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            show_grid: false,
+            game_over: Overlay {
+                text: "GAME OVER",
+                color: Color::Red,
+            },
+            sprint_complete: Overlay {
+                text: "SPRINT COMPLETE",
+                color: Color::Yellow,
+            },
+            paused: Overlay {
+                text: "Paused",
+                color: Color::Green,
+            },
+            press_start: Overlay {
+                text: "Press Enter",
+                color: Color::White,
+            },
+            orientation: DisplayOrientation::Normal,
+            show_trail: false,
+            cell_size: CellSize::Wide,
+            ghost: GhostStyle::Off,
+            palette: Palette::CLASSIC,
+            hide_board_on_pause: false,
+            show_spawn_preview: false,
+            patterns: false,
+        }
+    }
+}
+
+/// The colors a game is drawn with, the player could switch between
+/// [PALETTES] while playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    /// The color of the cells and the shapes.
+    pub cell: Color,
+    /// The colors of the falling shape and the next shapes by their
+    /// [game::PieceKind], in its order, or None to draw them with `cell`.
+    pub pieces: Option<[Color; 7]>,
+    /// The color of the borders around the widgets.
+    pub border: Color,
+    /// The color of all the overlays, each [Overlay] has its own color if
+    /// it's None.
+    pub overlay: Option<Color>,
+}
+
+impl Palette {
+    /// The terminal's own colors.
+    pub const CLASSIC: Palette = Palette {
+        name: "classic",
+        cell: Color::Reset,
+        pieces: None,
+        border: Color::Reset,
+        overlay: None,
+    };
+    pub const MONOCHROME: Palette = Palette {
+        name: "monochrome",
+        cell: Color::Gray,
+        pieces: None,
+        border: Color::DarkGray,
+        overlay: Some(Color::White),
+    };
+    pub const HIGH_CONTRAST: Palette = Palette {
+        name: "high contrast",
+        cell: Color::White,
+        pieces: None,
+        border: Color::LightYellow,
+        overlay: Some(Color::LightYellow),
+    };
+    /// Blue and orange, which are told apart with the common color vision
+    /// deficiencies, and so are the colors of the shapes.
+    pub const COLORBLIND: Palette = Palette {
+        name: "colorblind",
+        cell: Color::Rgb(230, 159, 0),
+        pieces: Some([
+            Color::Rgb(86, 180, 233),
+            Color::Rgb(240, 228, 66),
+            Color::Rgb(204, 121, 167),
+            Color::Rgb(0, 158, 115),
+            Color::Rgb(213, 94, 0),
+            Color::Rgb(0, 114, 178),
+            Color::Rgb(230, 159, 0),
+        ]),
+        border: Color::Rgb(86, 180, 233),
+        overlay: Some(Color::Rgb(0, 114, 178)),
+    };
+
+    /// Return the color of a shape of `kind`, the color of the cells if it's
+    /// not a standard shape or there are no [Palette::pieces].
+    pub fn piece(&self, kind: Option<game::PieceKind>) -> Color {
+        match (self.pieces, kind) {
+            (Some(pieces), Some(kind)) => pieces[kind as usize],
+            _ => self.cell,
+        }
+    }
+
+    /// Return the color of a shape of `kind` when it's used as the
+    /// background, which must be an actual color.
+    fn solid_piece(&self, kind: Option<game::PieceKind>) -> Color {
+        match self.piece(kind) {
+            Color::Reset => Color::White,
+            color => color,
+        }
+    }
+
+    /// Return the style of the borders.
+    fn border_style(&self) -> Style {
+        Style::default().fg(self.border)
+    }
+}
+
+/// The palettes switched between by the `t` key, in order.
+pub const PALETTES: [Palette; 4] = [
+    Palette::CLASSIC,
+    Palette::MONOCHROME,
+    Palette::HIGH_CONTRAST,
+    Palette::COLORBLIND,
+];
+
+/// Return the index in [PALETTES] of the palette after the one at `index`, it
+/// goes back to the first after the last.
+fn next_palette(index: usize) -> usize {
+    (index + 1) % PALETTES.len()
+}
+
+/// How the level is mirrored when it's drawn, this only changes the display,
+/// e.g. [game::Event::Left] still moves a shape to the left of the level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DisplayOrientation {
+    #[default]
+    Normal,
+    /// The left of the level is drawn on the right.
+    FlippedHorizontal,
+    /// The bottom of the level is drawn on the top.
+    FlippedVertical,
+}
+
+/// How much space a cell of the level takes on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CellSize {
+    /// Two columns for each cell, so it looks square.
+    #[default]
+    Wide,
+    /// Half a row for each cell, two rows of the level are drawn in a row of
+    /// the terminal with the half block characters, so the level takes a
+    /// quarter of the space. The grid isn't drawn in this mode.
+    HalfBlock,
+}
+
+/// How the ghost of the shape is drawn, see [Theme::ghost]. Both styles
+/// look the same with [CellSize::HalfBlock], which only has colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GhostStyle {
+    /// No ghost at all.
+    #[default]
+    Off,
+    /// The outline of each cell, i.e. `[]`.
+    Outline,
+    /// Each cell filled with a dim color.
+    DimFill,
+}
+
+/// A blinking message shown over the level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlay {
+    pub text: &'static str,
+    pub color: Color,
+}
+
+impl Theme {
+    /// Return the overlay for the state of the game, or None if nothing
+    /// should be shown over the level.
+    fn overlay(&self, g: &game::Game) -> Option<Overlay> {
+        let overlay = match g.state {
+            game::State::Init => Some(self.press_start),
+            game::State::Paused => Some(self.paused),
+            game::State::End => match g.summary().map(|s| (s.reason, s.mode)) {
+                Some((game::EndReason::GoalReached, game::GameMode::Sprint { .. })) => {
+                    Some(self.sprint_complete)
+                }
+                _ => Some(self.game_over),
+            },
+            game::State::Playing => None,
+        }?;
+        Some(Overlay {
+            color: self.palette.overlay.unwrap_or(overlay.color),
+            ..overlay
+        })
+    }
+}
+
+/// The symbol drawn in empty cells when [Theme::show_grid] is set.
+const GRID_DOT: &str = "·";
+
+/// The symbols of two cells in a row of the terminal for [CellSize::HalfBlock].
+const UPPER_HALF: &str = "▀";
+const LOWER_HALF: &str = "▄";
+
+/// Return true if `shape` at `at` has a cell at `pos` of the level.
+fn covers(shape: &game::Shape, at: (isize, isize), pos: (usize, usize)) -> bool {
+    let row = pos.0 as isize - at.0;
+    let col = pos.1 as isize - at.1;
+    shape.cells().get(row, col) == Some(true)
+}
+
+/// Return the symbols of the two halves of a cell of a shape of `kind` for
+/// [Theme::patterns], each kind has its own.
+fn pattern(kind: game::PieceKind) -> [&'static str; 2] {
+    match kind {
+        game::PieceKind::I => ["▐", "▌"],
+        game::PieceKind::O => ["▛", "▜"],
+        game::PieceKind::T => ["▙", "▟"],
+        game::PieceKind::S => ["▞", "▞"],
+        game::PieceKind::Z => ["▚", "▚"],
+        game::PieceKind::J => ["▌", "▌"],
+        game::PieceKind::L => ["▐", "▐"],
+    }
+}
+
+/// Return the symbols of the two halves of a cell of a shape of `kind`, its
+/// [pattern] if `patterns` is set and it's a standard shape.
+fn cell_symbols(kind: Option<game::PieceKind>, patterns: bool) -> [&'static str; 2] {
+    match kind {
+        Some(kind) if patterns => pattern(kind),
+        _ => [symbols::block::FULL; 2],
+    }
+}
+
+/// The symbols of a [Trail] from the latest position, so it fades out.
+const TRAIL_SYMBOLS: [&str; 3] = ["▓", "▒", "░"];
+
+/// The last positions of the falling shape, drawn behind it when
+/// [Theme::show_trail] is set. It's updated once per frame and cleared when
+/// the shape is locked.
+#[derive(Debug, Clone, Default)]
+pub struct Trail {
+    /// The shapes and their positions, the latest one is the first.
+    positions: VecDeque<(game::Shape, (isize, isize))>,
+    /// The active shape and the number of locked shapes at the last update.
+    last: Option<(game::Shape, (isize, isize), u32)>,
+}
+
+impl Trail {
+    /// Follow the active shape of `g`, its last position is added to the
+    /// trail if it has moved down.
+    pub fn update(&mut self, g: &game::Game) {
+        let pieces = g.stats().pieces;
+        let current = match g.active_piece() {
+            Some((shape, pos)) if g.state == game::State::Playing => (shape.clone(), pos),
+            _ => {
+                self.positions.clear();
+                self.last = None;
+                return;
+            }
+        };
+
+        if let Some((shape, pos, last_pieces)) = self.last.take() {
+            if last_pieces != pieces || current.1 .0 > pos.0 {
+                // a new shape, the old one is locked (or held).
+                self.positions.clear();
+            } else if current.1 .0 < pos.0 {
+                self.positions.push_front((shape, pos));
+                self.positions.truncate(TRAIL_SYMBOLS.len());
+            }
+        }
+        self.last = Some((current.0, current.1, pieces));
+    }
+
+    /// Return the symbol of the trail at `pos` of the level, if any.
+    fn symbol_at(&self, pos: (usize, usize)) -> Option<&'static str> {
+        self.positions
+            .iter()
+            .position(|(shape, at)| covers(shape, *at, pos))
+            .map(|age| TRAIL_SYMBOLS[age])
+    }
+}
+
+/// The number of frames the banner of a [LevelUpFlash] is shown for.
+const LEVEL_UP_FRAMES: u32 = 8;
+
+/// The banner shown over the level for a few frames after the level goes up,
+/// it's updated once per frame like [Trail].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelUpFlash {
+    /// The level of the game at the last update, None if it wasn't being
+    /// played.
+    last: Option<u32>,
+    /// The level reached and the number of frames it's still shown for.
+    shown: Option<(u32, u32)>,
+}
+
+impl LevelUpFlash {
+    /// Follow the level of `g`, the banner is shown again when it goes up,
+    /// a newer one replaces the one still shown.
+    pub fn update(&mut self, g: &game::Game) {
+        if g.state != game::State::Playing {
+            // a new game could start at a higher level than the last one.
+            *self = LevelUpFlash::default();
+            return;
+        }
+        let level = g.level_number();
+        self.shown = match level_up(self.last, level) {
+            Some(level) => Some((level, LEVEL_UP_FRAMES)),
+            None => self
+                .shown
+                .filter(|(_, frames)| *frames > 1)
+                .map(|(level, frames)| (level, frames - 1)),
+        };
+        self.last = Some(level);
+    }
+
+    /// Return the level to show the banner of, if any.
+    fn level(&self) -> Option<u32> {
+        self.shown.map(|(level, _)| level)
+    }
+}
+
+/// Return the level reached if the level went up from `last` to `now`.
+fn level_up(last: Option<u32>, now: u32) -> Option<u32> {
+    match last {
+        Some(last) if now > last => Some(now),
+        _ => None,
+    }
+}
+
+/// The symbols of the two halves of a ghost cell in [CellSize::Wide] with
+/// [GhostStyle::Outline].
+const GHOST_SYMBOLS: [&str; 2] = ["[", "]"];
+
+/// The number of empty rows above the stack when [in_danger] starts flashing
+/// the border of the level.
+const DANGER_ROWS: usize = 3;
+
+/// Return true if the stack of the game being played is within [DANGER_ROWS]
+/// of the top, see [game::Game::stack_height].
+fn in_danger(game: &game::Game) -> bool {
+    game.state == game::State::Playing && game.stack_height() + DANGER_ROWS >= game.level.rows
+}
+
+/// A ghost shape and its position in the level.
+type Ghost = (game::Shape, (isize, isize));
+
+/// The symbol of the cells marked by [spawn_marker] in [CellSize::Wide].
+const SPAWN_SYMBOL: &str = "▔";
+
+/// The row and the columns of the level marked where the next shape comes in.
+type SpawnMarker = (usize, Vec<usize>);
+
+/// Return the cells at the edge of the level the next shape comes in at, or
+/// None if it isn't waiting to spawn.
+fn spawn_marker(game: &game::Game) -> Option<SpawnMarker> {
+    if !game.spawn_pending() {
+        return None;
+    }
+    let shape = game.next_shapes(1).into_iter().next()?;
+    Some((game.spawn_edge(), game.spawn_columns(shape)))
+}
+
+/// Return true if `marker` has the cell at `pos` of the level.
+fn marks(marker: Option<&SpawnMarker>, pos: (usize, usize)) -> bool {
+    marker.is_some_and(|(row, columns)| pos.0 == *row && columns.contains(&pos.1))
+}
+
+/// Return the ghost of the active shape of `g` and where it lands if it's
+/// dropped. The ghost of the shape rotated by [game::Event::Rotate] is
+/// returned if `rotated` is set and the shape could be rotated, so the player
+/// could see where it would land before rotating it. The game isn't changed.
+pub fn ghost(g: &game::Game, rotated: bool) -> Option<Ghost> {
+    let (shape, pos) = match g.rotated_piece() {
+        Some(piece) if rotated => piece,
+        _ => g.active_piece().map(|(shape, pos)| (shape.clone(), pos))?,
+    };
+    let landing = g.landing_position(&shape, pos);
+    Some((shape, landing))
+}
+
 /// A widget to render a [Game]
 pub struct LevelWidget<'a> {
     block: Block<'a>,
     game: &'a game::Game,
+    theme: Theme,
+    trail: Option<&'a Trail>,
+    rotate_preview: bool,
+    level_up: Option<u32>,
 }
 
 impl<'a> LevelWidget<'a> {
     pub fn new(game: &'a game::Game) -> Self {
         let block = Block::default().title("Tetris").borders(Borders::ALL);
-        LevelWidget { block, game }
+        LevelWidget {
+            block,
+            game,
+            theme: Theme::default(),
+            trail: None,
+            rotate_preview: false,
+            level_up: None,
+        }
     }
 
-    /// Render the game level into a [Buffer], this is a helper function to
-    /// implement [Widget] trait.
-    fn render_to_buffer(self) -> Buffer {
-        let display = self.game.render();
-        let d_height = display.rows as u16;
-        let d_width = display.columns as u16;
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self.block = self.block.border_style(theme.palette.border_style());
+        self
+    }
 
-        let mut buf = Buffer::empty(Rect::new(0, 0, d_width * 2, d_height));
+    /// Draw `trail` behind the shape if [Theme::show_trail] is set.
+    pub fn trail(mut self, trail: &'a Trail) -> Self {
+        self.trail = Some(trail);
+        self
+    }
 
-        for r in 0..display.rows {
-            for c in 0..display.columns {
-                if display[(r, c)] {
-                    let x = (c * 2) as u16;
-                    let y = (display.rows - r - 1) as u16;
-                    buf.get_mut(x, y).set_symbol(symbols::block::FULL);
-                    buf.get_mut(x + 1, y).set_symbol(symbols::block::FULL);
+    /// Draw the ghost of the rotated shape instead of the shape if
+    /// [Theme::ghost] is drawn, e.g. while the rotate key is held.
+    pub fn rotate_preview(mut self, rotate_preview: bool) -> Self {
+        self.rotate_preview = rotate_preview;
+        self
+    }
+
+    /// Show the banner of `flash` over the level and brighten the border
+    /// while it's shown, it must be set after [LevelWidget::theme].
+    pub fn level_up(mut self, flash: &LevelUpFlash) -> Self {
+        self.level_up = flash.level();
+        if self.level_up.is_some() {
+            self.block = self.block.border_style(
+                self.theme
+                    .palette
+                    .border_style()
+                    .add_modifier(Modifier::BOLD),
+            );
+        }
+        self
+    }
+
+    /// Draw the level and the active shape into `area` of `buf`, which must be
+    /// [LevelWidget::level_size] large. The cells are read from the game
+    /// directly, so nothing is allocated.
+    fn render_level(&self, area: Rect, buf: &mut Buffer) {
+        self.render_cells(area, buf);
+        self.render_overlay(area, buf);
+    }
+
+    /// Draw the level like [LevelWidget::render_level] into `area`, which is
+    /// as wide as the level but not as high, so only the rows around the
+    /// active shape are drawn. The whole level is drawn into a buffer of its
+    /// own first, it's only done when the terminal is too small.
+    fn render_clipped(&self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = self.level_size();
+        let mut level_buf = Buffer::empty(Rect::new(0, 0, width, height));
+        self.render_cells(level_buf.area, &mut level_buf);
+
+        let start = clip_start(height, area.height, self.piece_rows());
+        for y in 0..area.height {
+            for x in 0..area.width {
+                *buf.get_mut(area.x + x, area.y + y) = level_buf.get(x, start + y).clone();
+            }
+        }
+        self.render_overlay(area, buf);
+    }
+
+    /// Return the first and the last rows of the screen the active shape is
+    /// drawn in, from the top of the level.
+    fn piece_rows(&self) -> Option<(u16, u16)> {
+        let (shape, pos) = self.game.active_piece()?;
+        let rows = self.game.level.rows;
+        // the shape could stick out of the level when it tops out.
+        let clamp = |row: isize| row.clamp(0, rows as isize - 1) as usize;
+        let (bottom, top) = (clamp(pos.0), clamp(pos.0 + shape.height() as isize - 1));
+        let (first, last) = match self.theme.orientation {
+            DisplayOrientation::FlippedVertical => (bottom, top),
+            _ => (rows - top - 1, rows - bottom - 1),
+        };
+        Some(match self.theme.cell_size {
+            CellSize::Wide => (first as u16, last as u16),
+            CellSize::HalfBlock => ((first / 2) as u16, (last / 2) as u16),
+        })
+    }
+
+    /// Draw the cells of the level and the shape into `area` of `buf`.
+    fn render_cells(&self, area: Rect, buf: &mut Buffer) {
+        if self.theme.hide_board_on_pause && self.game.state == game::State::Paused {
+            for y in area.top()..area.bottom() {
+                for x in area.left()..area.right() {
+                    buf.get_mut(x, y).reset();
                 }
             }
+            return;
         }
+        let ghost = if self.theme.ghost != GhostStyle::Off {
+            ghost(self.game, self.rotate_preview)
+        } else {
+            None
+        };
+        let ghost = ghost.as_ref();
+        let spawn = if self.theme.show_spawn_preview {
+            spawn_marker(self.game)
+        } else {
+            None
+        };
+        let spawn = spawn.as_ref();
+        match self.theme.cell_size {
+            CellSize::Wide => self.render_wide_cells(area, buf, ghost, spawn),
+            CellSize::HalfBlock => self.render_half_blocks(area, buf, ghost, spawn),
+        }
+    }
 
-        let mut tooltip: Option<Span> = None;
-        match self.game.state {
-            game::State::End => {
-                tooltip = Some(Span::styled(
-                    "GAME OVER",
+    /// Draw the overlay for the state of the game in the middle of `area`.
+    fn render_overlay(&self, area: Rect, buf: &mut Buffer) {
+        let tooltip = self
+            .theme
+            .overlay(self.game)
+            .map(|o| {
+                Span::styled(
+                    o.text,
                     Style::default()
-                        .fg(Color::Red)
+                        .fg(o.color)
                         .add_modifier(Modifier::RAPID_BLINK),
-                ));
+                )
+            })
+            .or_else(|| {
+                self.level_up.map(|level| {
+                    Span::styled(
+                        format!("LEVEL {}", level),
+                        Style::default()
+                            .fg(self.theme.palette.overlay.unwrap_or(Color::LightYellow))
+                            .add_modifier(Modifier::BOLD),
+                    )
+                })
+            });
+
+        if let Some(s) = &tooltip {
+            let s_len = s.content.len() as u16;
+            let x = area.x + (area.width / 2).saturating_sub(s_len / 2);
+            buf.set_span(x, area.y + area.height / 2, s, s_len.min(area.right() - x));
+        }
+    }
+
+    /// Draw the cells for [CellSize::Wide].
+    fn render_wide_cells(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        ghost: Option<&Ghost>,
+        spawn: Option<&SpawnMarker>,
+    ) {
+        let (rows, columns) = (self.game.level.rows, self.game.level.columns);
+        let active = self.game.active_piece().map(|(shape, at)| {
+            // the kind of the falling shape is kept, it's rotated.
+            let kind = self.game.current_piece_kind();
+            (
+                shape,
+                at,
+                cell_symbols(kind, self.theme.patterns),
+                self.theme.palette.piece(kind),
+            )
+        });
+        // each cell takes two columns so it looks square.
+        for (r, row) in self.game.rows().enumerate() {
+            for (c, cell) in row.enumerate() {
+                let pos = (rows - r - 1, c);
+                let ghost = ghost.is_some_and(|(shape, at)| covers(shape, *at, pos));
+                let trail = match self.trail {
+                    Some(trail) if self.theme.show_trail && !cell => trail.symbol_at(pos),
+                    _ => None,
+                };
+                let (r, c) = match self.theme.orientation {
+                    DisplayOrientation::Normal => (r, c),
+                    DisplayOrientation::FlippedHorizontal => (r, columns - c - 1),
+                    DisplayOrientation::FlippedVertical => (rows - r - 1, c),
+                };
+                let x = area.x + (c * 2) as u16;
+                let y = area.y + r as u16;
+                buf.get_mut(x, y).reset();
+                buf.get_mut(x + 1, y).reset();
+                if cell {
+                    let ([left, right], color) = match active {
+                        Some((shape, at, symbols, color)) if covers(shape, at, pos) => {
+                            (symbols, color)
+                        }
+                        _ => ([symbols::block::FULL; 2], self.theme.palette.cell),
+                    };
+                    buf.get_mut(x, y).set_symbol(left).set_fg(color);
+                    buf.get_mut(x + 1, y).set_symbol(right).set_fg(color);
+                } else if ghost {
+                    let (symbols, style) = match self.theme.ghost {
+                        GhostStyle::DimFill => (
+                            [symbols::block::FULL; 2],
+                            Style::default()
+                                .fg(Color::DarkGray)
+                                .add_modifier(Modifier::DIM),
+                        ),
+                        _ => (GHOST_SYMBOLS, Style::default().fg(Color::Gray)),
+                    };
+                    buf.get_mut(x, y).set_symbol(symbols[0]).set_style(style);
+                    buf.get_mut(x + 1, y)
+                        .set_symbol(symbols[1])
+                        .set_style(style);
+                } else if let Some(symbol) = trail {
+                    buf.get_mut(x, y).set_symbol(symbol).set_fg(Color::Gray);
+                    buf.get_mut(x + 1, y).set_symbol(symbol).set_fg(Color::Gray);
+                } else if marks(spawn, pos) {
+                    let style = Style::default()
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::DIM);
+                    buf.get_mut(x, y).set_symbol(SPAWN_SYMBOL).set_style(style);
+                    buf.get_mut(x + 1, y)
+                        .set_symbol(SPAWN_SYMBOL)
+                        .set_style(style);
+                } else if self.theme.show_grid {
+                    buf.get_mut(x, y)
+                        .set_symbol(GRID_DOT)
+                        .set_fg(Color::DarkGray);
+                }
             }
-            game::State::Paused => {
-                tooltip = Some(Span::styled(
-                    "Paused",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::RAPID_BLINK),
-                ));
+        }
+    }
+
+    /// Draw the cells for [CellSize::HalfBlock]: the upper half of a
+    /// character is a cell and the lower half is the cell below it, a half
+    /// is drawn with the foreground color if it's the only cell or the
+    /// background color if both are.
+    fn render_half_blocks(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        ghost: Option<&Ghost>,
+        spawn: Option<&SpawnMarker>,
+    ) {
+        let rows = self.game.level.rows;
+        for y in 0..area.height {
+            for x in 0..area.width {
+                let (row, col) = (y as usize * 2, x as usize);
+                let upper = self.color_at((row, col), ghost, spawn);
+                let lower = if row + 1 < rows {
+                    self.color_at((row + 1, col), ghost, spawn)
+                } else {
+                    None
+                };
+
+                let cell = buf.get_mut(area.x + x, area.y + y);
+                cell.reset();
+                match (upper, lower) {
+                    (Some(upper), Some(lower)) if upper == lower => {
+                        cell.set_symbol(symbols::block::FULL).set_fg(upper);
+                    }
+                    (Some(upper), Some(lower)) => {
+                        cell.set_symbol(UPPER_HALF).set_fg(upper).set_bg(lower);
+                    }
+                    (Some(upper), None) => {
+                        cell.set_symbol(UPPER_HALF).set_fg(upper);
+                    }
+                    (None, Some(lower)) => {
+                        cell.set_symbol(LOWER_HALF).set_fg(lower);
+                    }
+                    (None, None) => {}
+                }
             }
-            _ => {}
         }
+    }
 
-        if tooltip.is_some() {
-            let s = tooltip.as_ref().unwrap();
-            let s_len = s.content.len() as u16;
-            buf.set_span(
-                d_width.checked_sub(s_len / 2).unwrap_or(0),
-                d_height / 2,
-                s,
-                s_len,
-            );
+    /// Return the color of the cell drawn at `(row, col)` from the top-left
+    /// of the level, or None if it's empty. The colors are explicit as they
+    /// could be used as the background.
+    fn color_at(
+        &self,
+        pos: (usize, usize),
+        ghost: Option<&Ghost>,
+        spawn: Option<&SpawnMarker>,
+    ) -> Option<Color> {
+        let pos = self.level_pos(pos);
+        let shape = self
+            .game
+            .active_piece()
+            .is_some_and(|(shape, at)| covers(shape, at, pos));
+        if shape {
+            Some(
+                self.theme
+                    .palette
+                    .solid_piece(self.game.current_piece_kind()),
+            )
+        } else if self.game.is_cell_shown(pos) {
+            Some(self.theme.palette.solid_piece(None))
+        } else if ghost.is_some_and(|(shape, at)| covers(shape, *at, pos)) {
+            Some(Color::Gray)
+        } else if (self.theme.show_trail && self.trail.is_some_and(|t| t.symbol_at(pos).is_some()))
+            || marks(spawn, pos)
+        {
+            Some(Color::DarkGray)
+        } else {
+            None
         }
-        buf
+    }
+
+    /// Return the position in the level of the cell drawn at `(row, col)`
+    /// from the top-left.
+    fn level_pos(&self, pos: (usize, usize)) -> (usize, usize) {
+        let (rows, columns) = (self.game.level.rows, self.game.level.columns);
+        let (row, col) = match self.theme.orientation {
+            DisplayOrientation::Normal => pos,
+            DisplayOrientation::FlippedHorizontal => (pos.0, columns - pos.1 - 1),
+            DisplayOrientation::FlippedVertical => (rows - pos.0 - 1, pos.1),
+        };
+        (rows - row - 1, col)
+    }
+
+    /// Return the `(width, height)` of the level without the block, it's only
+    /// meaningful if the level [fits](LevelWidget::too_large) a terminal.
+    fn level_size(&self) -> (u16, u16) {
+        let (width, height) = self.needed_size();
+        let max = u16::MAX as usize;
+        ((width - 2).min(max) as u16, (height - 2).min(max) as u16)
+    }
+
+    /// Return the `(width, height)` of the level with the block, which could
+    /// be larger than any terminal for a custom level.
+    fn needed_size(&self) -> (usize, usize) {
+        let (rows, columns) = (self.game.level.rows, self.game.level.columns);
+        let (width, height) = match self.theme.cell_size {
+            CellSize::Wide => (columns.saturating_mul(2), rows),
+            CellSize::HalfBlock => (columns, rows.div_ceil(2)),
+        };
+        (width.saturating_add(2), height.saturating_add(2))
+    }
+
+    /// Return the size [LevelWidget::needed_size] if the level can't be drawn
+    /// in `area`: it's wider than `area` or too high to be drawn even clipped.
+    /// A level higher than `area` is clipped around the falling shape.
+    fn too_large(&self, area: Rect) -> Option<(usize, usize)> {
+        let (width, height) = self.needed_size();
+        (width > area.width as usize || height > u16::MAX as usize || area.height < 3)
+            .then_some((width, height))
     }
 
     /// Return the expected area of this widget. Note that `(x,y)` is always
-    /// set to `(0,0)`, only `width` and `height` are meaningful.
+    /// set to `(0,0)`, only `width` and `height` are meaningful. It's clamped
+    /// to the largest `Rect` for a level too large for any terminal.
     pub fn expected_area(&self) -> Rect {
-        let width = (self.game.level.columns * 2 + 2) as u16;
-        let height = (self.game.level.rows + 2) as u16;
+        let (width, height) = self.needed_size();
+        let max = u16::MAX as usize;
+        let (width, height) = (width.min(max) as u16, height.min(max) as u16);
         Rect {
             x: 0,
             y: 0,
@@ -102,114 +918,2420 @@ impl<'a> LevelWidget<'a> {
 
 impl<'a> Widget for LevelWidget<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let b = self.block.clone();
-        let level_area = b.inner(area);
-        b.render(area, buf);
-
-        let mut level_buf = self.render_to_buffer();
-        if level_buf.area.height > level_area.height || level_buf.area.width > level_area.width {
-            buf.set_string(
-                level_area.left(),
-                level_area.bottom() - (level_area.height / 2),
-                "Not enough display space",
+        let mut b = self.block.clone();
+        if in_danger(self.game) {
+            b = b.border_style(
+                Style::default()
+                    .fg(Color::Red)
+                    .add_modifier(Modifier::RAPID_BLINK),
+            );
+        }
+        if let Some((width, height)) = self.too_large(area) {
+            let message = format!("Board too large for terminal: needs {}x{}", width, height);
+            let len = (message.len() as u16).min(area.width);
+            let x = area.x + (area.width - len) / 2;
+            buf.set_stringn(
+                x,
+                area.y + area.height / 2,
+                message,
+                len as usize,
                 Style::default(),
             );
             return;
         }
+        let level_area = b.inner(area);
+        b.render(area, buf);
+
+        let (width, height) = self.level_size();
 
-        // put level_buf in the top-center of buf
+        // put the level in the top-center of buf
         let center = (level_area.left() + level_area.right()) / 2;
-        let new_x = center
-            .checked_sub(level_buf.area.width)
-            .unwrap_or(level_area.left());
-        level_buf.resize(Rect {
-            x: new_x,
-            y: level_area.top(),
-            ..level_buf.area
-        });
-        buf.merge(&level_buf);
+        let x = center.saturating_sub(width / 2).max(level_area.left());
+        if height > level_area.height {
+            let area = Rect::new(x, level_area.top(), width, level_area.height);
+            self.render_clipped(area, buf);
+        } else {
+            self.render_level(Rect::new(x, level_area.top(), width, height), buf);
+        }
     }
 }
 
-/// Start the game.
-pub fn start() -> Result<(), io::Error> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+/// Return the first of the `total` rows of the level drawn when there is only
+/// room for `visible` of them: the top ones, unless the `piece` (its first and
+/// last rows) is below them, then the rows down to the bottom of the piece.
+fn clip_start(total: u16, visible: u16, piece: Option<(u16, u16)>) -> u16 {
+    let max = total.saturating_sub(visible);
+    match piece {
+        Some((_, last)) if last >= visible => (last + 1 - visible).min(max),
+        _ => 0,
+    }
+}
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut term = Terminal::new(backend)?;
+/// The number of rows of each shape in [NextWidget], the tallest shape is 4 rows
+/// high and there is an empty row between shapes.
+const NEXT_SLOT_HEIGHT: u16 = 5;
 
-    let game_size: (u16, u16) = (16, 22);
+/// A widget to render the upcoming shapes of a [Game](game::Game), stacked
+/// vertically with the next one on top.
+pub struct NextWidget<'a> {
+    block: Block<'a>,
+    game: &'a game::Game,
+    palette: Palette,
+    patterns: bool,
+}
 
-    let mut g = game::Game::new((game_size.1 as usize, game_size.0 as usize));
-    g.handle_event(game::Event::Start);
+impl<'a> NextWidget<'a> {
+    pub fn new(game: &'a game::Game) -> Self {
+        let block = Block::default().title("Next").borders(Borders::ALL);
+        NextWidget {
+            block,
+            game,
+            palette: Palette::CLASSIC,
+            patterns: false,
+        }
+    }
 
-    let mut last_tick = Instant::now();
-    let tick_rate = Duration::from_millis(200);
-    loop {
-        term.draw(|f| {
-            let size = f.size();
-            let level = LevelWidget::new(&g);
-            let expected_area = level.expected_area();
-            let chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Length(expected_area.width)].as_ref())
-                .split(size);
+    /// Draw the shapes with their patterns, see [Theme::patterns].
+    pub fn patterns(mut self, patterns: bool) -> Self {
+        self.patterns = patterns;
+        self
+    }
 
-            f.render_widget(
-                level,
-                Rect {
-                    width: expected_area.width,
-                    height: expected_area.height,
-                    ..chunks[0]
-                },
-            );
-        })?;
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self.block = self.block.border_style(palette.border_style());
+        self
+    }
 
-        let timeout = tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_secs(0));
+    /// Return the expected area of this widget. Note that `(x,y)` is always
+    /// set to `(0,0)`, only `width` and `height` are meaningful.
+    pub fn expected_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: 4 * 2 + 2,
+            height: self.game.preview_count() as u16 * NEXT_SLOT_HEIGHT + 1,
+        }
+    }
+}
 
-        if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Down => {
-                        for _ in 0..5 {
-                            g.tick();
-                        }
-                    }
-                    KeyCode::Left => {
-                        g.handle_event(game::Event::Left);
-                    }
-                    KeyCode::Right => {
-                        g.handle_event(game::Event::Right);
-                    }
-                    KeyCode::Up => {
-                        g.handle_event(game::Event::Rotate);
-                    }
-                    KeyCode::Char('p') => {
-                        if g.state == game::State::Paused {
-                            g.handle_event(game::Event::Start);
-                        } else {
-                            g.handle_event(game::Event::Pause);
-                        }
+impl<'a> Widget for NextWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let inner = self.block.inner(area);
+        self.block.render(area, buf);
+
+        let shapes = self.game.next_shapes(self.game.preview_count());
+        for (i, shape) in shapes.into_iter().enumerate() {
+            let slot_y = inner.top() + i as u16 * NEXT_SLOT_HEIGHT;
+            let height = shape.height() as u16;
+            let kind = self.game.kind_of(shape);
+            let [left, right] = cell_symbols(kind, self.patterns);
+            let color = self.palette.piece(kind);
+            for r in 0..shape.height() {
+                for c in 0..shape.width() {
+                    let x = inner.left() + (c * 2) as u16;
+                    let y = slot_y + height - r as u16 - 1;
+                    if shape.cells()[(r, c)] && x + 1 < inner.right() && y < inner.bottom() {
+                        buf.get_mut(x, y).set_symbol(left).set_fg(color);
+                        buf.get_mut(x + 1, y).set_symbol(right).set_fg(color);
                     }
-                    KeyCode::Char('q') => break,
-                    _ => {}
                 }
             }
         }
+    }
+}
 
-        while last_tick.elapsed() >= tick_rate {
-            g.tick();
-            last_tick += tick_rate;
+/// Return the play time of `g` shown by [ClockWidget], `since_tick` is the
+/// time since the last tick. The play time only grows by whole ticks, so the
+/// time since the last one is added while the game is played, at most one
+/// tick of it so the clock never runs ahead of the next tick. The time of a
+/// finished game is its final time.
+pub fn clock_time(g: &game::Game, since_tick: Duration) -> Duration {
+    if let Some(time) = g.stats().completion_time {
+        return time;
+    }
+    match g.state {
+        game::State::Playing => g.stats().play_time + since_tick.min(g.gravity_interval()),
+        _ => g.stats().play_time,
+    }
+}
+
+/// A widget to render the play time of a [Game](game::Game) as `mm:ss.cc`,
+/// the time the game is paused isn't counted and it stops when the game is
+/// over.
+pub struct ClockWidget<'a> {
+    block: Block<'a>,
+    game: &'a game::Game,
+    since_tick: Duration,
+}
+
+impl<'a> ClockWidget<'a> {
+    pub fn new(game: &'a game::Game) -> Self {
+        let block = Block::default().title("Time").borders(Borders::ALL);
+        ClockWidget {
+            block,
+            game,
+            since_tick: Duration::ZERO,
         }
     }
 
-    disable_raw_mode()?;
-    execute!(term.backend_mut(), LeaveAlternateScreen)?;
-    term.show_cursor()?;
+    /// Set the time since the last tick, so the clock goes on between ticks.
+    pub fn since_tick(mut self, since_tick: Duration) -> Self {
+        self.since_tick = since_tick;
+        self
+    }
 
-    Ok(())
+    /// Return the expected area of this widget. Note that `(x,y)` is always
+    /// set to `(0,0)`, only `width` and `height` are meaningful.
+    pub fn expected_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: "mm:ss.cc".len() as u16 + 2,
+            height: 3,
+        }
+    }
+}
+
+impl<'a> Widget for ClockWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let time = format_time(clock_time(self.game, self.since_tick));
+        Paragraph::new(time).block(self.block).render(area, buf);
+    }
+}
+
+/// A widget to render the goal of a [Game](game::Game) in
+/// [GameMode::Sprint](game::GameMode::Sprint): the lines cleared out of the
+/// target, the time and the time it would finish in at the pace so far. The
+/// final time is shown once all lines are cleared.
+pub struct SprintWidget<'a> {
+    block: Block<'a>,
+    game: &'a game::Game,
+    since_tick: Duration,
+}
+
+impl<'a> SprintWidget<'a> {
+    pub fn new(game: &'a game::Game) -> Self {
+        let block = Block::default().title("Sprint").borders(Borders::ALL);
+        SprintWidget {
+            block,
+            game,
+            since_tick: Duration::ZERO,
+        }
+    }
+
+    /// Set the time since the last tick, like [ClockWidget::since_tick].
+    pub fn since_tick(mut self, since_tick: Duration) -> Self {
+        self.since_tick = since_tick;
+        self
+    }
+
+    /// Set the style of the borders.
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.block = self.block.border_style(style);
+        self
+    }
+
+    /// Return the expected area of this widget. Note that `(x,y)` is always
+    /// set to `(0,0)`, only `width` and `height` are meaningful.
+    pub fn expected_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: "Pace:  mm:ss.cc".len() as u16 + 2,
+            height: 6,
+        }
+    }
+}
+
+impl<'a> Widget for SprintWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let target = match self.game.mode() {
+            game::GameMode::Sprint { lines } => lines,
+            game::GameMode::Marathon | game::GameMode::Zen | game::GameMode::Survival { .. } => 0,
+        };
+        let cleared = target - self.game.lines_remaining().unwrap_or(0);
+        let time = clock_time(self.game, self.since_tick);
+        let pace =
+            estimated_finish(time, cleared, target).map_or("--:--.--".to_string(), format_time);
+        let mut lines = vec![
+            Spans::from(format!("Lines: {}/{}", cleared, target)),
+            Spans::from(format!("Time:  {}", format_time(time))),
+        ];
+        match self.game.stats().completion_time {
+            Some(_) => lines.push(Spans::from(Span::styled(
+                "FINISHED",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ))),
+            None => lines.push(Spans::from(format!("Pace:  {}", pace))),
+        }
+        Paragraph::new(lines).block(self.block).render(area, buf);
+    }
+}
+
+/// Return the time all `target` lines would be cleared in if the lines are
+/// cleared at the pace of `cleared` lines in `time`, or None before a line is
+/// cleared or any time is played.
+fn estimated_finish(time: Duration, cleared: u32, target: u32) -> Option<Duration> {
+    if cleared == 0 || time.is_zero() {
+        return None;
+    }
+    Some(time.mul_f64(target as f64 / cleared as f64))
+}
+
+/// A widget to render the running statistics of a [Game](game::Game) while
+/// it's played: the shapes locked, how many of them per second, the lines and
+/// the level.
+pub struct StatsWidget<'a> {
+    block: Block<'a>,
+    game: &'a game::Game,
+    since_tick: Duration,
+}
+
+impl<'a> StatsWidget<'a> {
+    pub fn new(game: &'a game::Game) -> Self {
+        let block = Block::default().title("Stats").borders(Borders::ALL);
+        StatsWidget {
+            block,
+            game,
+            since_tick: Duration::ZERO,
+        }
+    }
+
+    /// Set the time since the last tick, like [ClockWidget::since_tick].
+    pub fn since_tick(mut self, since_tick: Duration) -> Self {
+        self.since_tick = since_tick;
+        self
+    }
+
+    /// Set the style of the borders.
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.block = self.block.border_style(style);
+        self
+    }
+
+    /// Return the expected area of this widget. Note that `(x,y)` is always
+    /// set to `(0,0)`, only `width` and `height` are meaningful.
+    pub fn expected_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: 16,
+            height: 6,
+        }
+    }
+}
+
+impl<'a> Widget for StatsWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let stats = self.game.stats();
+        let time = clock_time(self.game, self.since_tick);
+        let lines = vec![
+            Spans::from(format!("Pieces: {}", stats.pieces)),
+            Spans::from(format!(
+                "PPS:    {:.2}",
+                pieces_per_second(stats.pieces, time)
+            )),
+            Spans::from(format!("Lines:  {}", stats.lines)),
+            Spans::from(format!("Level:  {}", self.game.level_number())),
+        ];
+        Paragraph::new(lines).block(self.block).render(area, buf);
+    }
+}
+
+/// The [combo](game::Game::combo) which fills the [ComboWidget].
+const COMBO_METER_MAX: i32 = 10;
+
+/// A widget to render the [combo](game::Game::combo) of a
+/// [Game](game::Game) as a meter, which fills up as the combo goes on and
+/// empties when it breaks.
+pub struct ComboWidget<'a> {
+    block: Block<'a>,
+    game: &'a game::Game,
+}
+
+impl<'a> ComboWidget<'a> {
+    pub fn new(game: &'a game::Game) -> Self {
+        let block = Block::default().title("Combo").borders(Borders::ALL);
+        ComboWidget { block, game }
+    }
+
+    /// Set the style of the borders.
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.block = self.block.border_style(style);
+        self
+    }
+
+    /// Return the expected area of this widget. Note that `(x,y)` is always
+    /// set to `(0,0)`, only `width` and `height` are meaningful.
+    pub fn expected_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: 16,
+            height: 3,
+        }
+    }
+}
+
+impl<'a> Widget for ComboWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let combo = self.game.combo();
+        let label = match combo {
+            c if c > 0 => format!("{} REN", c),
+            _ => String::new(),
+        };
+        Gauge::default()
+            .block(self.block)
+            .gauge_style(Style::default().fg(Color::LightMagenta))
+            .ratio(combo_ratio(combo))
+            .label(label)
+            .render(area, buf);
+    }
+}
+
+/// Return the part of the [ComboWidget] filled for `combo`: nothing without
+/// a combo, all of it from [COMBO_METER_MAX] on.
+fn combo_ratio(combo: i32) -> f64 {
+    combo.clamp(0, COMBO_METER_MAX) as f64 / COMBO_METER_MAX as f64
+}
+
+/// Return the number of `pieces` locked per second of play `time`, it's 0
+/// before any time is played.
+fn pieces_per_second(pieces: u32, time: Duration) -> f64 {
+    if time.is_zero() {
+        return 0.0;
+    }
+    pieces as f64 / time.as_secs_f64()
+}
+
+/// Format a duration as `mm:ss.cc`.
+fn format_time(d: Duration) -> String {
+    let centis = d.as_millis() / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// The shortest time between two updates of the title of the terminal
+/// window with [Options::window_title].
+const TITLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Return the title of the terminal window with [Options::window_title].
+pub fn window_title(g: &game::Game) -> String {
+    format!("tetris - Score: {}", g.stats().score)
+}
+
+/// Return the line drawn at the bottom of the terminal with
+/// [Options::window_title].
+pub fn status_bar(g: &game::Game) -> String {
+    let lines = match g.mode() {
+        game::GameMode::Sprint { lines } => format!("{}/{}", g.stats().lines.min(lines), lines),
+        _ => g.stats().lines.to_string(),
+    };
+    format!(
+        "Level {} | Lines {} | {}",
+        g.level_number(),
+        lines,
+        g.mode().name()
+    )
+}
+
+/// Return the text of the status panel shown next to the level.
+fn status_lines(g: &game::Game) -> Vec<Spans<'static>> {
+    let lines = match g.mode() {
+        game::GameMode::Sprint { lines } => {
+            format!("Lines: {}/{}", g.stats().lines.min(lines), lines)
+        }
+        game::GameMode::Marathon | game::GameMode::Zen | game::GameMode::Survival { .. } => {
+            format!("Lines: {}", g.stats().lines)
+        }
+    };
+    let time = g.stats().completion_time.unwrap_or(g.stats().play_time);
+    let mut res = vec![
+        Spans::from(format!("Level: {}", g.level_number())),
+        Spans::from(format!("Score: {}", g.stats().score)),
+        Spans::from(lines),
+        Spans::from(format!("Time:  {}", format_time(time))),
+    ];
+    if let Some(summary) = g.summary() {
+        res.push(Spans::from(format!(
+            "PPM:   {:.1}",
+            summary.stats.pieces_per_minute()
+        )));
+        res.push(Spans::from(format!(
+            "LPP:   {:.2}",
+            summary.stats.efficiency()
+        )));
+        if summary.stats.garbage_cleared > 0 {
+            res.push(Spans::from(format!(
+                "Down:  {:.2}",
+                summary.stats.downstack_efficiency()
+            )));
+        }
+    }
+    // shown before and after the game, so it could be shared and played
+    // again.
+    if let (Some(seed), game::State::Init | game::State::End) = (g.seed(), g.state) {
+        res.push(Spans::from(format!("Seed:  {}", seed)));
+    }
+    res.push(Spans::from("Hold:"));
+    let cell = symbols::block::FULL.repeat(2);
+    for held in g.held_pieces() {
+        for row in held.to_string().lines() {
+            res.push(Spans::from(row.replace('#', &cell).replace('.', "  ")));
+        }
+    }
+    res
+}
+
+/// Move the selection of a menu with `len` items by `delta`, it stops at the
+/// first and the last item.
+fn move_selection(state: &mut ListState, len: usize, delta: isize) {
+    let selected = state.selected().unwrap_or(0) as isize + delta;
+    state.select(Some(selected.clamp(0, len as isize - 1) as usize));
+}
+
+/// The items of the menu shown when the game is paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseItem {
+    Resume,
+    Restart,
+    /// Go back to the start screen.
+    Stop,
+    Quit,
+}
+
+impl PauseItem {
+    const ALL: [PauseItem; 4] = [
+        PauseItem::Resume,
+        PauseItem::Restart,
+        PauseItem::Stop,
+        PauseItem::Quit,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Restart => "Restart",
+            PauseItem::Stop => "Stop",
+            PauseItem::Quit => "Quit",
+        }
+    }
+}
+
+/// Render the pause menu below the center of `area`.
+fn render_pause_menu<B: Backend>(f: &mut tui::Frame<B>, area: Rect, state: &mut ListState) {
+    let items: Vec<ListItem> = PauseItem::ALL
+        .iter()
+        .map(|i| ListItem::new(i.name()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    let width = 14;
+    let menu_area = Rect {
+        x: (area.left() + area.right()).saturating_sub(width) / 2,
+        y: area.top() + area.height / 2 + 1,
+        width,
+        height: PauseItem::ALL.len() as u16 + 2,
+    }
+    .intersection(area);
+    f.render_widget(Clear, menu_area);
+    f.render_stateful_widget(list, menu_area, state);
+}
+
+/// Show the start menu and let the player choose a difficulty, it returns None
+/// if the player quits.
+fn select_difficulty<B: Backend>(term: &mut Terminal<B>) -> io::Result<Option<game::Difficulty>> {
+    let choices = game::Difficulty::ALL;
+    let mut state = ListState::default();
+    state.select(choices.iter().position(|d| *d == game::Difficulty::Normal));
+
+    loop {
+        term.draw(|f| {
+            let items: Vec<ListItem> = choices.iter().map(|d| ListItem::new(d.name())).collect();
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .title("Select difficulty")
+                        .borders(Borders::ALL),
+                )
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ");
+            let area = Rect {
+                width: 24,
+                height: choices.len() as u16 + 2,
+                ..f.size()
+            }
+            .intersection(f.size());
+            f.render_stateful_widget(list, area, &mut state);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Up => move_selection(&mut state, choices.len(), -1),
+                KeyCode::Down => move_selection(&mut state, choices.len(), 1),
+                KeyCode::Enter => return Ok(Some(choices[state.selected().unwrap_or(0)])),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Return the seed typed by the player, or None if it's empty or isn't a
+/// number, then a random seed is used.
+fn parse_seed(input: &str) -> Option<u64> {
+    input.trim().parse().ok()
+}
+
+/// Let the player type the seed of the shapes, it returns None if the player
+/// quits.
+fn input_seed<B: Backend>(term: &mut Terminal<B>) -> io::Result<Option<String>> {
+    let mut input = String::new();
+    loop {
+        term.draw(|f| {
+            let lines = vec![
+                Spans::from(format!("> {}", input)),
+                Spans::from(Span::styled(
+                    "empty for random",
+                    Style::default().add_modifier(Modifier::DIM),
+                )),
+            ];
+            let area = Rect {
+                width: 24,
+                height: 4,
+                ..f.size()
+            }
+            .intersection(f.size());
+            let seed =
+                Paragraph::new(lines).block(Block::default().title("Seed").borders(Borders::ALL));
+            f.render_widget(seed, area);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char(c) if input.len() < 20 => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => return Ok(Some(input)),
+                KeyCode::Esc => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Leave the raw mode and the alternate screen and show the cursor, so the
+/// terminal is usable again.
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, cursor::Show)
+}
+
+/// Call `restore` when it's dropped, i.e. when the game is over, an error is
+/// returned or a panic unwinds the stack.
+struct TerminalGuard<F: FnMut()> {
+    restore: F,
+}
+
+impl<F: FnMut()> Drop for TerminalGuard<F> {
+    fn drop(&mut self) {
+        (self.restore)();
+    }
+}
+
+/// Run `f` with the terminal in the raw mode and the alternate screen. The
+/// terminal is restored however it ends, a panic restores it before its
+/// message is printed so the message isn't lost in the alternate screen.
+/// The panic hook of the caller is put back afterwards.
+fn with_terminal(
+    f: impl FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()>,
+) -> io::Result<()> {
+    let previous_hook = Arc::new(panic::take_hook());
+    let hook = Arc::clone(&previous_hook);
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        hook(info);
+    }));
+    let _guard = TerminalGuard {
+        restore: move || {
+            let _ = restore_terminal();
+            // the hook can't be changed while a panic unwinds, it's left to
+            // restore the terminal again, which does no harm.
+            if !thread::panicking() {
+                let previous_hook = Arc::clone(&previous_hook);
+                panic::set_hook(Box::new(move |info| previous_hook(info)));
+            }
+        },
+    };
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+
+    let backend = CrosstermBackend::new(stdout);
+    let mut term = Terminal::new(backend)?;
+    f(&mut term)
+}
+
+/// Start the game with `settings` applied to `options`, see [Settings::apply]
+/// and [with_terminal]. It fails with [io::ErrorKind::InvalidInput] before
+/// the terminal is touched if a game can't be played with them, see
+/// [Options::validate].
+pub fn start(mut options: Options, settings: Settings) -> Result<(), io::Error> {
+    settings.apply(&mut options);
+    options
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    with_terminal(|term| play(term, options))
+}
+
+/// Show the start menu and play the game.
+fn play(term: &mut Terminal<CrosstermBackend<io::Stdout>>, mut options: Options) -> io::Result<()> {
+    let mut difficulty = match options.difficulty {
+        Some(d) => Some(d),
+        None => select_difficulty(term)?,
+    };
+    // the start menu asks for the seed after the difficulty.
+    if difficulty.is_some() && options.difficulty.is_none() && options.seed.is_none() {
+        match input_seed(term)? {
+            Some(input) => options.seed = parse_seed(&input),
+            None => difficulty = None,
+        }
+    }
+    // a random seed is picked here instead of by the game, so it can be shown.
+    options.seed = Some(options.seed.unwrap_or_else(rand::random));
+    if let Some(difficulty) = difficulty {
+        let (focus_change, window_title) = (options.pause_on_focus_lost, options.window_title);
+        // the modes are turned off and the title is cleared however the game
+        // ends, turning them off before they are on does nothing.
+        let _modes = TerminalGuard {
+            restore: move || {
+                let mut stdout = io::stdout();
+                let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+                if focus_change {
+                    let _ = execute!(stdout, DisableFocusChange);
+                }
+                if window_title {
+                    let _ = execute!(stdout, SetTitle(""));
+                }
+            },
+        };
+        if focus_change {
+            execute!(term.backend_mut(), EnableFocusChange)?;
+        }
+        // so a held key is let go as soon as it's released, where the
+        // terminal supports it.
+        execute!(
+            term.backend_mut(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+        run(term, options.config(difficulty), &options)?;
+    }
+
+    Ok(())
+}
+
+/// Step through `replay` in the terminal: the right and the left keys go a tick
+/// forward and back, page down and page up by [SCRUB_PAGE] ticks, home and
+/// end to the start and the end, and q quits.
+pub fn scrub(replay: game::Replay) -> io::Result<()> {
+    let mut scrubber = game::Scrubber::new(replay);
+    with_terminal(|term| loop {
+        term.draw(|f| {
+            let size = f.size();
+            let level = LevelWidget::new(scrubber.game());
+            let level_area = Rect {
+                x: 0,
+                y: 0,
+                ..level.expected_area()
+            }
+            .intersection(size);
+            f.render_widget(level, level_area);
+            let info = Paragraph::new(scrub_lines(&scrubber))
+                .block(Block::default().title("Replay").borders(Borders::ALL));
+            let info_area = Rect {
+                x: level_area.right(),
+                y: 0,
+                width: 20,
+                height: 4,
+            }
+            .intersection(size);
+            f.render_widget(info, info_area);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            let tick = scrubber.tick();
+            match key.code {
+                KeyCode::Right => scrubber.step_forward(),
+                KeyCode::Left => scrubber.step_back(),
+                KeyCode::PageDown => scrubber.seek(tick + SCRUB_PAGE),
+                KeyCode::PageUp => scrubber.seek(tick.saturating_sub(SCRUB_PAGE)),
+                KeyCode::Home => scrubber.seek(0),
+                KeyCode::End => scrubber.seek(scrubber.len()),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                _ => {}
+            }
+        }
+    })
+}
+
+/// The number of ticks page up and page down go by in [scrub].
+const SCRUB_PAGE: usize = 10;
+
+/// Return the text shown next to the level by [scrub].
+fn scrub_lines(scrubber: &game::Scrubber) -> Vec<Spans<'static>> {
+    vec![
+        Spans::from(format!("Tick:  {}/{}", scrubber.tick(), scrubber.len())),
+        Spans::from(format!("Score: {}", scrubber.game().stats().score)),
+    ]
+}
+
+/// Pause the game if it's being played and show the pause menu.
+fn pause(g: &mut game::Game, pause_menu: &mut ListState) {
+    if g.state == game::State::Playing {
+        g.handle_event(game::Event::Pause);
+        pause_menu.select(Some(0));
+    }
+}
+
+/// Return true if the player has been idle for the `timeout` since the last
+/// input, it's always false without a timeout.
+fn is_idle(last_input: Instant, now: Instant, timeout: Option<Duration>) -> bool {
+    timeout.is_some_and(|timeout| now.saturating_duration_since(last_input) >= timeout)
+}
+
+/// How a held key acts again by itself without waiting for the terminal to
+/// repeat it: once `delay` after it's pressed (the DAS), then every
+/// `interval` (the ARR).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoRepeat {
+    pub delay: Duration,
+    pub interval: Duration,
+}
+
+impl AutoRepeat {
+    /// Return the number of times a key held for `held` has repeated, it's 0
+    /// until the delay passes. An interval of zero is taken as 1ms.
+    pub fn repeats(&self, held: Duration) -> u32 {
+        match held.checked_sub(self.delay) {
+            Some(since) => 1 + (since.as_nanos() / self.interval().as_nanos()) as u32,
+            None => 0,
+        }
+    }
+
+    /// Return how long after being pressed a key held for `held` repeats
+    /// next.
+    pub fn next_repeat(&self, held: Duration) -> Duration {
+        self.delay + self.interval() * self.repeats(held)
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval.max(Duration::from_millis(1))
+    }
+}
+
+/// How soon after the first press of the hard drop key the second one must
+/// come with [Options::confirm_hard_drop], it's long enough for the key
+/// repeated by the terminal to confirm it if the key is held.
+const CONFIRM_WINDOW: Duration = Duration::from_millis(600);
+
+/// Return true if a press of the hard drop key at `now` confirms the one at
+/// `first`, i.e. it comes within [CONFIRM_WINDOW].
+fn confirms(first: Option<Instant>, now: Instant) -> bool {
+    first.is_some_and(|first| now.saturating_duration_since(first) < CONFIRM_WINDOW)
+}
+
+/// How long a key is taken as held after it's pressed, most terminals don't
+/// report releasing keys, so a key is held while it repeats.
+const HOLD_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Return true if the key last pressed at `pressed` is still held at `now`, it
+/// is cleared when the key is released.
+fn is_held(pressed: Option<Instant>, now: Instant) -> bool {
+    pressed.is_some_and(|pressed| now.saturating_duration_since(pressed) < HOLD_TIMEOUT)
+}
+
+/// Wait for up to `timeout` for an event, then take all the other pending
+/// events without waiting, so the inputs between two frames are all handled.
+fn read_events(
+    timeout: Duration,
+    mut poll: impl FnMut(Duration) -> io::Result<bool>,
+    mut read: impl FnMut() -> io::Result<Event>,
+) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    let mut wait = timeout;
+    while poll(wait)? {
+        events.push(read()?);
+        wait = Duration::ZERO;
+    }
+    Ok(events)
+}
+
+/// Handle a key pressed by the player, it returns false if the player quits.
+fn handle_key(
+    g: &mut game::Game,
+    pause_menu: &mut ListState,
+    key: KeyEvent,
+    keys: &KeyBindings,
+    mirror_controls: bool,
+) -> bool {
+    let (pause_key, quit_key) = (keys.pause, keys.quit);
+    if g.state == game::State::Paused {
+        let len = PauseItem::ALL.len();
+        match key.code {
+            KeyCode::Up => move_selection(pause_menu, len, -1),
+            KeyCode::Down => move_selection(pause_menu, len, 1),
+            KeyCode::Enter => match PauseItem::ALL[pause_menu.selected().unwrap_or(0)] {
+                PauseItem::Resume => {
+                    g.handle_event(game::Event::Start);
+                }
+                PauseItem::Restart => g.restart(),
+                PauseItem::Stop => g.stop(),
+                PauseItem::Quit => return false,
+            },
+            code if pause_key.matches(code) => {
+                g.handle_event(game::Event::Start);
+            }
+            code if quit_key.matches(code) => return false,
+            _ => {}
+        }
+        return true;
+    }
+
+    if g.state == game::State::Init {
+        match key.code {
+            KeyCode::Enter => {
+                g.handle_event(game::Event::Start);
+            }
+            code if quit_key.matches(code) => return false,
+            _ => {}
+        }
+        return true;
+    }
+
+    match key.code {
+        code if pause_key.matches(code) => pause(g, pause_menu),
+        code if quit_key.matches(code) => return false,
+        _ => {
+            let event = match game_event(key, keys) {
+                Some(event) if mirror_controls => mirrored(event),
+                Some(event) => event,
+                None => return true,
+            };
+            g.handle_event(event);
+        }
+    }
+    true
+}
+
+/// Return the event of the game a key pressed while it's being played stands
+/// for with `keys`, if any.
+fn game_event(key: KeyEvent, keys: &KeyBindings) -> Option<game::Event> {
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    Some(match key.code {
+        // holding it speeds up the gravity, see [game::Event::SoftDropHeld].
+        code if keys.soft_drop.matches(code) => game::Event::SoftDrop,
+        code if keys.left.matches(code) && shift => game::Event::MoveToWall(game::Direction::Left),
+        code if keys.right.matches(code) && shift => {
+            game::Event::MoveToWall(game::Direction::Right)
+        }
+        code if keys.left.matches(code) => game::Event::Left,
+        code if keys.right.matches(code) => game::Event::Right,
+        code if keys.rotate.matches(code) => game::Event::Rotate,
+        code if keys.hard_drop.matches(code) => game::Event::HardDrop,
+        code if keys.hold.matches(code) => game::Event::Hold,
+        KeyCode::Char('u') => game::Event::Undo,
+        KeyCode::Char('r') => game::Event::RetryPiece,
+        KeyCode::Char(c @ '1'..='9') => game::Event::PickPreview(c as usize - '1' as usize),
+        _ => return None,
+    })
+}
+
+/// Return `event` with the left and the right swapped, for
+/// [Options::mirror_controls]. There is only one rotation, it's left as it
+/// is.
+fn mirrored(event: game::Event) -> game::Event {
+    match event {
+        game::Event::Left => game::Event::Right,
+        game::Event::Right => game::Event::Left,
+        game::Event::MoveToWall(game::Direction::Left) => {
+            game::Event::MoveToWall(game::Direction::Right)
+        }
+        game::Event::MoveToWall(game::Direction::Right) => {
+            game::Event::MoveToWall(game::Direction::Left)
+        }
+        event => event,
+    }
+}
+
+/// Run the game until the player quits.
+fn run<B: Backend>(
+    term: &mut Terminal<B>,
+    config: game::GameConfig,
+    options: &Options,
+) -> io::Result<()> {
+    // the game waits on the start screen until the player starts it.
+    let mut g = game::Game::with_config(config)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if options.record.is_some() {
+        g.start_recording();
+    }
+
+    let mut pause_menu = ListState::default();
+    let mut trail = Trail::default();
+    let mut level_up = LevelUpFlash::default();
+    let mut last_tick = Instant::now();
+    let mut last_input = Instant::now();
+    let mut rotate_pressed = None;
+    let mut soft_drop_pressed = None;
+    // when the down key was pressed and the number of times it has repeated,
+    // with [Options::soft_drop_repeat].
+    let mut soft_drop_start: Option<(Instant, u32)> = None;
+    // the first press of the hard drop key and the number of shapes locked
+    // then, with [Options::confirm_hard_drop].
+    let mut hard_drop_press: Option<(Instant, u32)> = None;
+    let mut palette = PALETTES
+        .iter()
+        .position(|p| *p == options.theme.palette)
+        .unwrap_or(0);
+    // when the title was set last and to what.
+    let mut title: Option<(Instant, String)> = None;
+    #[cfg(feature = "spectator")]
+    let spectators = options
+        .spectate
+        .map(crate::spectator::Spectators::bind)
+        .transpose()?;
+    'game: loop {
+        let now = Instant::now();
+        let soft_drop_held = is_held(soft_drop_pressed, now);
+        if !soft_drop_held || g.state != game::State::Playing {
+            soft_drop_start = None;
+        }
+        let fast_drop = soft_drop_held && options.soft_drop_repeat.is_none();
+        if fast_drop != g.soft_drop_held() {
+            g.handle_event(game::Event::SoftDropHeld(fast_drop));
+        }
+        let tick_rate = g.tick_interval();
+        trail.update(&g);
+        level_up.update(&g);
+        let rotate_preview = is_held(rotate_pressed, now);
+        let theme = Theme {
+            palette: PALETTES[palette],
+            ..options.theme
+        };
+        if options.window_title {
+            let text = window_title(&g);
+            let due = title
+                .as_ref()
+                .is_none_or(|(at, last)| *last != text && at.elapsed() >= TITLE_INTERVAL);
+            if due {
+                execute!(io::stdout(), SetTitle(&text))?;
+                title = Some((Instant::now(), text));
+            }
+        }
+        term.draw(|f| {
+            let mut size = f.size();
+            // the bar takes the last row, the rest is laid out above it.
+            let bar = (options.window_title && size.height > 1).then(|| {
+                size.height -= 1;
+                Rect {
+                    y: size.bottom(),
+                    height: 1,
+                    ..size
+                }
+            });
+            let level = LevelWidget::new(&g)
+                .theme(theme)
+                .trail(&trail)
+                .rotate_preview(rotate_preview)
+                .level_up(&level_up);
+            let expected_area = level.expected_area();
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    [
+                        Constraint::Length(expected_area.width),
+                        Constraint::Length(20),
+                        Constraint::Length(16),
+                    ]
+                    .as_ref(),
+                )
+                .split(size);
+
+            let level_area = Rect {
+                width: expected_area.width,
+                height: expected_area.height,
+                ..chunks[0]
+            }
+            .intersection(size);
+            f.render_widget(level, level_area);
+            if g.state == game::State::Paused {
+                render_pause_menu(f, level_area, &mut pause_menu);
+            }
+
+            let status = Paragraph::new(status_lines(&g)).block(
+                Block::default()
+                    .title("Status")
+                    .borders(Borders::ALL)
+                    .border_style(theme.palette.border_style()),
+            );
+            let status_area = Rect {
+                height: 12,
+                ..chunks[1]
+            }
+            .intersection(size);
+            f.render_widget(status, status_area);
+
+            let mut next_y = status_area.bottom();
+            if let game::GameMode::Sprint { .. } = g.mode() {
+                let sprint = SprintWidget::new(&g)
+                    .since_tick(last_tick.elapsed())
+                    .border_style(theme.palette.border_style());
+                let sprint_area = Rect {
+                    x: chunks[1].x,
+                    y: next_y,
+                    ..sprint.expected_area()
+                }
+                .intersection(size);
+                next_y = sprint_area.bottom();
+                f.render_widget(sprint, sprint_area);
+            }
+
+            let next = NextWidget::new(&g)
+                .palette(theme.palette)
+                .patterns(theme.patterns);
+            let next_area = Rect {
+                x: chunks[1].x,
+                y: next_y,
+                ..next.expected_area()
+            }
+            .intersection(size);
+            f.render_widget(next, next_area);
+
+            let stats = StatsWidget::new(&g)
+                .since_tick(last_tick.elapsed())
+                .border_style(theme.palette.border_style());
+            let stats_area = Rect {
+                x: chunks[2].x,
+                y: chunks[2].y,
+                ..stats.expected_area()
+            }
+            .intersection(size);
+            f.render_widget(stats, stats_area);
+
+            let combo = ComboWidget::new(&g).border_style(theme.palette.border_style());
+            let combo_area = Rect {
+                x: chunks[2].x,
+                y: stats_area.bottom(),
+                ..combo.expected_area()
+            }
+            .intersection(size);
+            f.render_widget(combo, combo_area);
+
+            if let Some(bar) = bar {
+                f.render_widget(Paragraph::new(status_bar(&g)), bar);
+            }
+        })?;
+
+        let mut timeout = tick_rate
+            .checked_sub(last_tick.elapsed())
+            .unwrap_or_else(|| Duration::from_secs(0));
+        if let (Some(repeat), Some((start, _))) = (options.soft_drop_repeat, soft_drop_start) {
+            let held = start.elapsed();
+            timeout = timeout.min(repeat.next_repeat(held).saturating_sub(held));
+        }
+
+        let lines = g.stats().lines;
+        for e in read_events(timeout, event::poll, event::read)? {
+            match e {
+                Event::Key(key) if key.kind == KeyEventKind::Release => {
+                    // only the held keys are followed, the others act on
+                    // presses.
+                    match key.code {
+                        code if options.keys.rotate.matches(code) => rotate_pressed = None,
+                        code if options.keys.soft_drop.matches(code) => soft_drop_pressed = None,
+                        _ => {}
+                    }
+                }
+                Event::Key(key) => {
+                    last_input = Instant::now();
+                    match key.code {
+                        code if options.keys.rotate.matches(code) => {
+                            rotate_pressed = Some(last_input)
+                        }
+                        code if options.keys.soft_drop.matches(code) => {
+                            soft_drop_pressed = Some(last_input)
+                        }
+                        KeyCode::Char('t') => palette = next_palette(palette),
+                        _ => {}
+                    }
+                    if options.keys.soft_drop.matches(key.code)
+                        && options.soft_drop_repeat.is_some()
+                        && g.state == game::State::Playing
+                    {
+                        // the key repeated by the terminal is left to the
+                        // auto repeat.
+                        if soft_drop_start.is_some() {
+                            continue;
+                        }
+                        soft_drop_start = Some((last_input, 0));
+                    }
+                    if options.keys.hard_drop.matches(key.code)
+                        && options.confirm_hard_drop
+                        && g.state == game::State::Playing
+                    {
+                        // a press for a shape locked since doesn't count.
+                        let first = hard_drop_press
+                            .filter(|(_, pieces)| *pieces == g.stats().pieces)
+                            .map(|(first, _)| first);
+                        if !confirms(first, last_input) {
+                            hard_drop_press = Some((last_input, g.stats().pieces));
+                            continue;
+                        }
+                        hard_drop_press = None;
+                    }
+                    if !handle_key(
+                        &mut g,
+                        &mut pause_menu,
+                        key,
+                        &options.keys,
+                        options.mirror_controls,
+                    ) {
+                        break 'game;
+                    }
+                }
+                Event::FocusLost if options.pause_on_focus_lost => {
+                    pause(&mut g, &mut pause_menu);
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(repeat), Some((start, done))) =
+            (options.soft_drop_repeat, &mut soft_drop_start)
+        {
+            let due = repeat.repeats(start.elapsed());
+            for _ in *done..due {
+                g.handle_event(game::Event::SoftDrop);
+            }
+            *done = due;
+        }
+
+        if g.state == game::State::Playing
+            && is_idle(last_input, Instant::now(), options.idle_pause)
+        {
+            pause(&mut g, &mut pause_menu);
+        }
+
+        while last_tick.elapsed() >= tick_rate {
+            g.tick();
+            last_tick += tick_rate;
+            #[cfg(feature = "spectator")]
+            if let Some(spectators) = &spectators {
+                spectators.serve(&g);
+            }
+        }
+        if options.sound && g.stats().lines > lines {
+            // the bell character, most terminals beep or flash on it.
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
+    }
+
+    match (&options.record, g.recording()) {
+        (Some(path), Some(replay)) => save_replay(&replay, path),
+        _ => Ok(()),
+    }
+}
+
+/// Write `replay` as JSON to the file at `path`.
+fn save_replay(replay: &game::Replay, path: &Path) -> io::Result<()> {
+    let json = serde_json::to_string(replay)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use tui::buffer::Cell;
+
+    /// Return the symbols in `area` of `buf` as lines of text.
+    fn buffer_lines(buf: &Buffer, area: Rect) -> Vec<String> {
+        (area.top()..area.bottom())
+            .map(|y| {
+                (area.left()..area.right())
+                    .map(|x| buf.get(x, y).symbol.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The original rendering, which draws a copy of the level with the shape
+    /// into its own buffer and merges it, the widget must draw exactly the
+    /// same.
+    fn render_by_copy(widget: &LevelWidget, area: Rect, buf: &mut Buffer) {
+        let level_area = widget.block.inner(area);
+        widget.block.clone().render(area, buf);
+
+        let display = widget.game.render();
+        let d_height = display.rows as u16;
+        let d_width = display.columns as u16;
+        let mut level_buf = Buffer::empty(Rect::new(0, 0, d_width * 2, d_height));
+        for r in 0..display.rows {
+            for c in 0..display.columns {
+                let x = (c * 2) as u16;
+                let y = (display.rows - r - 1) as u16;
+                if display[(r, c)] {
+                    level_buf.get_mut(x, y).set_symbol(symbols::block::FULL);
+                    level_buf.get_mut(x + 1, y).set_symbol(symbols::block::FULL);
+                } else if widget.theme.show_grid {
+                    level_buf
+                        .get_mut(x, y)
+                        .set_symbol(GRID_DOT)
+                        .set_fg(Color::DarkGray);
+                }
+            }
+        }
+        if let Some(o) = widget.theme.overlay(widget.game) {
+            let s = Span::styled(
+                o.text,
+                Style::default()
+                    .fg(o.color)
+                    .add_modifier(Modifier::RAPID_BLINK),
+            );
+            let s_len = s.content.len() as u16;
+            level_buf.set_span(d_width.saturating_sub(s_len / 2), d_height / 2, &s, s_len);
+        }
+
+        let center = (level_area.left() + level_area.right()) / 2;
+        let new_x = center
+            .saturating_sub(level_buf.area.width / 2)
+            .max(level_area.left());
+        level_buf.resize(Rect {
+            x: new_x,
+            y: level_area.top(),
+            ..level_buf.area
+        });
+        buf.merge(&level_buf);
+    }
+
+    #[test]
+    fn level_matches_copy() {
+        let config = game::GameConfig::new((12, 6)).seed(3);
+        let mut g = game::Game::with_config(config).unwrap();
+        let area = Rect::new(0, 0, 30, 16);
+        let grid = Theme {
+            show_grid: true,
+            ..Theme::default()
+        };
+        for i in 0..8 {
+            for theme in [Theme::default(), grid] {
+                // start from a dirty buffer, the level must overwrite it.
+                let mut expected = Buffer::filled(area, Cell::default().set_symbol("x"));
+                let mut buf = expected.clone();
+                let widget = LevelWidget::new(&g).theme(theme);
+                render_by_copy(&widget, area, &mut expected);
+                widget.render(area, &mut buf);
+                assert_eq!(buf, expected, "step {}", i);
+            }
+            match i {
+                0 => g.handle_event(game::Event::Start),
+                3 => g.handle_event(game::Event::Pause),
+                4 => g.handle_event(game::Event::Start),
+                _ => g.handle_event(game::Event::HardDrop),
+            };
+        }
+    }
+
+    #[test]
+    fn level_orientation() {
+        let mut g = game::Game::new((4, 3));
+        g.level[(0, 0)] = true;
+        g.level[(0, 1)] = true;
+        g.level[(3, 2)] = true;
+
+        let render = |orientation| {
+            let widget = LevelWidget::new(&g).theme(Theme {
+                orientation,
+                ..Theme::default()
+            });
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        // the overlay stays in the middle whatever the orientation is.
+        assert_eq!(
+            render(DisplayOrientation::Normal),
+            vec!["    ██", "      ", "Press ", "████  "]
+        );
+        assert_eq!(
+            render(DisplayOrientation::FlippedHorizontal),
+            vec!["██    ", "      ", "Press ", "  ████"]
+        );
+        assert_eq!(
+            render(DisplayOrientation::FlippedVertical),
+            vec!["████  ", "      ", "Press ", "    ██"]
+        );
+    }
+
+    #[test]
+    fn level_up_detection() {
+        assert_eq!(level_up(None, 3), None);
+        assert_eq!(level_up(Some(3), 3), None);
+        assert_eq!(level_up(Some(3), 4), Some(4));
+        // undoing a clear lowers the level
+        assert_eq!(level_up(Some(4), 3), None);
+
+        let mut config = game::GameConfig::new((22, 10)).seed(1);
+        config.start_level = 5;
+        let mut g = game::Game::with_config(config).unwrap();
+        let mut flash = LevelUpFlash::default();
+        g.handle_event(game::Event::Start);
+        flash.update(&g);
+        assert_eq!(flash.level(), None);
+
+        flash.last = Some(4);
+        flash.update(&g);
+        assert_eq!(flash.level(), Some(5));
+        for _ in 1..LEVEL_UP_FRAMES {
+            flash.update(&g);
+            assert_eq!(flash.level(), Some(5));
+        }
+        flash.update(&g);
+        assert_eq!(flash.level(), None);
+
+        let widget = LevelWidget::new(&g).level_up(&LevelUpFlash {
+            last: Some(5),
+            shown: Some((5, 1)),
+        });
+        let area = widget.expected_area();
+        let mut buf = Buffer::empty(area);
+        widget.render(area, &mut buf);
+        assert!(buffer_lines(&buf, area)
+            .iter()
+            .any(|l| l.contains("LEVEL 5")));
+
+        g.handle_event(game::Event::Pause);
+        flash.update(&g);
+        assert_eq!(flash.last, None);
+    }
+
+    #[test]
+    fn trail() {
+        let config = game::GameConfig::new((8, 4)).piece_sequence(vec![0]);
+        let mut g = game::Game::with_config(config).unwrap();
+        let mut trail = Trail::default();
+        let theme = Theme {
+            show_trail: true,
+            ..Theme::default()
+        };
+        let render = |g: &game::Game, trail: &Trail| {
+            let widget = LevelWidget::new(g).theme(theme).trail(trail);
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+
+        g.handle_event(game::Event::Start);
+        g.level[(0, 0)] = true;
+        trail.update(&g);
+        for _ in 0..2 {
+            g.handle_event(game::Event::SoftDrop);
+            trail.update(&g);
+        }
+        let lines = render(&g, &trail);
+        assert_eq!(
+            lines,
+            vec![
+                "  ▒▒▒▒  ",
+                "  ▓▓▓▓  ",
+                "  ████  ",
+                "  ████  ",
+                "        ",
+                "        ",
+                "        ",
+                "██      ",
+            ]
+        );
+
+        // the old positions are shown with the newest first, but never over a
+        // cell.
+        g.handle_event(game::Event::Left);
+        trail.update(&g);
+        assert_eq!(render(&g, &trail)[4], "        ");
+        g.handle_event(game::Event::SoftDrop);
+        trail.update(&g);
+        assert_eq!(
+            render(&g, &trail)[..4],
+            ["  ░░░░  ", "  ▒▒▒▒  ", "▓▓▓▓▒▒  ", "████    "]
+        );
+
+        g.handle_event(game::Event::HardDrop);
+        trail.update(&g);
+        assert!(trail.positions.is_empty());
+        assert!(!render(&g, &trail).concat().contains(TRAIL_SYMBOLS[2]));
+    }
+
+    #[test]
+    fn half_blocks() {
+        // from the bottom: "##.", "#..", "...", "..#", ".#."
+        let mut g = game::Game::new((5, 3));
+        g.level[(0, 0)] = true;
+        g.level[(0, 1)] = true;
+        g.level[(1, 0)] = true;
+        g.level[(3, 2)] = true;
+        g.level[(4, 1)] = true;
+
+        let theme = Theme {
+            cell_size: CellSize::HalfBlock,
+            press_start: Overlay {
+                text: "",
+                color: Color::White,
+            },
+            ..Theme::default()
+        };
+        let render = |theme| {
+            let widget = LevelWidget::new(&g).theme(theme);
+            assert_eq!(widget.expected_area(), Rect::new(0, 0, 5, 5));
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buf
+        };
+        let buf = render(theme);
+        assert_eq!(buffer_lines(&buf, buf.area), vec![" ▀▄", "▄  ", "▀▀ "]);
+        assert_eq!(buf.get(2, 0).fg, Color::White);
+        assert_eq!(buf.get(2, 2).fg, Color::Reset);
+
+        let buf = render(Theme {
+            orientation: DisplayOrientation::FlippedVertical,
+            ..theme
+        });
+        assert_eq!(buffer_lines(&buf, buf.area), vec!["█▀ ", "  ▄", " ▀ "]);
+
+        // the hidden cells aren't drawn.
+        let config = game::GameConfig::new((5, 3)).invisible_after_ticks(5);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.level[(0, 0)] = true;
+        let widget = LevelWidget::new(&g).theme(theme);
+        let mut buf = Buffer::empty(Rect::new(0, 0, 3, 3));
+        widget.render_level(buf.area, &mut buf);
+        assert_eq!(buffer_lines(&buf, buf.area), vec!["   ", "   ", "   "]);
+    }
+
+    #[test]
+    fn level_grid() {
+        let mut g = game::Game::new((4, 3));
+        g.level[(0, 0)] = true;
+        g.level[(0, 1)] = true;
+        g.level[(1, 0)] = true;
+
+        // hide the overlay of the start screen.
+        let plain = Theme {
+            press_start: Overlay {
+                text: "",
+                color: Color::White,
+            },
+            ..Theme::default()
+        };
+        let render = |theme| {
+            let widget = LevelWidget::new(&g).theme(theme);
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        assert_eq!(render(plain), vec!["      ", "      ", "██    ", "████  "]);
+        assert_eq!(
+            render(Theme {
+                show_grid: true,
+                ..plain
+            }),
+            vec!["· · · ", "· · · ", "██· · ", "████· "]
+        );
+    }
+
+    #[test]
+    fn hide_board_on_pause() {
+        let config = game::GameConfig::new((4, 3)).piece_sequence(vec![6]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        g.level[(0, 0)] = true;
+        g.handle_event(game::Event::Pause);
+        let render = |theme| {
+            let widget = LevelWidget::new(&g).theme(theme);
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        let shown = render(Theme::default());
+        assert_eq!(shown, vec!["  ██  ", "██████", "Paused", "██    "]);
+        let hidden = render(Theme {
+            hide_board_on_pause: true,
+            ..Theme::default()
+        });
+        assert_eq!(hidden, vec!["      ", "      ", "Paused", "      "]);
+    }
+
+    #[test]
+    fn patterns() {
+        let config = game::GameConfig::new((4, 3)).piece_sequence(vec![6]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        g.level[(0, 0)] = true;
+        let render = |g: &game::Game, theme| {
+            let widget = LevelWidget::new(g).theme(theme);
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        let plain = render(&g, Theme::default());
+        assert_eq!(plain, vec!["  ██  ", "██████", "      ", "██    "]);
+        // the locked cell has no kind, it stays plain.
+        let patterned = render(
+            &g,
+            Theme {
+                patterns: true,
+                ..Theme::default()
+            },
+        );
+        assert_eq!(patterned, vec!["  ▙▟  ", "▙▟▙▟▙▟", "      ", "██    "]);
+        assert_eq!(cell_symbols(None, true), ["█", "█"]);
+
+        // the pattern stays when the shape is rotated.
+        g.handle_event(game::Event::Rotate);
+        let rotated = render(
+            &g,
+            Theme {
+                patterns: true,
+                ..Theme::default()
+            },
+        );
+        assert_eq!(rotated, vec!["  ▙▟  ", "  ▙▟▙▟", "  ▙▟  ", "██    "]);
+    }
+
+    #[test]
+    fn overlay_text() {
+        let theme = Theme::default();
+        let mut g = game::Game::new((10, 10));
+        assert_eq!(theme.overlay(&g).unwrap().text, "Press Enter");
+        g.handle_event(game::Event::Start);
+        assert_eq!(theme.overlay(&g), None);
+        g.handle_event(game::Event::Pause);
+        assert_eq!(theme.overlay(&g).unwrap().text, "Paused");
+
+        g.handle_event(game::Event::Start);
+        while g.state != game::State::End {
+            g.tick();
+        }
+        assert_eq!(theme.overlay(&g).unwrap().text, "GAME OVER");
+
+        let config = game::GameConfig::new((10, 10))
+            .mode(game::GameMode::Sprint { lines: 1 })
+            .initial_board(vec![vec![true; 10]]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        while g.state != game::State::End {
+            g.tick();
+        }
+        let overlay = theme.overlay(&g).unwrap();
+        assert_eq!(overlay.text, "SPRINT COMPLETE");
+        assert_eq!(overlay.color, Color::Yellow);
+    }
+
+    #[test]
+    fn next_widget() {
+        let config = game::GameConfig::new((22, 16)).preview_count(2).seed(1);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+
+        let widget = NextWidget::new(&g);
+        let area = widget.expected_area();
+        let mut buf = Buffer::empty(area);
+        widget.render(area, &mut buf);
+
+        let inner = Rect::new(1, 1, area.width - 2, area.height - 2);
+        let lines = buffer_lines(&buf, inner);
+        let next = g.next_shapes(2);
+        assert_eq!(next.len(), 2);
+        for (i, shape) in next.into_iter().enumerate() {
+            let top = i * NEXT_SLOT_HEIGHT as usize;
+            for (r, row) in shape.to_string().lines().enumerate() {
+                let expected = row.replace('#', "██").replace('.', "  ");
+                assert_eq!(lines[top + r].trim_end(), expected.trim_end());
+            }
+        }
+    }
+
+    #[test]
+    fn menu_selection() {
+        let mut state = ListState::default();
+        move_selection(&mut state, 3, 1);
+        assert_eq!(state.selected(), Some(1));
+        move_selection(&mut state, 3, 1);
+        move_selection(&mut state, 3, 1);
+        assert_eq!(state.selected(), Some(2));
+        move_selection(&mut state, 3, -5);
+        assert_eq!(state.selected(), Some(0));
+    }
+
+    #[test]
+    fn pause_only_playing_game() {
+        let mut g = game::Game::new((22, 16));
+        let mut menu = ListState::default();
+        pause(&mut g, &mut menu);
+        assert_eq!(g.state, game::State::Init);
+        assert_eq!(menu.selected(), None);
+
+        g.handle_event(game::Event::Start);
+        pause(&mut g, &mut menu);
+        assert_eq!(g.state, game::State::Paused);
+        assert_eq!(menu.selected(), Some(0));
+
+        // losing the focus again keeps the game paused and the selection.
+        move_selection(&mut menu, PauseItem::ALL.len(), 1);
+        pause(&mut g, &mut menu);
+        assert_eq!(g.state, game::State::Paused);
+        assert_eq!(menu.selected(), Some(1));
+    }
+
+    #[test]
+    fn buffered_inputs() {
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        let pending = RefCell::new(VecDeque::from(vec![
+            key(KeyCode::Left),
+            Event::FocusGained,
+            key(KeyCode::Left),
+            key(KeyCode::Left),
+        ]));
+        let mut waits = Vec::new();
+        let events = read_events(
+            Duration::from_millis(100),
+            |wait| {
+                waits.push(wait);
+                Ok(!pending.borrow().is_empty())
+            },
+            || Ok(pending.borrow_mut().pop_front().unwrap()),
+        )
+        .unwrap();
+        assert_eq!(events.len(), 4);
+        // only the first poll waits.
+        assert_eq!(waits[0], Duration::from_millis(100));
+        assert!(waits[1..].iter().all(|wait| wait.is_zero()));
+
+        let config = game::GameConfig::new((10, 10)).piece_sequence(vec![0]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        let before = g.active_piece().unwrap().1;
+        let mut menu = ListState::default();
+        for e in events {
+            if let Event::Key(key) = e {
+                assert!(handle_key(
+                    &mut g,
+                    &mut menu,
+                    key,
+                    &KeyBindings::default(),
+                    false
+                ));
+            }
+        }
+        assert_eq!(g.active_piece().unwrap().1, (before.0, before.1 - 3));
+        assert!(!handle_key(
+            &mut g,
+            &mut menu,
+            KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE),
+            &KeyBindings::default(),
+            false
+        ));
+    }
+
+    #[test]
+    fn rotated_ghost() {
+        let config = game::GameConfig::new((8, 4)).piece_sequence(vec![3]);
+        let mut g = game::Game::with_config(config).unwrap();
+        assert_eq!(ghost(&g, false), None);
+        g.handle_event(game::Event::Start);
+        let (shape, pos) = g.active_piece().map(|(s, p)| (s.clone(), p)).unwrap();
+
+        let (flat, at) = ghost(&g, false).unwrap();
+        assert_eq!(flat, shape);
+        assert_eq!(at, (0, pos.1));
+        let (rotated, at) = ghost(&g, true).unwrap();
+        assert_eq!(rotated.to_string(), "#.\n#.\n##");
+        assert_eq!(at.0, 0);
+        // the game is left as it is.
+        assert_eq!(g.active_piece(), Some((&shape, pos)));
+
+        let theme = Theme {
+            ghost: GhostStyle::Outline,
+            ..Theme::default()
+        };
+        let render = |rotate_preview| {
+            let widget = LevelWidget::new(&g)
+                .theme(theme)
+                .rotate_preview(rotate_preview);
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        assert_eq!(render(false)[6..], ["    []  ", "[][][]  "]);
+        assert_eq!(render(true)[5..], ["  []    ", "  []    ", "  [][]  "]);
+
+        // there is no room to rotate the shape, so its own ghost is shown.
+        for col in 0..4 {
+            g.level[(5, col)] = true;
+        }
+        assert_eq!(g.rotated_piece(), None);
+        assert_eq!(ghost(&g, true), Some((shape, pos)));
+    }
+
+    #[test]
+    fn terminal_guard() {
+        let restored = RefCell::new(0);
+        {
+            let _guard = TerminalGuard {
+                restore: || *restored.borrow_mut() += 1,
+            };
+            assert_eq!(*restored.borrow(), 0);
+        }
+        assert_eq!(*restored.borrow(), 1);
+
+        // it's restored when a panic unwinds the stack too.
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _guard = TerminalGuard {
+                restore: || *restored.borrow_mut() += 1,
+            };
+            panic!("in the game");
+        }));
+        assert!(result.is_err());
+        assert_eq!(*restored.borrow(), 2);
+    }
+
+    #[test]
+    fn hard_drop_confirmation() {
+        let first = Instant::now();
+        assert!(!confirms(None, first));
+        assert!(confirms(Some(first), first + Duration::from_millis(150)));
+        assert!(confirms(
+            Some(first),
+            first + CONFIRM_WINDOW - Duration::from_millis(1)
+        ));
+        // too slow.
+        assert!(!confirms(Some(first), first + CONFIRM_WINDOW));
+        assert!(!confirms(Some(first), first + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn soft_drop_repeat() {
+        let ms = Duration::from_millis;
+        let repeat = AutoRepeat {
+            delay: ms(150),
+            interval: ms(30),
+        };
+        assert_eq!(repeat.repeats(ms(0)), 0);
+        assert_eq!(repeat.repeats(ms(149)), 0);
+        assert_eq!(repeat.repeats(ms(150)), 1);
+        assert_eq!(repeat.repeats(ms(179)), 1);
+        assert_eq!(repeat.repeats(ms(180)), 2);
+        assert_eq!(repeat.repeats(ms(450)), 11);
+        assert_eq!(repeat.next_repeat(ms(0)), ms(150));
+        assert_eq!(repeat.next_repeat(ms(150)), ms(180));
+        assert_eq!(repeat.next_repeat(ms(200)), ms(210));
+
+        // every frame soft drops by as many rows as repeats were due.
+        let config = game::GameConfig::new((22, 10)).piece_sequence(vec![1]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        let mut done = 0;
+        for held in [100, 160, 230, 240] {
+            let due = repeat.repeats(ms(held));
+            for _ in done..due {
+                g.handle_event(game::Event::SoftDrop);
+            }
+            done = due;
+        }
+        assert_eq!(done, 4);
+        assert_eq!(g.active_piece().unwrap().1 .0, 21 - 4);
+        assert_eq!(g.stats().score, 4);
+
+        let instant = AutoRepeat {
+            delay: Duration::ZERO,
+            interval: Duration::ZERO,
+        };
+        assert_eq!(instant.repeats(ms(5)), 6);
+    }
+
+    #[test]
+    fn palettes() {
+        let mut index = 0;
+        let mut names = Vec::new();
+        for _ in 0..PALETTES.len() + 1 {
+            names.push(PALETTES[index].name);
+            index = next_palette(index);
+        }
+        assert_eq!(
+            names,
+            [
+                "classic",
+                "monochrome",
+                "high contrast",
+                "colorblind",
+                "classic"
+            ]
+        );
+        assert_eq!(next_palette(PALETTES.len() - 1), 0);
+
+        // the palette colors the cells, the border and the overlay.
+        let mut g = game::Game::new((4, 3));
+        g.level[(0, 0)] = true;
+        let theme = Theme {
+            palette: Palette::COLORBLIND,
+            ..Theme::default()
+        };
+        let mut buf = Buffer::empty(Rect::new(0, 0, 8, 6));
+        LevelWidget::new(&g).theme(theme).render(buf.area, &mut buf);
+        assert_eq!(buf.get(1, 4).fg, Palette::COLORBLIND.cell);
+        assert_eq!(buf.get(0, 0).fg, Palette::COLORBLIND.border);
+        assert_eq!(theme.overlay(&g).unwrap().color, Color::Rgb(0, 114, 178));
+        assert_eq!(Theme::default().overlay(&g).unwrap().color, Color::White);
+
+        // the colorblind palette tells the kinds of the shapes apart, the
+        // falling one and the next ones.
+        let pieces = Palette::COLORBLIND.pieces.unwrap();
+        for (i, color) in pieces.iter().enumerate() {
+            assert!(!pieces[i + 1..].contains(color));
+        }
+        assert_eq!(
+            Palette::CLASSIC.piece(Some(game::PieceKind::T)),
+            Color::Reset
+        );
+        assert_eq!(Palette::COLORBLIND.piece(None), Palette::COLORBLIND.cell);
+
+        let config = game::GameConfig::new((22, 10))
+            .preview_count(1)
+            .first_piece(game::PieceKind::T)
+            .seed(1);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        g.level[(0, 0)] = true;
+        let mut buf = Buffer::empty(Rect::new(0, 0, 22, 24));
+        LevelWidget::new(&g).theme(theme).render(buf.area, &mut buf);
+        let t = Palette::COLORBLIND.piece(Some(game::PieceKind::T));
+        assert!(buf.content.iter().any(|cell| cell.fg == t));
+        assert_eq!(buf.get(1, 22).fg, Palette::COLORBLIND.cell);
+
+        let next = g.kind_of(g.next_shapes(1)[0]);
+        let widget = NextWidget::new(&g).palette(Palette::COLORBLIND);
+        let area = widget.expected_area();
+        let mut buf = Buffer::empty(area);
+        widget.render(area, &mut buf);
+        let color = Palette::COLORBLIND.piece(next);
+        assert!(buf.content.iter().any(|cell| cell.fg == color));
+    }
+
+    #[test]
+    fn clipped_level() {
+        assert_eq!(clip_start(20, 8, None), 0);
+        assert_eq!(clip_start(20, 8, Some((0, 1))), 0);
+        assert_eq!(clip_start(20, 8, Some((6, 7))), 0);
+        assert_eq!(clip_start(20, 8, Some((7, 8))), 1);
+        assert_eq!(clip_start(20, 8, Some((18, 19))), 12);
+        assert_eq!(clip_start(20, 30, Some((18, 19))), 0);
+
+        let mut g = game::Game::with_config(game::GameConfig::new((10, 4)).seed(0)).unwrap();
+        g.handle_event(game::Event::Start);
+        g.level[(0, 0)] = true;
+        let render = |g: &game::Game| {
+            let mut buf = Buffer::empty(Rect::new(0, 0, 10, 6));
+            LevelWidget::new(g).render(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        // the top of the level is drawn while the shape is up there.
+        assert_eq!(
+            render(&g),
+            vec![
+                "┌Tetris──┐",
+                "│████    │",
+                "│  ████  │",
+                "│        │",
+                "│        │",
+                "└────────┘"
+            ]
+        );
+        for _ in 0..6 {
+            g.handle_event(game::Event::SoftDrop);
+        }
+        // then the rows down to the shape, the bottom ones aren't drawn.
+        assert_eq!(
+            render(&g),
+            vec![
+                "┌Tetris──┐",
+                "│        │",
+                "│        │",
+                "│████    │",
+                "│  ████  │",
+                "└────────┘"
+            ]
+        );
+    }
+
+    #[test]
+    fn hold() {
+        let start = Instant::now();
+        assert!(!is_held(None, start));
+        assert!(is_held(Some(start), start));
+        assert!(is_held(Some(start), start + HOLD_TIMEOUT / 2));
+        assert!(!is_held(Some(start), start + HOLD_TIMEOUT));
+    }
+
+    #[test]
+    fn idle() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let timeout = Some(secs(30));
+        assert!(!is_idle(start, start, timeout));
+        assert!(!is_idle(start, start + secs(29), timeout));
+        assert!(is_idle(start, start + secs(30), timeout));
+        assert!(!is_idle(start, start + secs(3600), None));
+        // an input after `now` isn't idle.
+        assert!(!is_idle(start + secs(60), start, timeout));
+    }
+
+    #[test]
+    fn time_format() {
+        assert_eq!(format_time(Duration::ZERO), "00:00.00");
+        assert_eq!(format_time(Duration::from_millis(61_234)), "01:01.23");
+        assert_eq!(format_time(Duration::from_secs(3600)), "60:00.00");
+    }
+
+    #[test]
+    fn clock() {
+        let config = game::GameConfig::new((22, 16)).mode(game::GameMode::Sprint { lines: 40 });
+        let mut g = game::Game::with_config(config).unwrap();
+        let tick = g.gravity_interval();
+        let half = tick / 2;
+        assert_eq!(clock_time(&g, half), Duration::ZERO);
+
+        g.handle_event(game::Event::Start);
+        assert_eq!(clock_time(&g, half), half);
+        assert_eq!(clock_time(&g, tick * 3), tick);
+        g.tick();
+        g.tick();
+        assert_eq!(clock_time(&g, half), tick * 2 + half);
+
+        g.handle_event(game::Event::Pause);
+        g.tick();
+        assert_eq!(clock_time(&g, half), tick * 2);
+
+        g.handle_event(game::Event::Start);
+        g.tick();
+        let mut buf = Buffer::empty(Rect::new(0, 0, 10, 3));
+        ClockWidget::new(&g)
+            .since_tick(Duration::from_millis(10))
+            .render(buf.area, &mut buf);
+        let expected = format_time(tick * 3 + Duration::from_millis(10));
+        assert_eq!(buffer_lines(&buf, buf.area)[1], format!("│{}│", expected));
+    }
+
+    #[test]
+    fn pps() {
+        assert_eq!(pieces_per_second(0, Duration::ZERO), 0.0);
+        assert_eq!(pieces_per_second(10, Duration::ZERO), 0.0);
+        assert_eq!(pieces_per_second(10, Duration::from_secs(4)), 2.5);
+        assert_eq!(pieces_per_second(3, Duration::from_millis(1500)), 2.0);
+
+        let mut g = game::Game::with_config(game::GameConfig::new((22, 16)).seed(2)).unwrap();
+        g.handle_event(game::Event::Start);
+        let mut buf = Buffer::empty(Rect::new(0, 0, 16, 6));
+        StatsWidget::new(&g).render(buf.area, &mut buf);
+        assert_eq!(
+            buffer_lines(&buf, buf.area),
+            vec![
+                "┌Stats─────────┐",
+                "│Pieces: 0     │",
+                "│PPS:    0.00  │",
+                "│Lines:  0     │",
+                "│Level:  0     │",
+                "└──────────────┘"
+            ]
+        );
+        g.handle_event(game::Event::HardDrop);
+        g.handle_event(game::Event::HardDrop);
+        let mut buf = Buffer::empty(Rect::new(0, 0, 16, 6));
+        StatsWidget::new(&g)
+            .since_tick(g.gravity_interval() * 5)
+            .render(buf.area, &mut buf);
+        let pps = pieces_per_second(2, g.gravity_interval());
+        assert_eq!(
+            buffer_lines(&buf, buf.area)[2],
+            format!("│PPS:    {:<6.2}│", pps)
+        );
+    }
+
+    #[test]
+    fn sprint_pace() {
+        let secs = Duration::from_secs;
+        assert_eq!(estimated_finish(Duration::ZERO, 0, 40), None);
+        assert_eq!(estimated_finish(Duration::ZERO, 4, 40), None);
+        assert_eq!(estimated_finish(secs(10), 0, 40), None);
+        assert_eq!(estimated_finish(secs(10), 4, 40), Some(secs(100)));
+        assert_eq!(estimated_finish(secs(90), 40, 40), Some(secs(90)));
+
+        let render = |g: &game::Game| {
+            let widget = SprintWidget::new(g);
+            let mut buf = Buffer::empty(widget.expected_area());
+            widget.render(buf.area, &mut buf);
+            buffer_lines(&buf, buf.area)
+        };
+        let config = game::GameConfig::new((10, 4))
+            .mode(game::GameMode::Sprint { lines: 2 })
+            .base_tick(Duration::from_millis(500))
+            .gravity(game::GravityCurve::Constant)
+            .shapes(vec!["####".parse().unwrap()]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        assert_eq!(
+            render(&g)[1..4],
+            [
+                "│Lines: 0/2     │",
+                "│Time:  00:00.00│",
+                "│Pace:  --:--.--│"
+            ]
+        );
+
+        g.tick();
+        g.handle_event(game::Event::HardDrop);
+        assert_eq!(g.lines_remaining(), Some(1));
+        assert_eq!(
+            render(&g)[1..4],
+            [
+                "│Lines: 1/2     │",
+                "│Time:  00:00.50│",
+                "│Pace:  00:01.00│"
+            ]
+        );
+
+        g.tick();
+        g.tick();
+        g.handle_event(game::Event::HardDrop);
+        assert_eq!(
+            render(&g)[1..4],
+            [
+                "│Lines: 2/2     │",
+                "│Time:  00:01.50│",
+                "│FINISHED       │"
+            ]
+        );
+        // the time stops once it's finished
+        g.tick();
+        assert_eq!(render(&g)[2], "│Time:  00:01.50│");
+    }
+
+    #[test]
+    fn sprint_status() {
+        let config = game::GameConfig::new((22, 16)).mode(game::GameMode::Sprint { lines: 40 });
+        let g = game::Game::with_config(config).unwrap();
+        let lines = status_lines(&g);
+        assert_eq!(lines[1], Spans::from("Score: 0"));
+        assert_eq!(lines[2], Spans::from("Lines: 0/40"));
+        assert_eq!(lines[3], Spans::from("Time:  00:00.00"));
+    }
+
+    #[test]
+    fn seed_input() {
+        assert_eq!(parse_seed("42"), Some(42));
+        assert_eq!(parse_seed(" 7 "), Some(7));
+        assert_eq!(parse_seed(""), None);
+        assert_eq!(parse_seed("abc"), None);
+        assert_eq!(parse_seed("-1"), None);
+
+        let config = game::GameConfig::new((22, 16)).seed(42);
+        let mut g = game::Game::with_config(config).unwrap();
+        assert!(status_lines(&g).contains(&Spans::from("Seed:  42")));
+        g.handle_event(game::Event::Start);
+        assert!(!status_lines(&g).contains(&Spans::from("Seed:  42")));
+    }
+
+    #[test]
+    fn piece_above_level() {
+        let config = game::GameConfig::new((10, 10)).piece_sequence(vec![0]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        g.fill_rows(&[0, 1, 2, 3, 4, 5, 6], &[0]);
+        let mut trail = Trail::default();
+        trail.update(&g);
+        g.hard_drop();
+        // the next square is pushed up out of the top and the game is over.
+        assert_eq!(g.state, game::State::End);
+        assert_eq!(g.active_piece().unwrap().1, (9, 4));
+
+        for cell_size in [CellSize::Wide, CellSize::HalfBlock] {
+            for orientation in [
+                DisplayOrientation::Normal,
+                DisplayOrientation::FlippedHorizontal,
+                DisplayOrientation::FlippedVertical,
+            ] {
+                let theme = Theme {
+                    cell_size,
+                    orientation,
+                    ghost: GhostStyle::DimFill,
+                    show_trail: true,
+                    show_spawn_preview: true,
+                    patterns: true,
+                    ..Theme::default()
+                };
+                let widget = LevelWidget::new(&g).theme(theme).trail(&trail);
+                let area = widget.expected_area();
+                let mut buf = Buffer::empty(area);
+                widget.render(area, &mut buf);
+                // too small, only the rows around the shape are drawn.
+                let widget = LevelWidget::new(&g).theme(theme).trail(&trail);
+                let mut buf = Buffer::empty(Rect { height: 4, ..area });
+                widget.render(buf.area, &mut buf);
+            }
+        }
+
+        // only the cells in the level are drawn.
+        let widget = LevelWidget::new(&g);
+        let (width, height) = widget.level_size();
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+        widget.render_level(buf.area, &mut buf);
+        assert_eq!(buffer_lines(&buf, buf.area)[0], "        ████        ");
+        assert_eq!(
+            g.render_ascii().lines().take(2).collect::<Vec<_>>(),
+            ["....##....", "....##...."]
+        );
+    }
+
+    #[test]
+    fn mirror_controls() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let left = key(KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(
+            game_event(left, &KeyBindings::default()),
+            Some(game::Event::Left)
+        );
+        assert_eq!(
+            mirrored(game_event(left, &KeyBindings::default()).unwrap()),
+            game::Event::Right
+        );
+        let wall = game_event(
+            key(KeyCode::Right, KeyModifiers::SHIFT),
+            &KeyBindings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            mirrored(wall),
+            game::Event::MoveToWall(game::Direction::Left)
+        );
+        assert_eq!(mirrored(game::Event::Rotate), game::Event::Rotate);
+
+        let config = game::GameConfig::new((10, 10)).piece_sequence(vec![0]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        let before = g.active_piece().unwrap().1;
+        let mut menu = ListState::default();
+        assert!(handle_key(
+            &mut g,
+            &mut menu,
+            left,
+            &KeyBindings::default(),
+            true
+        ));
+        assert_eq!(g.active_piece().unwrap().1, (before.0, before.1 + 1));
+        assert!(handle_key(
+            &mut g,
+            &mut menu,
+            left,
+            &KeyBindings::default(),
+            false
+        ));
+        assert_eq!(g.active_piece().unwrap().1, before);
+    }
+
+    #[test]
+    fn combo_meter() {
+        assert_eq!(combo_ratio(-1), 0.0);
+        assert_eq!(combo_ratio(0), 0.0);
+        assert_eq!(combo_ratio(1), 0.1);
+        assert_eq!(combo_ratio(5), 0.5);
+        assert_eq!(combo_ratio(COMBO_METER_MAX), 1.0);
+        assert_eq!(combo_ratio(25), 1.0);
+
+        let g = game::Game::new((10, 10));
+        let widget = ComboWidget::new(&g);
+        let mut buf = Buffer::empty(widget.expected_area());
+        widget.render(buf.area, &mut buf);
+        assert_eq!(
+            buffer_lines(&buf, buf.area),
+            ["┌Combo─────────┐", "│              │", "└──────────────┘"]
+        );
+    }
+
+    #[test]
+    fn ghost_styles() {
+        let config = game::GameConfig::new((6, 4)).piece_sequence(vec![6]);
+        let mut g = game::Game::with_config(config).unwrap();
+        g.handle_event(game::Event::Start);
+        g.level[(0, 0)] = true;
+        let render = |ghost| {
+            let widget = LevelWidget::new(&g).theme(Theme {
+                ghost,
+                ..Theme::default()
+            });
+            let (width, height) = widget.level_size();
+            let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+            widget.render_level(buf.area, &mut buf);
+            (buffer_lines(&buf, buf.area), buf)
+        };
+
+        let (outline, _) = render(GhostStyle::Outline);
+        assert_eq!(
+            outline,
+            [
+                "  ██    ",
+                "██████  ",
+                "        ",
+                "  []    ",
+                "[][][]  ",
+                "██      "
+            ]
+        );
+        let (fill, buf) = render(GhostStyle::DimFill);
+        assert_eq!(fill[3..], ["  ██    ", "██████  ", "██      "]);
+        assert_eq!(buf.get(2, 3).fg, Color::DarkGray);
+        assert!(buf.get(2, 3).modifier.contains(Modifier::DIM));
+        let (off, _) = render(GhostStyle::Off);
+        assert_eq!(off[..2], outline[..2]);
+        assert_eq!(off[3..], ["        ", "        ", "██      "]);
+    }
+
+    #[test]
+    fn title_and_status_bar() {
+        let config = game::GameConfig::new((10, 10)).piece_sequence(vec![1]);
+        let mut g = game::Game::with_config(config.clone()).unwrap();
+        assert_eq!(window_title(&g), "tetris - Score: 0");
+        assert_eq!(status_bar(&g), "Level 0 | Lines 0 | Marathon");
+
+        g.handle_event(game::Event::Start);
+        g.fill_rows(&[0], &[3, 4, 5, 6]);
+        g.hard_drop();
+        assert_eq!(window_title(&g), "tetris - Score: 118");
+        assert_eq!(status_bar(&g), "Level 0 | Lines 1 | Marathon");
+
+        let sprint = config.mode(game::GameMode::Sprint { lines: 40 });
+        let g = game::Game::with_config(sprint).unwrap();
+        assert_eq!(status_bar(&g), "Level 0 | Lines 0/40 | Sprint");
+    }
+
+    #[test]
+    fn scrub_info() {
+        let config = game::GameConfig::new((10, 10)).piece_sequence(vec![0]);
+        let events = vec![vec![game::Event::Start], vec![game::Event::HardDrop]];
+        let mut scrubber = game::Scrubber::new(game::Replay::new(config, events).unwrap());
+        assert_eq!(
+            scrub_lines(&scrubber),
+            [Spans::from("Tick:  0/2"), Spans::from("Score: 0")]
+        );
+        scrubber.seek(2);
+        assert_eq!(
+            scrub_lines(&scrubber),
+            [Spans::from("Tick:  2/2"), Spans::from("Score: 14")]
+        );
+    }
+
+    #[test]
+    fn board_too_large() {
+        let config = game::GameConfig::new((4, 40_000));
+        let g = game::Game::with_config(config).unwrap();
+        let level = LevelWidget::new(&g);
+        assert_eq!(level.too_large(Rect::new(0, 0, 80, 24)), Some((80_002, 6)));
+        let expected = level.expected_area();
+        assert_eq!((expected.width, expected.height), (u16::MAX, 6));
+
+        let area = Rect::new(0, 0, 50, 5);
+        let mut buf = Buffer::empty(area);
+        LevelWidget::new(&g).render(area, &mut buf);
+        assert_eq!(
+            buffer_lines(&buf, area)[2],
+            "   Board too large for terminal: needs 80002x6    "
+        );
+
+        // a level higher than the area is clipped, not too large.
+        let g = game::Game::with_config(game::GameConfig::new((40, 10))).unwrap();
+        let level = LevelWidget::new(&g);
+        assert_eq!(level.too_large(Rect::new(0, 0, 22, 10)), None);
+        assert_eq!(level.too_large(Rect::new(0, 0, 21, 10)), Some((22, 42)));
+        assert_eq!(level.too_large(Rect::new(0, 0, 22, 2)), Some((22, 42)));
+    }
+
+    #[test]
+    fn custom_keys() {
+        let keys = KeyBindings {
+            left: settings::Key(KeyCode::Char('a')),
+            quit: settings::Key(KeyCode::Esc),
+            ..KeyBindings::default()
+        };
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let a = key(KeyCode::Char('a'), KeyModifiers::NONE);
+        assert_eq!(game_event(a, &keys), Some(game::Event::Left));
+        // the terminals report a letter with shift in upper case.
+        assert_eq!(
+            game_event(key(KeyCode::Char('A'), KeyModifiers::SHIFT), &keys),
+            Some(game::Event::MoveToWall(game::Direction::Left))
+        );
+        assert_eq!(
+            game_event(key(KeyCode::Char('A'), KeyModifiers::NONE), &keys),
+            Some(game::Event::Left)
+        );
+        assert_eq!(
+            game_event(key(KeyCode::Left, KeyModifiers::NONE), &keys),
+            None
+        );
+
+        let mut g = game::Game::new((10, 10));
+        let mut menu = ListState::default();
+        let q = key(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert!(handle_key(&mut g, &mut menu, q, &keys, false));
+        let esc = key(KeyCode::Esc, KeyModifiers::NONE);
+        assert!(!handle_key(&mut g, &mut menu, esc, &keys, false));
+    }
+
+    #[test]
+    fn validate_options() {
+        assert_eq!(Options::default().validate(), Ok(()));
+        let options = Options {
+            mode: game::GameMode::Survival {
+                interval: Duration::ZERO,
+                gap: game::GarbageGap::Random,
+            },
+            ..Options::default()
+        };
+        assert!(options.validate().is_err());
+    }
 }
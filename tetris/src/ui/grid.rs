@@ -0,0 +1,103 @@
+//! Draw several games at once in a grid of [LevelWidget]s, e.g. to watch bots
+//! play against each other.
+
+use super::{CellSize, LevelWidget, Theme};
+use crate::game::Game;
+use tui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    widgets::Widget,
+};
+
+/// The smallest height of a row of games, the border and a few rows of the
+/// levels.
+const MIN_HEIGHT: u16 = 6;
+
+/// Return the areas of the games drawn in `area` with `cols` games in a row,
+/// for as many of the first `count` games as there is room for: a row of
+/// games is at least [MIN_HEIGHT] high, the games after the last row which
+/// fits are left out.
+pub fn layout(area: Rect, count: usize, cols: usize) -> Vec<Rect> {
+    let cols = cols.max(1);
+    let rows = count
+        .div_ceil(cols)
+        .min((area.height / MIN_HEIGHT).max(1) as usize);
+    if rows == 0 {
+        return Vec::new();
+    }
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Ratio(1, rows as u32); rows])
+        .split(area)
+        .into_iter()
+        .flat_map(|row| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Ratio(1, cols as u32); cols])
+                .split(row)
+        })
+        .take(count)
+        .collect()
+}
+
+/// Draw `games` into `area` of `buf` with `cols` games in a row, see
+/// [layout]. A level is drawn with [CellSize::HalfBlock] if it doesn't fit
+/// with the wide cells and clipped if it doesn't fit at all. The number of
+/// the games left out is shown in the bottom-right corner.
+pub fn render_games(games: &[&Game], cols: usize, area: Rect, buf: &mut Buffer) {
+    let areas = layout(area, games.len(), cols);
+    for (game, game_area) in games.iter().zip(&areas) {
+        let mut theme = Theme::default();
+        let wide = LevelWidget::new(game).theme(theme).expected_area();
+        if wide.width > game_area.width || wide.height > game_area.height {
+            theme.cell_size = CellSize::HalfBlock;
+        }
+        LevelWidget::new(game).theme(theme).render(*game_area, buf);
+    }
+    if areas.len() < games.len() && area.height > 0 {
+        let more = format!("+{} more", games.len() - areas.len());
+        let x = area.right().saturating_sub(more.len() as u16).max(area.x);
+        buf.set_string(x, area.bottom() - 1, more, Style::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_layout() {
+        let area = Rect::new(0, 0, 40, 30);
+        let areas = layout(area, 5, 2);
+        assert_eq!(
+            areas,
+            vec![
+                Rect::new(0, 0, 20, 10),
+                Rect::new(20, 0, 20, 10),
+                Rect::new(0, 10, 20, 10),
+                Rect::new(20, 10, 20, 10),
+                Rect::new(0, 20, 20, 10),
+            ]
+        );
+        assert_eq!(layout(area, 3, 3).len(), 3);
+        assert_eq!(layout(area, 3, 3)[2], Rect::new(26, 0, 14, 30));
+        assert_eq!(layout(area, 0, 2), vec![]);
+        // only the rows of games at least MIN_HEIGHT high are laid out.
+        assert_eq!(layout(Rect::new(0, 0, 40, 13), 10, 2).len(), 4);
+        assert_eq!(layout(Rect::new(0, 0, 40, 3), 10, 2).len(), 2);
+    }
+
+    #[test]
+    fn render_more_games() {
+        let games: Vec<Game> = (0..5).map(|_| Game::new((10, 10))).collect();
+        let games: Vec<&Game> = games.iter().collect();
+        let mut buf = Buffer::empty(Rect::new(0, 0, 30, 12));
+        render_games(&games, 2, buf.area, &mut buf);
+        let bottom: String = (0..30).map(|x| buf.get(x, 11).symbol.clone()).collect();
+        assert!(bottom.ends_with("+1 more"), "{:?}", bottom);
+        // the levels don't fit with wide cells in 15 columns.
+        assert_eq!(buf.get(0, 0).symbol, "┌");
+        assert_eq!(buf.get(15, 0).symbol, "┌");
+    }
+}
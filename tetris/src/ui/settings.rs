@@ -0,0 +1,475 @@
+//! The settings of the terminal UI kept in a file of the user, see
+//! [Settings::load].
+
+use super::{CellSize, DisplayOrientation, GhostStyle, Options};
+use crate::game::{ConfigError, GameConfig};
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// A key of [KeyBindings]. It's written as the character it types, e.g. `"c"`,
+/// or as one of `left`, `right`, `up`, `down`, `space`, `enter`, `tab`, `esc`
+/// and `backspace`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key(pub KeyCode);
+
+/// The keys with a name, see [Key].
+const KEY_NAMES: [(&str, KeyCode); 9] = [
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("esc", KeyCode::Esc),
+    ("backspace", KeyCode::Backspace),
+];
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if let Some((_, code)) = KEY_NAMES.iter().find(|(name, _)| *name == s) {
+            return Ok(Key(*code));
+        }
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Key(KeyCode::Char(c))),
+            _ => Err(format!("unknown key {:?}", s)),
+        }
+    }
+}
+
+impl Key {
+    /// Return true if `code` is this key. The letters match in either case,
+    /// since the terminals report them in upper case with shift.
+    pub fn matches(self, code: KeyCode) -> bool {
+        match (self.0, code) {
+            (KeyCode::Char(a), KeyCode::Char(b)) => a.to_lowercase().eq(b.to_lowercase()),
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        match KEY_NAMES.iter().find(|(_, code)| *code == key.0) {
+            Some((name, _)) => name.to_string(),
+            None => match key.0 {
+                KeyCode::Char(c) => c.to_string(),
+                code => format!("{:?}", code),
+            },
+        }
+    }
+}
+
+/// The keys the game is played with. Shift with the left or the right key
+/// moves the shape to the wall. The keys of [Options::practice] and the one
+/// changing the palette can't be changed, and no two actions could share a
+/// key, see [KeyBindings::conflict].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub left: Key,
+    pub right: Key,
+    pub rotate: Key,
+    /// Holding it makes the shape fall faster, see [Options::soft_drop_repeat].
+    pub soft_drop: Key,
+    pub hard_drop: Key,
+    pub hold: Key,
+    pub pause: Key,
+    pub quit: Key,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            left: Key(KeyCode::Left),
+            right: Key(KeyCode::Right),
+            rotate: Key(KeyCode::Up),
+            soft_drop: Key(KeyCode::Down),
+            hard_drop: Key(KeyCode::Char(' ')),
+            hold: Key(KeyCode::Char('c')),
+            pause: Key(KeyCode::Char('p')),
+            quit: Key(KeyCode::Char('q')),
+        }
+    }
+}
+
+/// The keys which can't be changed, with the name of what they do.
+const FIXED_KEYS: [(&str, KeyCode); 12] = [
+    ("undo", KeyCode::Char('u')),
+    ("retry", KeyCode::Char('r')),
+    ("palette", KeyCode::Char('t')),
+    ("preview 1", KeyCode::Char('1')),
+    ("preview 2", KeyCode::Char('2')),
+    ("preview 3", KeyCode::Char('3')),
+    ("preview 4", KeyCode::Char('4')),
+    ("preview 5", KeyCode::Char('5')),
+    ("preview 6", KeyCode::Char('6')),
+    ("preview 7", KeyCode::Char('7')),
+    ("preview 8", KeyCode::Char('8')),
+    ("preview 9", KeyCode::Char('9')),
+];
+
+impl KeyBindings {
+    /// Return the names of the first two actions bound to the same key, the
+    /// fields of the bindings or the ones of the keys which can't be changed.
+    /// It's None if every key does one thing.
+    pub fn conflict(&self) -> Option<(&'static str, &'static str)> {
+        let keys = [
+            ("left", self.left),
+            ("right", self.right),
+            ("rotate", self.rotate),
+            ("soft_drop", self.soft_drop),
+            ("hard_drop", self.hard_drop),
+            ("hold", self.hold),
+            ("pause", self.pause),
+            ("quit", self.quit),
+        ];
+        let fixed = FIXED_KEYS.iter().map(|(name, code)| (*name, Key(*code)));
+        let all: Vec<_> = keys.into_iter().chain(fixed).collect();
+        all.iter().enumerate().find_map(|(i, (first, key))| {
+            all[i + 1..]
+                .iter()
+                .find(|(_, other)| key.matches(other.0))
+                .map(|(second, _)| (*first, *second))
+        })
+    }
+}
+
+/// The parts of [Theme](super::Theme) which could be set in [Settings], named
+/// like its fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSettings {
+    pub show_grid: bool,
+    pub show_trail: bool,
+    pub patterns: bool,
+    pub hide_board_on_pause: bool,
+    pub show_spawn_preview: bool,
+    pub orientation: DisplayOrientation,
+    pub cell_size: CellSize,
+}
+
+/// The settings of the terminal UI read from a JSON file, see
+/// [Settings::path]. Every field could be left out of the file, the default
+/// one is used then.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub keys: KeyBindings,
+    pub theme: ThemeSettings,
+    /// The interval of the gravity at the first level in milliseconds, see
+    /// [GameConfig::base_tick]. The one of the difficulty is used if it's
+    /// None.
+    pub base_tick_ms: Option<u64>,
+    /// See [Options::preview_count].
+    pub preview_count: Option<usize>,
+    pub ghost: GhostStyle,
+    /// See [Options::sound].
+    pub sound: bool,
+}
+
+/// Why [Settings] couldn't be loaded.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The file couldn't be read.
+    Read { path: PathBuf, error: io::Error },
+    /// The file isn't valid JSON or has a field of the wrong type.
+    Parse {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    /// The file is valid, but a game can't be played with it.
+    Invalid {
+        path: PathBuf,
+        error: InvalidSettings,
+    },
+}
+
+/// Why the settings of a valid file can't be used, see
+/// [SettingsError::Invalid].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidSettings {
+    /// The config of the game is wrong.
+    Config(ConfigError),
+    /// Two actions are bound to the same key, see [KeyBindings::conflict].
+    KeyConflict {
+        first: &'static str,
+        second: &'static str,
+    },
+}
+
+impl fmt::Display for InvalidSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidSettings::Config(error) => write!(f, "{}", error),
+            InvalidSettings::KeyConflict { first, second } => {
+                write!(f, "{} and {} are bound to the same key", first, second)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Read { path, error } => {
+                write!(f, "can't read {}: {}", path.display(), error)
+            }
+            SettingsError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            SettingsError::Invalid { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl Settings {
+    /// Return the path of the file of the settings: `tetris/settings.json` in
+    /// `$XDG_CONFIG_HOME`, or in `$HOME/.config` if it isn't set. It's None if
+    /// neither is set.
+    pub fn path() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("tetris").join("settings.json"))
+    }
+
+    /// Load the settings from [Settings::path], the default ones are returned
+    /// if there is no such file.
+    pub fn load() -> Result<Settings, SettingsError> {
+        match Settings::path() {
+            Some(path) => Settings::load_from(&path),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    /// Load the settings from the file at `path`, like [Settings::load].
+    pub fn load_from(path: &Path) -> Result<Settings, SettingsError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Settings::parse(&json, path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(error) => Err(SettingsError::Read {
+                path: path.to_owned(),
+                error,
+            }),
+        }
+    }
+
+    /// Parse the settings read from the file at `path`.
+    fn parse(json: &str, path: &Path) -> Result<Settings, SettingsError> {
+        let settings: Settings =
+            serde_json::from_str(json).map_err(|error| SettingsError::Parse {
+                path: path.to_owned(),
+                error,
+            })?;
+        let invalid = |error| SettingsError::Invalid {
+            path: path.to_owned(),
+            error,
+        };
+        settings
+            .apply_config(GameConfig::new((22, 16)))
+            .validate()
+            .map_err(|error| invalid(InvalidSettings::Config(error)))?;
+        if let Some((first, second)) = settings.keys.conflict() {
+            return Err(invalid(InvalidSettings::KeyConflict { first, second }));
+        }
+        Ok(settings)
+    }
+
+    /// Set the options to these settings, except what's already set in
+    /// `options`, e.g. from the command line: a theme switch set in either is
+    /// on, and the ghost, the orientation and the cell size of the settings
+    /// are only used if the options have the default ones.
+    pub fn apply(&self, options: &mut Options) {
+        let (theme, ours) = (&mut options.theme, &self.theme);
+        theme.show_grid |= ours.show_grid;
+        theme.show_trail |= ours.show_trail;
+        theme.patterns |= ours.patterns;
+        theme.hide_board_on_pause |= ours.hide_board_on_pause;
+        theme.show_spawn_preview |= ours.show_spawn_preview;
+        if theme.orientation == DisplayOrientation::default() {
+            theme.orientation = ours.orientation;
+        }
+        if theme.cell_size == CellSize::default() {
+            theme.cell_size = ours.cell_size;
+        }
+        if theme.ghost == GhostStyle::default() {
+            theme.ghost = self.ghost;
+        }
+        options.keys = self.keys;
+        options.base_tick = options
+            .base_tick
+            .or(self.base_tick_ms.map(Duration::from_millis));
+        options.preview_count = options.preview_count.or(self.preview_count);
+        options.sound |= self.sound;
+    }
+
+    /// Return `config` with the settings of the game set. The preview count
+    /// isn't clamped like [GameConfig::preview_count] does, so a wrong one is
+    /// reported by [GameConfig::validate].
+    fn apply_config(&self, mut config: GameConfig) -> GameConfig {
+        if let Some(ms) = self.base_tick_ms {
+            config = config.base_tick(Duration::from_millis(ms));
+        }
+        if let Some(preview_count) = self.preview_count {
+            config.preview_count = preview_count;
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Settings, SettingsError> {
+        Settings::parse(json, Path::new("settings.json"))
+    }
+
+    #[test]
+    fn full_settings() {
+        let json = r#"{
+            "keys": {
+                "left": "a", "right": "d", "rotate": "w", "soft_drop": "s",
+                "hard_drop": "enter", "hold": "tab", "pause": "esc", "quit": "x"
+            },
+            "theme": {
+                "show_grid": true, "show_trail": true, "patterns": true,
+                "hide_board_on_pause": true, "show_spawn_preview": true,
+                "orientation": "FlippedVertical", "cell_size": "HalfBlock"
+            },
+            "base_tick_ms": 300,
+            "preview_count": 5,
+            "ghost": "DimFill",
+            "sound": true
+        }"#;
+        let settings = parse(json).unwrap();
+        assert_eq!(
+            settings.keys,
+            KeyBindings {
+                left: Key(KeyCode::Char('a')),
+                right: Key(KeyCode::Char('d')),
+                rotate: Key(KeyCode::Char('w')),
+                soft_drop: Key(KeyCode::Char('s')),
+                hard_drop: Key(KeyCode::Enter),
+                hold: Key(KeyCode::Tab),
+                pause: Key(KeyCode::Esc),
+                quit: Key(KeyCode::Char('x')),
+            }
+        );
+        assert_eq!(
+            settings.theme.orientation,
+            DisplayOrientation::FlippedVertical
+        );
+        assert_eq!(settings.theme.cell_size, CellSize::HalfBlock);
+        assert!(settings.theme.show_grid && settings.theme.show_spawn_preview);
+        assert_eq!(
+            (
+                settings.base_tick_ms,
+                settings.preview_count,
+                settings.ghost,
+                settings.sound
+            ),
+            (Some(300), Some(5), GhostStyle::DimFill, true)
+        );
+        // it's written back as it's read.
+        let written = serde_json::to_string(&settings).unwrap();
+        assert_eq!(parse(&written).unwrap(), settings);
+
+        let mut options = Options::default();
+        settings.apply(&mut options);
+        assert_eq!(options.keys, settings.keys);
+        assert_eq!(options.base_tick, Some(Duration::from_millis(300)));
+        assert_eq!(options.preview_count, Some(5));
+        assert_eq!(options.theme.ghost, GhostStyle::DimFill);
+        assert!(options.theme.patterns && options.sound);
+    }
+
+    #[test]
+    fn partial_settings() {
+        assert_eq!(parse("{}").unwrap(), Settings::default());
+
+        let settings = parse(r#"{"keys": {"hold": "v"}, "theme": {"show_grid": true}}"#).unwrap();
+        assert_eq!(
+            settings.keys,
+            KeyBindings {
+                hold: Key(KeyCode::Char('v')),
+                ..KeyBindings::default()
+            }
+        );
+        assert_eq!(
+            settings.theme,
+            ThemeSettings {
+                show_grid: true,
+                ..ThemeSettings::default()
+            }
+        );
+        assert_eq!(settings.preview_count, None);
+
+        // the options already set are kept.
+        let mut options = Options {
+            preview_count: Some(2),
+            ..Options::default()
+        };
+        options.theme.ghost = GhostStyle::Outline;
+        Settings {
+            preview_count: Some(4),
+            ghost: GhostStyle::DimFill,
+            ..settings
+        }
+        .apply(&mut options);
+        assert_eq!(options.preview_count, Some(2));
+        assert_eq!(options.theme.ghost, GhostStyle::Outline);
+        assert!(options.theme.show_grid);
+    }
+
+    #[test]
+    fn malformed_settings() {
+        let error = |json| parse(json).unwrap_err().to_string();
+        assert!(error("{").starts_with("settings.json: EOF"));
+        assert!(error(r#"{"sound": 1}"#).starts_with("settings.json: invalid type"));
+        assert!(error(r#"{"keys": {"left": "ctrl"}}"#).contains("unknown key \"ctrl\""));
+        assert!(error(r#"{"colour": "red"}"#).contains("unknown field `colour`"));
+        assert!(matches!(
+            parse(r#"{"preview_count": 0}"#),
+            Err(SettingsError::Invalid {
+                error: InvalidSettings::Config(_),
+                ..
+            })
+        ));
+        // two actions on one key, in either case.
+        assert_eq!(
+            error(r#"{"keys": {"left": "a", "rotate": "A"}}"#),
+            "settings.json: left and rotate are bound to the same key"
+        );
+        assert_eq!(
+            error(r#"{"keys": {"hold": "u"}}"#),
+            "settings.json: hold and undo are bound to the same key"
+        );
+        assert!(matches!(
+            parse(r#"{"keys": {"quit": "7"}}"#),
+            Err(SettingsError::Invalid {
+                error: InvalidSettings::KeyConflict {
+                    first: "quit",
+                    second: "preview 7"
+                },
+                ..
+            })
+        ));
+        assert_eq!(KeyBindings::default().conflict(), None);
+
+        // a missing file isn't an error.
+        let missing = Path::new("/nonexistent/tetris/settings.json");
+        assert_eq!(Settings::load_from(missing).unwrap(), Settings::default());
+    }
+}
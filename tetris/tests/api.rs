@@ -0,0 +1,16 @@
+use tetris::game::{Event, Game, State};
+
+/// The game could be played through the public API of the library only.
+#[test]
+fn play_through_the_library() {
+    let mut g = Game::new((20, 10));
+    assert_eq!(g.state, State::Init);
+    g.handle_event(Event::Start);
+    assert_eq!(g.state, State::Playing);
+    assert!(g.active_piece().is_some());
+
+    g.handle_event(Event::HardDrop);
+    assert_eq!(g.stats().pieces, 1);
+    let display = g.render();
+    assert_eq!((display.rows, display.columns), (20, 10));
+}
@@ -0,0 +1,8 @@
+use tetris::shape;
+
+fn main() {
+    let _ = shape![
+        true, true;
+        true;
+    ];
+}
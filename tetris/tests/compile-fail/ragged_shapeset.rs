@@ -0,0 +1,8 @@
+use tetris::shapeset;
+
+fn main() {
+    let _ = shapeset! {
+        O = "## ##";
+        T = ".#. ##";
+    };
+}
@@ -0,0 +1,7 @@
+use tetris::shapeset;
+
+fn main() {
+    let _ = shapeset! {
+        I = "##x#";
+    };
+}
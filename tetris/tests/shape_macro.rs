@@ -0,0 +1,49 @@
+use tetris::game::PieceKind;
+use tetris::{shape, shapeset};
+
+#[test]
+fn shape_macro() {
+    let s = shape![
+        false, true, false;
+        true, true, true;
+    ];
+    assert_eq!(s.to_string(), ".#.\n###");
+    assert_eq!((s.height(), s.width()), (2, 3));
+}
+
+#[test]
+fn shape2_macro() {
+    // it doesn't need shape! to be imported.
+    use tetris::shape2;
+    let s = shape2! {
+        o__
+        ooo
+    };
+    assert_eq!(s.to_string(), "#..\n###");
+}
+
+#[test]
+fn shapeset_macro() {
+    let shapes = shapeset! {
+        I = "####";
+        T = ".#. ###";
+        S = r"
+            _oo
+            oo_
+        ";
+        O = "##\n##";
+    };
+    let kinds: Vec<_> = shapes.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(
+        kinds,
+        [PieceKind::I, PieceKind::T, PieceKind::S, PieceKind::O]
+    );
+    let shapes: Vec<_> = shapes.iter().map(|(_, s)| s.to_string()).collect();
+    assert_eq!(shapes, ["####", ".#.\n###", ".##\n##.", "##\n##"]);
+    assert!(shapeset! {}.is_empty());
+}
+
+#[test]
+fn ragged_shape_does_not_compile() {
+    trybuild::TestCases::new().compile_fail("tests/compile-fail/*.rs");
+}
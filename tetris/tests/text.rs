@@ -0,0 +1,46 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run the text front-end with the given commands and return the last level
+/// it printed.
+fn play(commands: &str) -> Vec<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tetris"))
+        .args(["--text", "--seed", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let last = stdout.trim_end().rsplit("\n\n").next().unwrap();
+    last.lines().map(String::from).collect()
+}
+
+#[test]
+fn drop_shape_to_the_left() {
+    // move the first shape (a T with this seed) to the wall and drop it to
+    // the floor, the next shape is spawned once it's locked.
+    let commands = format!("{}{}\nq", "a".repeat(8), "s".repeat(21));
+    let level = play(&commands);
+    assert_eq!(level.len(), 22);
+    assert_eq!(level[20], ".#..............");
+    assert_eq!(level[21], "###.............");
+    assert!(level[..2].iter().any(|row| row.contains('#')));
+}
+
+#[test]
+fn rotate_and_move_right() {
+    let commands = format!("w{}{}", "d".repeat(10), "s".repeat(21));
+    let level = play(&commands);
+    assert_eq!(level[19], "..............#.");
+    assert_eq!(level[20], "..............##");
+    assert_eq!(level[21], "..............#.");
+}
@@ -1,6 +1,9 @@
 extern crate proc_macro;
-use proc_macro::{TokenStream};
+use proc_macro::{Span, TokenStream, TokenTree};
 
+/// Return a `Shape` drawn with `o` for the cells and any other character for
+/// the empty ones, e.g. `shape2! { _o_ ooo }`. The rows are separated by
+/// whitespace, the top one first, see `tetris::shape!`.
 #[proc_macro]
 pub fn shape2(body: TokenStream) -> TokenStream {
     let res = body
@@ -20,5 +23,144 @@ pub fn shape2(body: TokenStream) -> TokenStream {
         )
         .collect::<Vec<String>>()
         .join("");
-    format!("shape![{}]",res).parse().unwrap()
+    format!("::tetris::shape![{}]",res).parse().unwrap()
+}
+
+/// Return a `Vec<(PieceKind, Shape)>` of named shapes, e.g.
+/// `shapeset! { I = "####"; T = ".#. ###"; }`. The rows of a shape are
+/// separated by whitespace, the top one first, `#` or `o` is a cell and `.` or
+/// `_` isn't. A shape whose rows don't have the same number of cells, with no
+/// cells or with another character doesn't compile.
+#[proc_macro]
+pub fn shapeset(body: TokenStream) -> TokenStream {
+    match parse_shapeset(body) {
+        Ok(shapes) => {
+            let items: Vec<String> = shapes
+                .iter()
+                .map(|(kind, rows)| {
+                    let cells: String = rows
+                        .iter()
+                        .map(|row| {
+                            let row: Vec<&str> = row
+                                .iter()
+                                .map(|cell| if *cell { "true" } else { "false" })
+                                .collect();
+                            row.join(",") + ";"
+                        })
+                        .collect();
+                    format!(
+                        "(::tetris::game::PieceKind::{}, ::tetris::shape![{}])",
+                        kind, cells
+                    )
+                })
+                .collect();
+            format!(
+                "{{ let shapes: ::std::vec::Vec<(::tetris::game::PieceKind, ::tetris::game::Shape)> = ::std::vec![{}]; shapes }}",
+                items.join(",")
+            )
+            .parse()
+            .unwrap()
+        }
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+/// A named shape of [shapeset], its rows from the top.
+type NamedShape = (String, Vec<Vec<bool>>);
+
+/// Parse the `Kind = "rows";` entries of [shapeset], or return the error and
+/// where it is.
+fn parse_shapeset(body: TokenStream) -> Result<Vec<NamedShape>, (String, Span)> {
+    let mut shapes: Vec<NamedShape> = Vec::new();
+    let mut tokens = body.into_iter();
+    while let Some(token) = tokens.next() {
+        let kind = match token {
+            TokenTree::Ident(kind) => kind,
+            other => {
+                return Err((
+                    "expected the kind of a shape, e.g. `T`".into(),
+                    other.span(),
+                ))
+            }
+        };
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '=' => {}
+            other => {
+                let span = other.map_or(kind.span(), |t| t.span());
+                return Err(("expected `=` after the kind of a shape".into(), span));
+            }
+        }
+        let literal = match tokens.next() {
+            Some(TokenTree::Literal(literal)) => literal,
+            other => {
+                let span = other.map_or(kind.span(), |t| t.span());
+                return Err(("expected the cells of the shape as a string".into(), span));
+            }
+        };
+        let rows = parse_rows(&literal.to_string()).map_err(|e| (e, literal.span()))?;
+        let name = kind.to_string();
+        if shapes.iter().any(|(n, _)| *n == name) {
+            return Err((format!("the shape {} is defined twice", name), kind.span()));
+        }
+        shapes.push((name, rows));
+        match tokens.next() {
+            None => break,
+            Some(TokenTree::Punct(p)) if p.as_char() == ';' => {}
+            Some(other) => return Err(("expected `;` after a shape".into(), other.span())),
+        }
+    }
+    Ok(shapes)
+}
+
+/// Parse the rows of a shape from a string literal as written in the source.
+fn parse_rows(literal: &str) -> Result<Vec<Vec<bool>>, String> {
+    let text = unquote(literal).ok_or_else(|| "expected a string literal".to_string())?;
+    let rows: Vec<Vec<bool>> = text
+        .split_whitespace()
+        .map(|row| {
+            row.chars()
+                .map(|ch| match ch {
+                    '#' | 'o' => Ok(true),
+                    '.' | '_' => Ok(false),
+                    _ => Err(format!("invalid character {:?} in a shape", ch)),
+                })
+                .collect()
+        })
+        .collect::<Result<_, _>>()?;
+    if let Some(row) = rows.iter().position(|row| row.len() != rows[0].len()) {
+        return Err(format!(
+            "row {} of the shape has {} cells but the first one has {}",
+            row,
+            rows[row].len(),
+            rows[0].len()
+        ));
+    }
+    if !rows.iter().flatten().any(|cell| *cell) {
+        return Err("the shape has no cells".to_string());
+    }
+    Ok(rows)
+}
+
+/// Return the text of a string literal, the escaped newlines and tabs of a
+/// normal string are turned into spaces as only the whitespace matters.
+fn unquote(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let text = raw.get(hashes + 1..raw.len().checked_sub(hashes + 1)?)?;
+        return Some(text.to_string());
+    }
+    let text = literal.strip_prefix('"')?.strip_suffix('"')?;
+    Some(text.replace("\\n", " ").replace("\\t", " "))
+}
+
+/// Return a `compile_error!` with `message` at `span`.
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let error: TokenStream = format!("compile_error!({:?})", message).parse().unwrap();
+    error
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
 }